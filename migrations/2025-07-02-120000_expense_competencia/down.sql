@@ -0,0 +1,17 @@
+DROP VIEW despesa_com_deputado;
+
+UPDATE expenses SET data_emissao = competencia WHERE data_emissao IS NULL;
+
+ALTER TABLE expenses DROP COLUMN competencia;
+
+CREATE VIEW despesa_com_deputado AS
+    SELECT expenses.id AS expense_id,
+           expenses.data_emissao,
+           expenses.fornecedor,
+           expenses.valor_liquido,
+           expenses.url_documento,
+           deputados.nome,
+           deputados.cpf,
+           deputados.uf
+    FROM expenses INNER JOIN deputados
+    ON expenses.deputado_id = deputados.id;
@@ -0,0 +1,39 @@
+-- A competência é o mês ao qual a despesa se refere (numMes/numAno), armazenada como o primeiro dia
+-- desse mês. Até aqui, a data de emissão ausente era substituída pelo primeiro dia da competência,
+-- então não é possível distinguir essas datas fabricadas das reais nos registros existentes; elas
+-- são mantidas, e a competência é derivada delas.
+DROP VIEW despesa_com_deputado;
+
+ALTER TABLE expenses ADD COLUMN competencia DATE;
+
+UPDATE expenses SET competencia = date_trunc('month', data_emissao)::date;
+
+-- Sem a data de emissão, não há de onde derivar a competência: a tabela não guarda numMes/numAno.
+-- Essas despesas precisam ser corrigidas, ou removidas e reimportadas, antes desta migração.
+DO $$
+DECLARE
+    sem_data BIGINT;
+BEGIN
+    SELECT count(*) INTO sem_data FROM expenses WHERE competencia IS NULL;
+    IF sem_data > 0 THEN
+        RAISE EXCEPTION '% expenses have no data_emissao to derive competencia from; fix or delete them before migrating', sem_data;
+    END IF;
+END
+$$;
+
+ALTER TABLE expenses
+    ALTER COLUMN competencia SET NOT NULL,
+    ADD CONSTRAINT competencia_primeiro_dia_do_mes CHECK (extract(day FROM competencia) = 1);
+
+CREATE VIEW despesa_com_deputado AS
+    SELECT expenses.id AS expense_id,
+           expenses.data_emissao,
+           expenses.competencia,
+           expenses.fornecedor,
+           expenses.valor_liquido,
+           expenses.url_documento,
+           deputados.nome,
+           deputados.cpf,
+           deputados.uf
+    FROM expenses INNER JOIN deputados
+    ON expenses.deputado_id = deputados.id;
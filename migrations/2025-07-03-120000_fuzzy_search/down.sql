@@ -0,0 +1,5 @@
+DROP INDEX deputados_nome_trgm_idx;
+DROP INDEX expenses_fornecedor_trgm_idx;
+DROP FUNCTION f_unaccent(text);
+DROP EXTENSION unaccent;
+DROP EXTENSION pg_trgm;
@@ -0,0 +1,14 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE EXTENSION IF NOT EXISTS unaccent;
+
+-- unaccent() não é IMMUTABLE, pois depende do dicionário configurado, e por isso não pode ser usada
+-- em índices; este wrapper fixa o dicionário.
+CREATE OR REPLACE FUNCTION f_unaccent(text) RETURNS text AS $$
+    SELECT public.unaccent('public.unaccent', $1)
+$$ LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT;
+
+CREATE INDEX expenses_fornecedor_trgm_idx
+    ON expenses USING gin (f_unaccent(lower(fornecedor)) gin_trgm_ops);
+
+CREATE INDEX deputados_nome_trgm_idx
+    ON deputados USING gin (f_unaccent(lower(nome)) gin_trgm_ops);
@@ -0,0 +1,42 @@
+-- Documento (CNPJ ou CPF) do fornecedor, como informado em txtCNPJCPF, apenas com os dígitos.
+ALTER TABLE expenses ADD COLUMN fornecedor_documento VARCHAR;
+
+-- Grupos de variantes do nome de um mesmo fornecedor ("TAM", "TAM LINHAS AEREAS S/A"), formados
+-- pelo job de resolução de fornecedores ou manualmente. Grupos e variantes marcados como manuais
+-- são preservados quando o job é executado novamente.
+CREATE TABLE grupos_fornecedores (
+    id SERIAL PRIMARY KEY,
+    nome VARCHAR NOT NULL,
+    documento VARCHAR,
+    confianca REAL NOT NULL,
+    manual BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE fornecedores_agrupados (
+    fornecedor VARCHAR PRIMARY KEY,
+    grupo_id INTEGER NOT NULL REFERENCES grupos_fornecedores(id) ON DELETE CASCADE,
+    confianca REAL NOT NULL,
+    manual BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX fornecedores_agrupados_grupo_id_idx ON fornecedores_agrupados (grupo_id);
+
+DROP VIEW despesa_com_deputado;
+
+CREATE VIEW despesa_com_deputado AS
+    SELECT expenses.id AS expense_id,
+           expenses.data_emissao,
+           expenses.competencia,
+           expenses.fornecedor,
+           expenses.valor_liquido,
+           expenses.url_documento,
+           deputados.nome,
+           deputados.cpf,
+           deputados.uf,
+           expenses.fornecedor_documento,
+           fornecedores_agrupados.grupo_id AS grupo_fornecedor_id,
+           COALESCE(grupos_fornecedores.nome, expenses.fornecedor) AS fornecedor_canonico
+    FROM expenses
+    INNER JOIN deputados ON expenses.deputado_id = deputados.id
+    LEFT JOIN fornecedores_agrupados ON expenses.fornecedor = fornecedores_agrupados.fornecedor
+    LEFT JOIN grupos_fornecedores ON fornecedores_agrupados.grupo_id = grupos_fornecedores.id;
@@ -0,0 +1,23 @@
+DROP VIEW despesa_com_deputado;
+
+CREATE VIEW despesa_com_deputado AS
+    SELECT expenses.id AS expense_id,
+           expenses.data_emissao,
+           expenses.competencia,
+           expenses.fornecedor,
+           expenses.valor_liquido,
+           expenses.url_documento,
+           deputados.nome,
+           deputados.cpf,
+           deputados.uf,
+           expenses.fornecedor_documento,
+           fornecedores_agrupados.grupo_id AS grupo_fornecedor_id,
+           COALESCE(grupos_fornecedores.nome, expenses.fornecedor) AS fornecedor_canonico
+    FROM expenses
+    INNER JOIN deputados ON expenses.deputado_id = deputados.id
+    LEFT JOIN fornecedores_agrupados ON expenses.fornecedor = fornecedores_agrupados.fornecedor
+    LEFT JOIN grupos_fornecedores ON fornecedores_agrupados.grupo_id = grupos_fornecedores.id;
+
+DROP TABLE resumo_despesas_estado;
+DROP TABLE resumo_despesas;
+ALTER TABLE expenses DROP COLUMN categoria;
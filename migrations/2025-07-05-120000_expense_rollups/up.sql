@@ -0,0 +1,54 @@
+-- Categoria da despesa (txtDescricao). As despesas importadas antes desta migração não a tinham.
+ALTER TABLE expenses ADD COLUMN categoria VARCHAR NOT NULL DEFAULT 'NÃO INFORMADA';
+ALTER TABLE expenses ALTER COLUMN categoria DROP DEFAULT;
+
+-- Totais das despesas por deputado, competência e categoria, dos quais os totais por deputado, UF,
+-- mês e categoria são obtidos sem percorrer as despesas. São recalculados ao fim de cada
+-- importação, apenas para os deputados e competências importados.
+CREATE TABLE resumo_despesas (
+    deputado_id INTEGER NOT NULL REFERENCES deputados(id),
+    uf CHAR(2) NOT NULL,
+    competencia DATE NOT NULL,
+    categoria VARCHAR NOT NULL,
+    total NUMERIC(16, 2) NOT NULL,
+    quantidade BIGINT NOT NULL,
+    PRIMARY KEY (deputado_id, competencia, categoria)
+);
+
+CREATE INDEX resumo_despesas_uf_idx ON resumo_despesas (uf);
+CREATE INDEX resumo_despesas_competencia_idx ON resumo_despesas (competencia);
+
+-- Momento da última atualização dos totais; a tabela tem sempre uma única linha.
+CREATE TABLE resumo_despesas_estado (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    atualizado_em TIMESTAMP NOT NULL
+);
+
+INSERT INTO resumo_despesas (deputado_id, uf, competencia, categoria, total, quantidade)
+    SELECT expenses.deputado_id, deputados.uf, expenses.competencia, expenses.categoria,
+           sum(expenses.valor_liquido), count(*)
+    FROM expenses INNER JOIN deputados ON expenses.deputado_id = deputados.id
+    GROUP BY expenses.deputado_id, deputados.uf, expenses.competencia, expenses.categoria;
+
+INSERT INTO resumo_despesas_estado (id, atualizado_em) VALUES (1, now());
+
+DROP VIEW despesa_com_deputado;
+
+CREATE VIEW despesa_com_deputado AS
+    SELECT expenses.id AS expense_id,
+           expenses.data_emissao,
+           expenses.competencia,
+           expenses.fornecedor,
+           expenses.valor_liquido,
+           expenses.url_documento,
+           deputados.nome,
+           deputados.cpf,
+           deputados.uf,
+           expenses.fornecedor_documento,
+           fornecedores_agrupados.grupo_id AS grupo_fornecedor_id,
+           COALESCE(grupos_fornecedores.nome, expenses.fornecedor) AS fornecedor_canonico,
+           expenses.categoria
+    FROM expenses
+    INNER JOIN deputados ON expenses.deputado_id = deputados.id
+    LEFT JOIN fornecedores_agrupados ON expenses.fornecedor = fornecedores_agrupados.fornecedor
+    LEFT JOIN grupos_fornecedores ON fornecedores_agrupados.grupo_id = grupos_fornecedores.id;
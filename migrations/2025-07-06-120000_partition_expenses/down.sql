@@ -0,0 +1,49 @@
+DROP VIEW despesa_com_deputado;
+
+ALTER TABLE expenses RENAME TO expenses_particionadas;
+ALTER TABLE expenses_particionadas RENAME CONSTRAINT competencia_primeiro_dia_do_mes TO competencia_primeiro_dia_do_mes_particionadas;
+ALTER TABLE expenses_particionadas RENAME CONSTRAINT expenses_deputado_id_fkey TO expenses_particionadas_deputado_id_fkey;
+ALTER INDEX expenses_fornecedor_trgm_idx RENAME TO expenses_particionadas_fornecedor_trgm_idx;
+ALTER INDEX expenses_pkey RENAME TO expenses_particionadas_pkey;
+
+CREATE TABLE expenses (
+    id INTEGER PRIMARY KEY DEFAULT nextval('expenses_id_seq'),
+    data_emissao TIMESTAMP,
+    fornecedor VARCHAR NOT NULL,
+    valor_liquido NUMERIC(14, 2) NOT NULL,
+    url_documento VARCHAR,
+    deputado_id INTEGER NOT NULL CONSTRAINT expenses_deputado_id_fkey REFERENCES deputados(id),
+    competencia DATE NOT NULL CONSTRAINT competencia_primeiro_dia_do_mes CHECK (extract(day FROM competencia) = 1),
+    fornecedor_documento VARCHAR,
+    categoria VARCHAR NOT NULL
+);
+
+ALTER SEQUENCE expenses_id_seq OWNED BY expenses.id;
+
+INSERT INTO expenses SELECT * FROM expenses_particionadas;
+
+DROP TABLE expenses_particionadas;
+DROP FUNCTION cria_particao_despesas(INTEGER);
+DROP FUNCTION remove_particao_despesas(INTEGER);
+
+CREATE INDEX expenses_fornecedor_trgm_idx
+    ON expenses USING gin (f_unaccent(lower(fornecedor)) gin_trgm_ops);
+
+CREATE VIEW despesa_com_deputado AS
+    SELECT expenses.id AS expense_id,
+           expenses.data_emissao,
+           expenses.competencia,
+           expenses.fornecedor,
+           expenses.valor_liquido,
+           expenses.url_documento,
+           deputados.nome,
+           deputados.cpf,
+           deputados.uf,
+           expenses.fornecedor_documento,
+           fornecedores_agrupados.grupo_id AS grupo_fornecedor_id,
+           COALESCE(grupos_fornecedores.nome, expenses.fornecedor) AS fornecedor_canonico,
+           expenses.categoria
+    FROM expenses
+    INNER JOIN deputados ON expenses.deputado_id = deputados.id
+    LEFT JOIN fornecedores_agrupados ON expenses.fornecedor = fornecedores_agrupados.fornecedor
+    LEFT JOIN grupos_fornecedores ON fornecedores_agrupados.grupo_id = grupos_fornecedores.id;
@@ -0,0 +1,98 @@
+-- As despesas passam a ser particionadas pelo ano da competência, já que a data de emissão pode
+-- estar ausente. Cada ano fica em sua própria partição, expenses_{ano}; o importador cria as
+-- partições que faltarem, e a remoção das despesas de um ano descarta a partição inteira.
+DROP VIEW despesa_com_deputado;
+
+ALTER TABLE expenses RENAME TO expenses_antigas;
+ALTER TABLE expenses_antigas RENAME CONSTRAINT competencia_primeiro_dia_do_mes TO competencia_primeiro_dia_do_mes_antigas;
+ALTER TABLE expenses_antigas RENAME CONSTRAINT expenses_deputado_id_fkey TO expenses_antigas_deputado_id_fkey;
+ALTER INDEX expenses_fornecedor_trgm_idx RENAME TO expenses_antigas_fornecedor_trgm_idx;
+ALTER INDEX expenses_pkey RENAME TO expenses_antigas_pkey;
+
+-- A chave primária de uma tabela particionada deve conter a coluna de particionamento.
+CREATE TABLE expenses (
+    id INTEGER NOT NULL DEFAULT nextval('expenses_id_seq'),
+    data_emissao TIMESTAMP,
+    fornecedor VARCHAR NOT NULL,
+    valor_liquido NUMERIC(14, 2) NOT NULL,
+    url_documento VARCHAR,
+    deputado_id INTEGER NOT NULL CONSTRAINT expenses_deputado_id_fkey REFERENCES deputados(id),
+    competencia DATE NOT NULL CONSTRAINT competencia_primeiro_dia_do_mes CHECK (extract(day FROM competencia) = 1),
+    fornecedor_documento VARCHAR,
+    categoria VARCHAR NOT NULL,
+    PRIMARY KEY (id, competencia)
+) PARTITION BY RANGE (competencia);
+
+ALTER SEQUENCE expenses_id_seq OWNED BY expenses.id;
+
+CREATE INDEX expenses_fornecedor_trgm_idx
+    ON expenses USING gin (f_unaccent(lower(fornecedor)) gin_trgm_ops);
+CREATE INDEX expenses_deputado_id_competencia_idx ON expenses (deputado_id, competencia);
+
+-- Cria a partição das despesas com competência no ano informado, caso ainda não exista. Retorna
+-- verdadeiro caso a partição tenha sido criada.
+CREATE FUNCTION cria_particao_despesas(ano INTEGER) RETURNS BOOLEAN AS $$
+BEGIN
+    IF to_regclass(format('expenses_%s', ano)) IS NOT NULL THEN
+        RETURN FALSE;
+    END IF;
+
+    EXECUTE format(
+        'CREATE TABLE %I PARTITION OF expenses FOR VALUES FROM (%L) TO (%L)',
+        format('expenses_%s', ano),
+        make_date(ano, 1, 1),
+        make_date(ano + 1, 1, 1)
+    );
+    RETURN TRUE;
+END
+$$ LANGUAGE plpgsql;
+
+-- Descarta a partição das despesas com competência no ano informado, retornando o número de
+-- despesas removidas, ou NULL caso a partição não exista.
+CREATE FUNCTION remove_particao_despesas(ano INTEGER) RETURNS BIGINT AS $$
+DECLARE
+    removidas BIGINT;
+BEGIN
+    IF to_regclass(format('expenses_%s', ano)) IS NULL THEN
+        RETURN NULL;
+    END IF;
+
+    EXECUTE format('SELECT count(*) FROM %I', format('expenses_%s', ano)) INTO removidas;
+    EXECUTE format('DROP TABLE %I', format('expenses_%s', ano));
+    RETURN removidas;
+END
+$$ LANGUAGE plpgsql;
+
+-- Cria as partições dos anos da CEAP até o atual, além das dos anos presentes nas despesas.
+SELECT cria_particao_despesas(ano)
+FROM generate_series(2008, extract(year FROM now())::integer) AS ano;
+
+SELECT cria_particao_despesas(ano)
+FROM (SELECT DISTINCT extract(year FROM competencia)::integer AS ano FROM expenses_antigas) AS anos;
+
+INSERT INTO expenses (id, data_emissao, fornecedor, valor_liquido, url_documento, deputado_id,
+                      competencia, fornecedor_documento, categoria)
+    SELECT id, data_emissao, fornecedor, valor_liquido, url_documento, deputado_id,
+           competencia, fornecedor_documento, categoria
+    FROM expenses_antigas;
+
+DROP TABLE expenses_antigas;
+
+CREATE VIEW despesa_com_deputado AS
+    SELECT expenses.id AS expense_id,
+           expenses.data_emissao,
+           expenses.competencia,
+           expenses.fornecedor,
+           expenses.valor_liquido,
+           expenses.url_documento,
+           deputados.nome,
+           deputados.cpf,
+           deputados.uf,
+           expenses.fornecedor_documento,
+           fornecedores_agrupados.grupo_id AS grupo_fornecedor_id,
+           COALESCE(grupos_fornecedores.nome, expenses.fornecedor) AS fornecedor_canonico,
+           expenses.categoria
+    FROM expenses
+    INNER JOIN deputados ON expenses.deputado_id = deputados.id
+    LEFT JOIN fornecedores_agrupados ON expenses.fornecedor = fornecedores_agrupados.fornecedor
+    LEFT JOIN grupos_fornecedores ON fornecedores_agrupados.grupo_id = grupos_fornecedores.id;
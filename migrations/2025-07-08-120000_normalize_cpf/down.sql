@@ -0,0 +1,3 @@
+-- Esta migração é irreversível: a forma original dos CPFs e os deputados duplicados, unidos aos
+-- mais antigos, não são guardados, e por isso não podem ser restaurados. O down não faz nada.
+SELECT 1;
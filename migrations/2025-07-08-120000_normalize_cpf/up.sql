@@ -0,0 +1,71 @@
+-- Os CPFs passam a ser armazenados na forma canônica, com 11 dígitos. CPFs importados sem os zeros
+-- à esquerda, ou formatados, podem ter criado mais de um deputado para a mesma pessoa; nesse caso, é
+-- mantido o deputado mais antigo, que recebe as despesas dos demais.
+--
+-- Um CPF com menos de 11 dígitos só é completado com zeros à esquerda caso o resultado seja um CPF
+-- válido; caso contrário, é completado com zeros à direita, caso esse seja válido, e, se nenhum dos
+-- dois for, é mantido como está, sem ser unido a outros deputados, e informado num aviso.
+CREATE FUNCTION pg_temp.cpf_valido(cpf text) RETURNS boolean AS $$
+    SELECT CASE
+        WHEN cpf !~ '^[0-9]{11}$' OR cpf ~ '^(.)\1{10}$' THEN false
+        ELSE (SELECT sum(substr(cpf, i, 1)::int * (11 - i)) * 10 % 11 % 10 FROM generate_series(1, 9) AS i) = substr(cpf, 10, 1)::int
+            AND (SELECT sum(substr(cpf, i, 1)::int * (12 - i)) * 10 % 11 % 10 FROM generate_series(1, 10) AS i) = substr(cpf, 11, 1)::int
+    END
+$$ LANGUAGE sql IMMUTABLE;
+
+CREATE TEMPORARY TABLE cpfs_canonicos AS
+    WITH digitos AS (
+        SELECT id, cpf, regexp_replace(cpf, '[^0-9]', '', 'g') AS digitos FROM deputados
+    ), canonicos AS (
+        SELECT id,
+               CASE
+                   WHEN length(digitos) = 11 THEN digitos
+                   WHEN length(digitos) < 11 AND pg_temp.cpf_valido(lpad(digitos, 11, '0')) THEN lpad(digitos, 11, '0')
+                   WHEN length(digitos) < 11 AND pg_temp.cpf_valido(rpad(digitos, 11, '0')) THEN rpad(digitos, 11, '0')
+                   ELSE cpf
+               END AS cpf
+        FROM digitos
+    )
+    SELECT id, cpf, min(id) OVER (PARTITION BY cpf) AS mantido
+    FROM canonicos;
+
+DO $$
+DECLARE
+    invalidos text;
+BEGIN
+    SELECT string_agg(format('%s (%s)', id, cpf), ', ' ORDER BY id) INTO invalidos
+        FROM cpfs_canonicos
+        WHERE cpf !~ '^[0-9]{11}$';
+
+    IF invalidos IS NOT NULL THEN
+        RAISE WARNING 'Deputados com CPF que não pôde ser normalizado, mantidos como estão: %', invalidos;
+    END IF;
+END
+$$;
+
+UPDATE expenses SET deputado_id = cpfs_canonicos.mantido
+    FROM cpfs_canonicos
+    WHERE expenses.deputado_id = cpfs_canonicos.id AND cpfs_canonicos.id <> cpfs_canonicos.mantido;
+
+-- Os totais dos deputados unidos são recalculados.
+DELETE FROM resumo_despesas
+    WHERE deputado_id IN (SELECT id FROM cpfs_canonicos WHERE mantido IN (SELECT mantido FROM cpfs_canonicos WHERE id <> mantido));
+
+INSERT INTO resumo_despesas (deputado_id, uf, competencia, categoria, total, quantidade)
+    SELECT expenses.deputado_id, deputados.uf, expenses.competencia, expenses.categoria,
+           sum(expenses.valor_liquido), count(*)
+    FROM expenses INNER JOIN deputados ON expenses.deputado_id = deputados.id
+    WHERE expenses.deputado_id IN (SELECT mantido FROM cpfs_canonicos WHERE id <> mantido)
+    GROUP BY expenses.deputado_id, deputados.uf, expenses.competencia, expenses.categoria;
+
+DELETE FROM deputados
+    WHERE id IN (SELECT id FROM cpfs_canonicos WHERE id <> mantido);
+
+UPDATE deputados SET cpf = cpfs_canonicos.cpf
+    FROM cpfs_canonicos
+    WHERE deputados.id = cpfs_canonicos.id AND deputados.cpf <> cpfs_canonicos.cpf;
+
+UPDATE resumo_despesas_estado SET atualizado_em = now();
+
+DROP TABLE cpfs_canonicos;
+DROP FUNCTION pg_temp.cpf_valido(text);
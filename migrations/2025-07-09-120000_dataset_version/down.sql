@@ -0,0 +1 @@
+DROP TABLE versao_dados;
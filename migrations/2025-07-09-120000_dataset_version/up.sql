@@ -0,0 +1,11 @@
+-- Versão dos dados, incrementada a cada importação e a cada alteração administrativa das despesas
+-- ou dos grupos de fornecedores, na mesma transação da alteração. Identifica, nos cabeçalhos ETag e
+-- Last-Modified, o estado dos dados a partir do qual uma resposta foi gerada; atualizado_em está em
+-- UTC. A tabela tem sempre uma única linha.
+CREATE TABLE versao_dados (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    versao BIGINT NOT NULL,
+    atualizado_em TIMESTAMP NOT NULL
+);
+
+INSERT INTO versao_dados (id, versao, atualizado_em) VALUES (1, 1, timezone('utc', now()));
@@ -0,0 +1,40 @@
+use actix_web::http::header::{self, HeaderMap};
+use subtle::ConstantTimeEq;
+
+// Token informado no cabeçalho `Authorization: Bearer {token}`.
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+// Compara o token informado com um token válido em tempo constante, para que o tempo da
+// comparação não revele quantos caracteres do início estão corretos.
+pub fn token_valido(informado: &str, esperado: &str) -> bool {
+    informado.as_bytes().ct_eq(esperado.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn compara_tokens() {
+        assert!(token_valido("segredo", "segredo"));
+        assert!(!token_valido("segredx", "segredo"));
+        assert!(!token_valido("segred", "segredo"));
+        assert!(!token_valido("", "segredo"));
+    }
+
+    #[actix_web::test]
+    async fn le_o_token_do_cabecalho() {
+        let req = test::TestRequest::default().insert_header(("Authorization", "Bearer segredo")).to_http_request();
+        assert_eq!(bearer_token(req.headers()), Some("segredo"));
+
+        let req = test::TestRequest::default().insert_header(("Authorization", "Basic segredo")).to_http_request();
+        assert_eq!(bearer_token(req.headers()), None);
+    }
+}
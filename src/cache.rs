@@ -0,0 +1,347 @@
+use std::{collections::{HashMap, VecDeque}, hash::{DefaultHasher, Hash, Hasher}, str::FromStr, sync::Mutex, time::{Duration, SystemTime}};
+
+use actix_web::{body::{self, BodySize, BoxBody, MessageBody}, dev::{ServiceRequest, ServiceResponse}, error::ErrorInternalServerError, http::{header::{self, HeaderMap, HeaderName, HeaderValue, HttpDate}, Method, StatusCode}, middleware::Next, web::{self, Bytes}, Error, HttpRequest, HttpResponse};
+use anyhow::Context;
+use diesel::{r2d2::ConnectionManager, PgConnection};
+use r2d2::Pool;
+
+use crate::models::VersaoDados;
+
+// Os dados só mudam com as importações e as alterações administrativas, que incrementam a versão
+// dos dados (veja VersaoDados). As respostas dos endpoints de leitura são identificadas pela versão:
+// levam um ETag, formado pela versão e pela requisição, e o Last-Modified da versão; requisições
+// com If-None-Match ou If-Modified-Since correspondentes recebem 304, sem que a resposta seja
+// gerada novamente. As respostas são ainda guardadas em memória, e reaproveitadas enquanto a versão
+// não muda; a primeira requisição que encontra uma versão nova descarta todas as anteriores.
+//
+// Ficam de fora as requisições autenticadas, cujas respostas podem depender de quem as faz, e os
+// demais endpoints, como os uploads e a documentação, cujas respostas não dependem da versão dos
+// dados; veja endpoint_de_leitura.
+
+// Número máximo de respostas guardadas, tamanho máximo de cada uma e tamanho máximo do conjunto;
+// as respostas transmitidas aos poucos, como as exportações das despesas, nunca são guardadas.
+const MAX_ENTRIES: usize = 1000;
+const MAX_ENTRY_SIZE: u64 = 1024 * 1024;
+const MAX_SIZE: usize = 64 * 1024 * 1024;
+
+// Informa se a resposta veio do cache (HIT) ou foi gerada (MISS).
+const X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+
+#[derive(Default)]
+pub struct ResponseCache {
+    estado: Mutex<Estado>,
+}
+
+#[derive(Default)]
+struct Estado {
+    versao: i64,
+    respostas: HashMap<String, Resposta>,
+    // Chaves das respostas na ordem em que foram guardadas; as mais antigas são descartadas
+    // primeiro.
+    ordem: VecDeque<String>,
+    bytes: usize,
+}
+
+#[derive(Clone)]
+struct Resposta {
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl ResponseCache {
+    fn get(&self, versao: i64, chave: &str) -> Option<Resposta> {
+        let estado = self.estado.lock().unwrap();
+
+        if estado.versao == versao { estado.respostas.get(chave).cloned() } else { None }
+    }
+
+    fn insert(&self, versao: i64, chave: String, resposta: Resposta) {
+        let mut estado = self.estado.lock().unwrap();
+
+        // Uma resposta de uma versão anterior, gerada enquanto os dados eram alterados, é
+        // descartada; uma de uma versão posterior invalida as demais.
+        if versao < estado.versao || estado.respostas.contains_key(&chave) {
+            return;
+        }
+        if versao > estado.versao {
+            *estado = Estado { versao, ..Default::default() };
+        }
+
+        estado.bytes += resposta.body.len();
+        estado.ordem.push_back(chave.clone());
+        estado.respostas.insert(chave, resposta);
+
+        while estado.respostas.len() > MAX_ENTRIES || estado.bytes > MAX_SIZE {
+            let Some(chave) = estado.ordem.pop_front() else { break };
+            if let Some(resposta) = estado.respostas.remove(&chave) {
+                estado.bytes -= resposta.body.len();
+            }
+        }
+    }
+}
+
+// Identificação de uma versão dos dados nos cabeçalhos das respostas.
+struct Validadores {
+    etag: String,
+    modificacao: SystemTime,
+}
+
+impl Validadores {
+    // O ETag distingue, numa mesma versão, as respostas a URLs e a cabeçalhos Accept diferentes.
+    fn new(versao: &VersaoDados, chave: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        chave.hash(&mut hasher);
+
+        // Last-Modified tem a precisão de segundos.
+        let segundos = versao.atualizado_em.and_utc().timestamp().max(0) as u64;
+
+        Self {
+            etag: format!("\"{}-{:016x}\"", versao.versao, hasher.finish()),
+            modificacao: SystemTime::UNIX_EPOCH + Duration::from_secs(segundos),
+        }
+    }
+
+    fn apply(&self, headers: &mut HeaderMap) {
+        headers.insert(header::ETAG, HeaderValue::from_str(&self.etag).unwrap());
+        headers.insert(header::LAST_MODIFIED, HeaderValue::from_str(&HttpDate::from(self.modificacao).to_string()).unwrap());
+        // O cliente pode guardar a resposta, mas deve revalidá-la antes de reaproveitá-la.
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        headers.append(header::VARY, HeaderValue::from_static("Accept"));
+    }
+
+    // Verifica as condições da requisição: If-None-Match, caso presente, ou If-Modified-Since.
+    fn not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(valor) = headers.get(header::IF_NONE_MATCH) {
+            return valor.to_str().is_ok_and(|valor| {
+                valor.split(',')
+                    .map(str::trim)
+                    .any(|etag| etag == "*" || etag.trim_start_matches("W/") == self.etag)
+            });
+        }
+
+        headers.get(header::IF_MODIFIED_SINCE)
+            .and_then(|valor| valor.to_str().ok())
+            .and_then(|valor| HttpDate::from_str(valor).ok())
+            .is_some_and(|data| self.modificacao <= SystemTime::from(data))
+    }
+}
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+// Recursos dos endpoints de leitura, cujas respostas dependem apenas da versão dos dados; a
+// documentação, o GraphiQL e os uploads ficam de fora.
+const RECURSOS: [&str; 5] = ["busca", "deputados", "despesas", "fornecedores", "ranking"];
+
+// Verifica se o caminho, com ou sem o prefixo da versão da API, é de um endpoint de leitura.
+fn endpoint_de_leitura(caminho: &str) -> bool {
+    let caminho = ["/v1", "/v2"].into_iter()
+        .find_map(|prefixo| caminho.strip_prefix(prefixo).filter(|resto| resto.starts_with('/')))
+        .unwrap_or(caminho);
+    let recurso = caminho.trim_start_matches('/').split('/').next().unwrap_or_default();
+
+    RECURSOS.contains(&recurso)
+}
+
+// Pool e cache da aplicação, caso a requisição seja atendida pelo cache.
+fn contexto(req: &ServiceRequest) -> Option<(web::Data<DbPool>, web::Data<ResponseCache>)> {
+    if req.method() != Method::GET || req.headers().contains_key(header::AUTHORIZATION) || !endpoint_de_leitura(req.path()) {
+        return None;
+    }
+
+    Some((req.app_data::<web::Data<DbPool>>()?.clone(), req.app_data::<web::Data<ResponseCache>>()?.clone()))
+}
+
+fn nao_modificada(req: HttpRequest, validadores: &Validadores) -> ServiceResponse<BoxBody> {
+    let mut resposta = HttpResponse::NotModified().finish();
+    validadores.apply(resposta.headers_mut());
+
+    ServiceResponse::new(req, resposta)
+}
+
+pub async fn middleware(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, Error> {
+    let Some((pool, cache)) = contexto(&req) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    let versao = web::block(move || {
+        let connection = &mut pool.get().with_context(|| "database error")?;
+
+        VersaoDados::get(connection).with_context(|| "database error")
+    })
+    .await;
+    // Sem a versão, a requisição é atendida normalmente, sem o cache.
+    let versao = match versao {
+        Ok(Ok(versao)) => versao,
+        Ok(Err(e)) => {
+            log::warn!("failed to read the dataset version: {e:#}");
+            return Ok(next.call(req).await?.map_into_boxed_body());
+        },
+        Err(e) => {
+            log::warn!("failed to read the dataset version: {e}");
+            return Ok(next.call(req).await?.map_into_boxed_body());
+        },
+    };
+
+    let accept = req.headers().get(header::ACCEPT).and_then(|accept| accept.to_str().ok()).unwrap_or_default();
+    let chave = format!("{}\n{}", req.uri(), accept);
+    let validadores = Validadores::new(&versao, &chave);
+    // O 304 só é enviado quando a resposta existe, isto é, quando está no cache ou quando o
+    // handler responde 200; uma condição satisfeita não encobre, por exemplo, um 404.
+    let condicional = validadores.not_modified(req.headers());
+
+    if let Some(guardada) = cache.get(versao.versao, &chave) {
+        if condicional {
+            return Ok(nao_modificada(req.into_parts().0, &validadores));
+        }
+
+        let mut resposta = HttpResponse::Ok().body(guardada.body);
+        *resposta.headers_mut() = guardada.headers;
+        resposta.headers_mut().insert(X_CACHE, HeaderValue::from_static("HIT"));
+
+        return Ok(req.into_response(resposta));
+    }
+
+    let mut res = next.call(req).await?;
+    if res.status() != StatusCode::OK {
+        return Ok(res.map_into_boxed_body());
+    }
+    validadores.apply(res.headers_mut());
+
+    match res.response().body().size() {
+        BodySize::Sized(tamanho) if tamanho <= MAX_ENTRY_SIZE => {
+            let (req, res) = res.into_parts();
+            let (mut res, corpo) = res.into_parts();
+            let corpo = body::to_bytes(corpo).await.map_err(|e| ErrorInternalServerError(e.into()))?;
+
+            cache.insert(versao.versao, chave, Resposta { headers: res.headers().clone(), body: corpo.clone() });
+            if condicional {
+                return Ok(nao_modificada(req, &validadores));
+            }
+            res.headers_mut().insert(X_CACHE, HeaderValue::from_static("MISS"));
+
+            Ok(ServiceResponse::new(req, res.set_body(corpo)).map_into_boxed_body())
+        },
+        _ if condicional => Ok(nao_modificada(res.into_parts().0, &validadores)),
+        _ => {
+            res.headers_mut().insert(X_CACHE, HeaderValue::from_static("MISS"));
+
+            Ok(res.map_into_boxed_body())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{middleware::from_fn, test, App};
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::routes::{perfil_deputado, soma_despesas, soma_todas_as_despesas, tests::build_test_connection_pool};
+
+    fn get_csv(valor: &str) -> String {
+        format!("txNomeParlamentar;cpf;ideCadastro;nuCarteiraParlamentar;nuLegislatura;sgUF;sgPartido;codLegislatura;numSubCota;txtDescricao;numEspecificacaoSubCota;txtDescricaoEspecificacao;txtFornecedor;txtCNPJCPF;txtNumero;indTipoDocumento;datEmissao;vlrDocumento;vlrGlosa;vlrLiquido;numMes;numAno;numParcela;txtPassageiro;txtTrecho;numLote;numRessarcimento;datPagamentoRestituicao;vlrRestituicao;nuDeputadoId;ideDocumento;urlDocumento
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;2025-01-10T00:00:00;1467;0;{valor};1;2025;0;;;0;;;;0;0;")
+    }
+
+    #[actix_web::test]
+    async fn compare_validators() {
+        let versao = VersaoDados { versao: 3, atualizado_em: NaiveDate::from_ymd_opt(2025, 7, 9).unwrap().and_hms_opt(12, 0, 0).unwrap() };
+        let validadores = Validadores::new(&versao, "/despesas/soma\n");
+        assert!(validadores.etag.starts_with("\"3-"));
+        assert_ne!(validadores.etag, Validadores::new(&versao, "/despesas/soma\ntext/csv").etag);
+
+        let condicao = |nome, valor: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(nome, HeaderValue::from_str(valor).unwrap());
+            validadores.not_modified(&headers)
+        };
+        assert!(condicao(header::IF_NONE_MATCH, &validadores.etag));
+        assert!(condicao(header::IF_NONE_MATCH, &format!("\"outra\", W/{}", validadores.etag)));
+        assert!(condicao(header::IF_NONE_MATCH, "*"));
+        assert!(!condicao(header::IF_NONE_MATCH, "\"2-0000000000000000\""));
+        assert!(condicao(header::IF_MODIFIED_SINCE, "Wed, 09 Jul 2025 12:00:00 GMT"));
+        assert!(!condicao(header::IF_MODIFIED_SINCE, "Wed, 09 Jul 2025 11:59:59 GMT"));
+        assert!(!condicao(header::IF_MODIFIED_SINCE, "ontem"));
+        assert!(!validadores.not_modified(&HeaderMap::new()));
+    }
+
+    #[actix_web::test]
+    async fn cache_only_the_data_endpoints() {
+        for caminho in ["/despesas/soma", "/v1/deputados/1", "/v2/fornecedores", "/busca", "/ranking/deputados"] {
+            assert!(endpoint_de_leitura(caminho), "{caminho}");
+        }
+        for caminho in ["/uploads/1", "/v1/uploads/1", "/v2/uploads/1", "/docs/", "/openapi.json", "/graphql", "/v2docs", "/v3/despesas"] {
+            assert!(!endpoint_de_leitura(caminho), "{caminho}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn revalidate_and_cache_until_the_next_import() {
+        let pool = build_test_connection_pool().unwrap();
+        crate::import::process_csv(&mut pool.get().unwrap(), get_csv("100").as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(middleware))
+                .service(soma_todas_as_despesas)
+                .service(soma_despesas)
+                .service(perfil_deputado)
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(ResponseCache::default()))
+        ).await;
+
+        let req = test::TestRequest::get().uri("/despesas/soma").to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(X_CACHE).unwrap(), "MISS");
+        assert_eq!(response.headers().get(header::CACHE_CONTROL).unwrap(), "no-cache");
+        let etag = response.headers().get(header::ETAG).unwrap().clone();
+        let modificacao = response.headers().get(header::LAST_MODIFIED).unwrap().clone();
+        let corpo = test::read_body(response).await;
+
+        let req = test::TestRequest::get().uri("/despesas/soma").to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.headers().get(X_CACHE).unwrap(), "HIT");
+        assert_eq!(response.headers().get(header::ETAG).unwrap(), &etag);
+        assert_eq!(test::read_body(response).await, corpo);
+
+        let req = test::TestRequest::get().uri("/despesas/soma").insert_header((header::IF_NONE_MATCH, etag.clone())).to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get(header::ETAG).unwrap(), &etag);
+        assert!(test::read_body(response).await.is_empty());
+
+        let req = test::TestRequest::get().uri("/despesas/soma").insert_header((header::IF_MODIFIED_SINCE, modificacao)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_MODIFIED);
+
+        // Requisições autenticadas e respostas de erro não passam pelo cache.
+        let req = test::TestRequest::get().uri("/despesas/soma").insert_header((header::AUTHORIZATION, "Bearer token")).to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::ETAG).is_none());
+        let req = test::TestRequest::get().uri("/despesas/cpf/71838787089/soma").to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(response.headers().get(header::ETAG).is_none());
+
+        // Uma condição satisfeita por qualquer resposta não encobre a ausência do recurso.
+        let req = test::TestRequest::get().uri("/deputados/999").insert_header((header::IF_NONE_MATCH, "*")).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+        // Uma resposta ainda fora do cache é gerada, e guardada, antes do 304.
+        let req = test::TestRequest::get().uri("/despesas/cpf/22488012033/soma").insert_header((header::IF_NONE_MATCH, "*")).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_MODIFIED);
+        let req = test::TestRequest::get().uri("/despesas/cpf/22488012033/soma").to_request();
+        assert_eq!(test::call_service(&app, req).await.headers().get(X_CACHE).unwrap(), "HIT");
+
+        // Uma nova importação incrementa a versão, e as respostas anteriores deixam de valer.
+        crate::import::process_csv(&mut pool.get().unwrap(), get_csv("50").as_bytes()).unwrap();
+        let req = test::TestRequest::get().uri("/despesas/soma").insert_header((header::IF_NONE_MATCH, etag.clone())).to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(X_CACHE).unwrap(), "MISS");
+        assert_ne!(response.headers().get(header::ETAG).unwrap(), &etag);
+        let soma: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(soma["soma"], "150.00");
+    }
+}
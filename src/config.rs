@@ -0,0 +1,123 @@
+use std::{env, path::PathBuf, time::Duration};
+
+use dotenvy::dotenv;
+
+// Tamanho máximo padrão de uma requisição de importação: 512 MiB, suficiente para o arquivo anual
+// completo da CEAP.
+const DEFAULT_MAX_UPLOAD_SIZE: usize = 512 * 1024 * 1024;
+
+// Tempo padrão após o qual um upload retomável que não recebe blocos é removido: 24 horas.
+const DEFAULT_UPLOAD_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Configuração dos endpoints de importação. Pode ser ajustada pelas variáveis de ambiente
+// UPLOAD_FIELD_NAME, que define o nome do campo do multipart que contém os arquivos,
+// UPLOAD_MAX_SIZE, que define o tamanho máximo aceito, em bytes, da soma dos arquivos enviados, e
+// UPLOAD_DIR, que define o diretório onde os uploads retomáveis são armazenados até serem
+// concluídos, e UPLOAD_TTL, que define após quantos segundos sem receber blocos um upload é
+// considerado abandonado e removido.
+#[derive(Clone, Debug)]
+pub struct UploadConfig {
+    pub field_name: String,
+    pub max_size: usize,
+    pub dir: PathBuf,
+    pub ttl: Duration,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            field_name: String::from("file"),
+            max_size: DEFAULT_MAX_UPLOAD_SIZE,
+            dir: env::temp_dir().join("knex-uploads"),
+            ttl: DEFAULT_UPLOAD_TTL,
+        }
+    }
+}
+
+impl UploadConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        dotenv().ok();
+        let default = Self::default();
+
+        let field_name = env::var("UPLOAD_FIELD_NAME").unwrap_or(default.field_name);
+        let max_size = match env::var("UPLOAD_MAX_SIZE") {
+            Ok(size) => size
+                .parse()
+                .map_err(|_| anyhow::anyhow!("UPLOAD_MAX_SIZE must be a number of bytes"))?,
+            Err(_) => default.max_size,
+        };
+        let dir = env::var("UPLOAD_DIR").map(PathBuf::from).unwrap_or(default.dir);
+        let ttl = match env::var("UPLOAD_TTL") {
+            Ok(ttl) => ttl
+                .parse()
+                .map(Duration::from_secs)
+                .map_err(|_| anyhow::anyhow!("UPLOAD_TTL must be a number of seconds"))?,
+            Err(_) => default.ttl,
+        };
+
+        Ok(Self { field_name, max_size, dir, ttl })
+    }
+}
+
+// Configuração dos endpoints administrativos, em /admin. Eles exigem o cabeçalho
+// `Authorization: Bearer {ADMIN_TOKEN}`, e ficam desabilitados caso ADMIN_TOKEN não seja definida.
+#[derive(Clone, Debug, Default)]
+pub struct AdminConfig {
+    pub token: Option<String>,
+}
+
+impl AdminConfig {
+    pub fn from_env() -> Self {
+        dotenv().ok();
+
+        Self {
+            token: env::var("ADMIN_TOKEN").ok().filter(|token| !token.is_empty()),
+        }
+    }
+}
+
+// Configuração da política de privacidade dos CPFs; veja privacy. Os CPFs são mascarados nas
+// respostas, exceto para as requisições com `Authorization: Bearer {token}`, com o token
+// administrativo ou um dos tokens de CPF_ACCESS_TOKENS, separados por vírgulas. A máscara pode
+// ser desabilitada com CPF_MASKING=false. Com CPF_LOOKUP_REQUIRES_AUTH=true, as consultas por CPF
+// das requisições sem autenticação são recusadas.
+#[derive(Clone, Debug)]
+pub struct PrivacyConfig {
+    pub mascara_cpf: bool,
+    pub tokens: Vec<String>,
+    pub restringe_consultas: bool,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            mascara_cpf: true,
+            tokens: Vec::new(),
+            restringe_consultas: false,
+        }
+    }
+}
+
+impl PrivacyConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        dotenv().ok();
+
+        let mascara_cpf = match env::var("CPF_MASKING") {
+            Ok(valor) => valor
+                .parse()
+                .map_err(|_| anyhow::anyhow!("CPF_MASKING must be true or false"))?,
+            Err(_) => true,
+        };
+        let tokens = env::var("CPF_ACCESS_TOKENS")
+            .map(|tokens| tokens.split(',').map(str::trim).filter(|token| !token.is_empty()).map(String::from).collect())
+            .unwrap_or_default();
+        let restringe_consultas = match env::var("CPF_LOOKUP_REQUIRES_AUTH") {
+            Ok(valor) => valor
+                .parse()
+                .map_err(|_| anyhow::anyhow!("CPF_LOOKUP_REQUIRES_AUTH must be true or false"))?,
+            Err(_) => false,
+        };
+
+        Ok(Self { mascara_cpf, tokens, restringe_consultas })
+    }
+}
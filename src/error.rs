@@ -0,0 +1,218 @@
+use actix_web::{error::BlockingError, http::StatusCode, HttpResponse, ResponseError};
+use derive_more::Display;
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::types::ValorInvalido;
+
+// Erros retornados pelos endpoints. Cada variante corresponde a um status HTTP, e todas são
+// serializadas no mesmo formato: {"code": "not_found", "message": "Deputado não encontrado."},
+// acrescido de `details` quando há informações adicionais, como o resultado de uma importação.
+#[derive(Debug, Display)]
+pub enum ApiError {
+    #[display("{_0}")]
+    NotFound(String),
+    // Parâmetros, caminho ou corpo da requisição inválidos.
+    #[display("{_0}")]
+    BadRequest(String),
+    #[display("{_0}")]
+    Unauthorized(String),
+    #[display("{_0}")]
+    Forbidden(String),
+    #[display("{_0}")]
+    Conflict(String),
+    #[display("{_0}")]
+    PayloadTooLarge(String),
+    #[display("{_0}")]
+    UnsupportedMediaType(String),
+    #[display("{_0}")]
+    Locked(String),
+    // Importação em que ao menos um arquivo foi rejeitado; `details` traz o resultado de cada um.
+    #[display("{message}")]
+    Import { message: String, details: Value },
+    // Nenhuma conexão com o banco de dados disponível no pool.
+    #[display("Serviço temporariamente indisponível.")]
+    Unavailable,
+    // A causa é registrada no log, mas não é exposta na resposta.
+    #[display("Erro interno.")]
+    Internal(anyhow::Error),
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody<'a> {
+    #[schema(example = "not_found")]
+    code: &'static str,
+    #[schema(example = "Deputado não encontrado.")]
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    details: Option<&'a Value>,
+}
+
+impl ApiError {
+    // Corpo da resposta de erro.
+    pub fn body(&self) -> ErrorBody<'_> {
+        let details = match self {
+            ApiError::Import { details, .. } => Some(details),
+            _ => None,
+        };
+
+        ErrorBody { code: self.code(), message: self.to_string(), details }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::NotFound(_) => "not_found",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::Locked(_) => "locked",
+            ApiError::Import { .. } => "import_failed",
+            ApiError::Unavailable => "service_unavailable",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::Locked(_) => StatusCode::LOCKED,
+            ApiError::Import { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            ApiError::Internal(e) => log::error!("{e:#}"),
+            ApiError::Unavailable => log::warn!("no database connection available"),
+            _ => {},
+        }
+
+        HttpResponse::build(self.status_code()).json(self.body())
+    }
+}
+
+// Necessário para que os erros possam interromper as respostas transmitidas aos poucos, como as
+// exportações em CSV.
+impl std::error::Error for ApiError {}
+
+// Os erros das operações executadas em web::block chegam como anyhow::Error; a causa original,
+// mesmo envolvida em um contexto, determina o status.
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        if matches!(e.downcast_ref(), Some(diesel::result::Error::NotFound)) {
+            ApiError::NotFound("Recurso não encontrado.".to_string())
+        } else if e.downcast_ref::<r2d2::Error>().is_some() {
+            ApiError::Unavailable
+        } else if let Some(invalido) = e.downcast_ref::<ValorInvalido>() {
+            ApiError::BadRequest(invalido.to_string())
+        } else {
+            ApiError::Internal(e)
+        }
+    }
+}
+
+impl From<diesel::result::Error> for ApiError {
+    fn from(e: diesel::result::Error) -> Self {
+        anyhow::Error::from(e).into()
+    }
+}
+
+impl From<r2d2::Error> for ApiError {
+    fn from(_: r2d2::Error) -> Self {
+        ApiError::Unavailable
+    }
+}
+
+impl From<ValorInvalido> for ApiError {
+    fn from(e: ValorInvalido) -> Self {
+        ApiError::BadRequest(e.to_string())
+    }
+}
+
+impl From<BlockingError> for ApiError {
+    fn from(e: BlockingError) -> Self {
+        ApiError::Internal(anyhow::anyhow!("{e}"))
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        ApiError::Internal(e.into())
+    }
+}
+
+impl From<csv::Error> for ApiError {
+    fn from(e: csv::Error) -> Self {
+        ApiError::Internal(e.into())
+    }
+}
+
+// A única falha esperada na montagem de uma planilha é exceder o limite de linhas do formato.
+impl From<rust_xlsxwriter::XlsxError> for ApiError {
+    fn from(e: rust_xlsxwriter::XlsxError) -> Self {
+        match e {
+            rust_xlsxwriter::XlsxError::RowColumnLimitError => {
+                ApiError::BadRequest("A exportação excede o limite de linhas do XLSX; use o formato CSV.".to_string())
+            },
+            e => ApiError::Internal(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::body::to_bytes;
+    use anyhow::Context;
+    use serde_json::json;
+
+    use super::*;
+
+    async fn body(error: ApiError) -> (StatusCode, Value) {
+        let response = error.error_response();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body()).await.unwrap();
+
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn serializa_erros_no_formato_comum() {
+        let (status, corpo) = body(ApiError::NotFound("Deputado não encontrado.".to_string())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(corpo, json!({"code": "not_found", "message": "Deputado não encontrado."}));
+
+        let (status, corpo) = body(ApiError::Import { message: "Falha.".to_string(), details: json!([{"arquivo": "a.csv"}]) }).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(corpo["details"][0]["arquivo"], "a.csv");
+
+        let (status, corpo) = body(ApiError::Internal(anyhow::anyhow!("senha do banco"))).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(corpo, json!({"code": "internal_error", "message": "Erro interno."}));
+    }
+
+    #[test]
+    fn identifica_a_causa_dos_erros() {
+        let nao_encontrado = Err::<(), _>(diesel::result::Error::NotFound).with_context(|| "database error").unwrap_err();
+        assert!(matches!(ApiError::from(nao_encontrado), ApiError::NotFound(_)));
+
+        let invalido = anyhow::Error::from(ValorInvalido { tipo: "UF", valor: "XX".to_string() });
+        assert!(matches!(ApiError::from(invalido), ApiError::BadRequest(_)));
+
+        assert!(matches!(ApiError::from(anyhow::anyhow!("falha")), ApiError::Internal(_)));
+    }
+}
@@ -0,0 +1,387 @@
+use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::{Datelike, NaiveDateTime};
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::models::{money::to_cents, ClienteFornecedor, Tabela, Deputado, DeputadoComTotal, DespesaExportada, FornecedorComTotal, FornecedorRanking, Posicao};
+
+// Exportação das listagens e das agregações em CSV e em XLSX, sem o limite de tamanho das páginas.
+// As colunas têm os nomes das colunas dos arquivos da CEAP (txNomeParlamentar, sgUF, vlrLiquido...),
+// e as que não existem nos arquivos seguem o mesmo padrão; os CSVs, como os da CEAP, são separados
+// por ponto e vírgula.
+
+// Formato de resposta de uma listagem ou agregação. Em NDJSON, cada item é um objeto JSON numa
+// linha própria, sem paginação.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Formato {
+    #[default]
+    Json,
+    Csv,
+    Xlsx,
+    Ndjson,
+}
+
+pub const XLSX_MIME: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+pub const NDJSON_MIME: &str = "application/x-ndjson";
+
+impl Formato {
+    // Formato pedido no cabeçalho Accept: o primeiro dos tipos informados que corresponda a um dos
+    // formatos, ou JSON, caso nenhum corresponda.
+    pub fn from_accept(accept: &str) -> Self {
+        accept
+            .split(',')
+            .filter_map(|tipo| match tipo.split(';').next().unwrap_or_default().trim() {
+                "application/json" => Some(Formato::Json),
+                "text/csv" => Some(Formato::Csv),
+                XLSX_MIME => Some(Formato::Xlsx),
+                NDJSON_MIME => Some(Formato::Ndjson),
+                _ => None,
+            })
+            .next()
+            .unwrap_or_default()
+    }
+}
+
+// Valor de uma célula exportada. Valores monetários são escritos com duas casas decimais, e datas
+// no mesmo formato da coluna datEmissao da CEAP.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Celula {
+    Texto(String),
+    Valor(BigDecimal),
+    Inteiro(i64),
+    Numero(f64),
+    DataHora(NaiveDateTime),
+    Vazia,
+}
+
+impl From<&str> for Celula {
+    fn from(texto: &str) -> Self {
+        Celula::Texto(texto.to_string())
+    }
+}
+
+impl<T: Into<Celula>> From<Option<T>> for Celula {
+    fn from(valor: Option<T>) -> Self {
+        valor.map(Into::into).unwrap_or(Celula::Vazia)
+    }
+}
+
+impl From<String> for Celula {
+    fn from(texto: String) -> Self {
+        Celula::Texto(texto)
+    }
+}
+
+impl From<&BigDecimal> for Celula {
+    fn from(valor: &BigDecimal) -> Self {
+        Celula::Valor(valor.clone())
+    }
+}
+
+impl From<i64> for Celula {
+    fn from(valor: i64) -> Self {
+        Celula::Inteiro(valor)
+    }
+}
+
+impl From<f64> for Celula {
+    fn from(valor: f64) -> Self {
+        Celula::Numero(valor)
+    }
+}
+
+impl From<NaiveDateTime> for Celula {
+    fn from(data: NaiveDateTime) -> Self {
+        Celula::DataHora(data)
+    }
+}
+
+// Valores de uma agregação, lidos como JSON.
+impl From<&Value> for Celula {
+    fn from(valor: &Value) -> Self {
+        match valor {
+            Value::Null => Celula::Vazia,
+            Value::String(texto) => Celula::Texto(texto.clone()),
+            Value::Number(numero) => numero.as_i64().map(Celula::Inteiro)
+                .or_else(|| numero.as_f64().map(Celula::Numero))
+                .unwrap_or(Celula::Vazia),
+            outro => Celula::Texto(outro.to_string()),
+        }
+    }
+}
+
+impl Celula {
+    fn texto(&self) -> String {
+        match self {
+            Celula::Texto(texto) => texto.clone(),
+            Celula::Valor(valor) => to_cents(valor).to_plain_string(),
+            Celula::Inteiro(valor) => valor.to_string(),
+            Celula::Numero(valor) => valor.to_string(),
+            Celula::DataHora(data) => data.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            Celula::Vazia => String::new(),
+        }
+    }
+}
+
+// Item de uma listagem exportável: os nomes das colunas e os valores de cada uma.
+pub trait Exportavel {
+    fn colunas() -> Vec<&'static str>;
+    fn celulas(&self) -> Vec<Celula>;
+}
+
+// Escreve as linhas em CSV, precedidas pelo cabeçalho, caso seja informado. Os lotes de uma
+// exportação são escritos separadamente, e apenas o primeiro traz o cabeçalho.
+pub fn csv<S: AsRef<str>>(colunas: Option<&[S]>, linhas: &[Vec<Celula>]) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::WriterBuilder::new().delimiter(b';').from_writer(Vec::new());
+
+    if let Some(colunas) = colunas {
+        writer.write_record(colunas.iter().map(AsRef::as_ref))?;
+    }
+    for linha in linhas {
+        writer.write_record(linha.iter().map(Celula::texto))?;
+    }
+
+    writer.into_inner().map_err(|e| e.into_error().into())
+}
+
+// Escreve os itens em NDJSON, um por linha.
+pub fn ndjson<T: Serialize>(itens: &[T]) -> Result<Vec<u8>, serde_json::Error> {
+    let mut bytes = Vec::new();
+
+    for item in itens {
+        serde_json::to_writer(&mut bytes, item)?;
+        bytes.push(b'\n');
+    }
+
+    Ok(bytes)
+}
+
+// Número máximo de linhas de dados de uma planilha XLSX: o limite do formato, 1.048.576 linhas,
+// menos a do cabeçalho.
+pub const XLSX_MAX_LINHAS: usize = 1_048_575;
+
+// Escreve as linhas em uma planilha XLSX, com o cabeçalho em negrito. A planilha é montada em
+// memória, e está sujeita ao limite de linhas do formato; veja XLSX_MAX_LINHAS.
+pub fn xlsx<S: AsRef<str>>(colunas: &[S], linhas: impl IntoIterator<Item = Vec<Celula>>) -> Result<Vec<u8>, XlsxError> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    let negrito = Format::new().set_bold();
+    let valor = Format::new().set_num_format("0.00");
+    let data = Format::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+
+    for (coluna, nome) in colunas.iter().enumerate() {
+        worksheet.write_string_with_format(0, coluna as u16, nome.as_ref(), &negrito)?;
+    }
+
+    for (linha, celulas) in linhas.into_iter().enumerate() {
+        let linha = linha as u32 + 1;
+        for (coluna, celula) in celulas.iter().enumerate() {
+            let coluna = coluna as u16;
+            match celula {
+                Celula::Texto(texto) => { worksheet.write_string(linha, coluna, texto)?; },
+                Celula::Valor(numero) => { worksheet.write_number_with_format(linha, coluna, to_cents(numero).to_f64().unwrap_or_default(), &valor)?; },
+                Celula::Inteiro(numero) => { worksheet.write_number(linha, coluna, *numero as f64)?; },
+                Celula::Numero(numero) => { worksheet.write_number(linha, coluna, *numero)?; },
+                Celula::DataHora(momento) => { worksheet.write_datetime_with_format(linha, coluna, momento, &data)?; },
+                Celula::Vazia => {},
+            }
+        }
+    }
+
+    workbook.save_to_buffer()
+}
+
+// Linhas de uma agregação. As métricas monetárias, que o JSON traz como texto, são exportadas como
+// valores, para que possam ser somadas na planilha.
+pub fn linhas_tabela(tabela: &Tabela) -> Vec<Vec<Celula>> {
+    let monetarias: Vec<bool> = tabela.colunas.iter()
+        .map(|coluna| matches!(coluna.as_str(), "soma" | "media" | "minimo" | "maximo"))
+        .collect();
+
+    tabela.valores()
+        .map(|linha| linha.iter().zip(&monetarias)
+            .map(|(valor, monetaria)| match valor.as_ref() {
+                Value::String(texto) if *monetaria => texto.parse().map(Celula::Valor).unwrap_or_else(|_| valor.as_ref().into()),
+                valor => valor.into(),
+            })
+            .collect())
+        .collect()
+}
+
+impl Exportavel for DespesaExportada {
+    fn colunas() -> Vec<&'static str> {
+        vec![
+            "txNomeParlamentar", "cpf", "sgUF", "sgPartido", "txtDescricao", "txtFornecedor", "txtCNPJCPF",
+            "txtFornecedorCanonico", "datEmissao", "vlrLiquido", "numMes", "numAno", "urlDocumento",
+        ]
+    }
+
+    fn celulas(&self) -> Vec<Celula> {
+        vec![
+            self.nome.as_str().into(),
+            self.cpf.exibido().into_owned().into(),
+            self.uf.as_str().into(),
+            self.partido.clone().into(),
+            self.categoria.as_str().into(),
+            self.fornecedor.as_str().into(),
+            self.fornecedor_documento.as_ref().map(|documento| documento.exibido().into_owned()).into(),
+            self.fornecedor_canonico.as_str().into(),
+            self.data_emissao.into(),
+            (&self.valor_liquido).into(),
+            (self.competencia.month() as i64).into(),
+            (self.competencia.year() as i64).into(),
+            self.url_documento.clone().into(),
+        ]
+    }
+}
+
+impl Exportavel for Deputado {
+    fn colunas() -> Vec<&'static str> {
+        vec!["id", "txNomeParlamentar", "cpf", "sgUF", "sgPartido"]
+    }
+
+    fn celulas(&self) -> Vec<Celula> {
+        vec![
+            (self.id as i64).into(),
+            self.nome.as_str().into(),
+            self.cpf.exibido().into_owned().into(),
+            self.uf.as_str().into(),
+            self.partido.clone().into(),
+        ]
+    }
+}
+
+impl Exportavel for DeputadoComTotal {
+    fn colunas() -> Vec<&'static str> {
+        let mut colunas = Deputado::colunas();
+        colunas.push("vlrTotal");
+        colunas
+    }
+
+    fn celulas(&self) -> Vec<Celula> {
+        let mut celulas = self.deputado.celulas();
+        celulas.push((&self.total).into());
+        celulas
+    }
+}
+
+impl Exportavel for ClienteFornecedor {
+    fn colunas() -> Vec<&'static str> {
+        let mut colunas = Deputado::colunas();
+        colunas.extend(["vlrTotal", "qtdDespesas"]);
+        colunas
+    }
+
+    fn celulas(&self) -> Vec<Celula> {
+        let mut celulas = self.deputado.celulas();
+        celulas.extend([(&self.total).into(), self.despesas.into()]);
+        celulas
+    }
+}
+
+impl Exportavel for FornecedorComTotal {
+    fn colunas() -> Vec<&'static str> {
+        vec!["txtCNPJCPF", "txtFornecedor", "vlrTotal", "qtdDespesas", "qtdDeputados"]
+    }
+
+    fn celulas(&self) -> Vec<Celula> {
+        vec![
+            self.documento.exibido().into_owned().into(),
+            self.nome.as_str().into(),
+            (&self.total).into(),
+            self.despesas.into(),
+            self.deputados.into(),
+        ]
+    }
+}
+
+impl Exportavel for FornecedorRanking {
+    fn colunas() -> Vec<&'static str> {
+        vec!["txtFornecedor"]
+    }
+
+    fn celulas(&self) -> Vec<Celula> {
+        vec![self.fornecedor.as_str().into()]
+    }
+}
+
+impl<T: Exportavel> Exportavel for Posicao<T> {
+    fn colunas() -> Vec<&'static str> {
+        let mut colunas = vec!["numPosicao"];
+        colunas.extend(T::colunas());
+        colunas.extend(["vlrTotal", "pctParticipacao", "vlrAnterior", "vlrVariacao", "pctVariacao"]);
+        colunas
+    }
+
+    fn celulas(&self) -> Vec<Celula> {
+        let mut celulas = vec![(self.posicao as i64).into()];
+        celulas.extend(self.item.celulas());
+        celulas.extend([
+            (&self.valor).into(),
+            self.participacao.into(),
+            self.anterior.as_ref().map(|anterior| &anterior.valor).into(),
+            self.anterior.as_ref().map(|anterior| &anterior.variacao).into(),
+            self.anterior.as_ref().and_then(|anterior| anterior.percentual).into(),
+        ]);
+        celulas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chrono::NaiveDate;
+    use serde_json::json;
+
+    use super::*;
+    use crate::privacy::Privacidade;
+
+    #[test]
+    fn escolhe_formato_pelo_cabecalho_accept() {
+        assert_eq!(Formato::from_accept("text/csv"), Formato::Csv);
+        assert_eq!(Formato::from_accept(&format!("text/html, {XLSX_MIME};q=0.9")), Formato::Xlsx);
+        assert_eq!(Formato::from_accept("application/json, text/csv"), Formato::Json);
+        assert_eq!(Formato::from_accept("*/*"), Formato::Json);
+        assert_eq!(Formato::from_accept("application/x-ndjson"), Formato::Ndjson);
+    }
+
+    #[test]
+    fn escreve_celulas_em_csv_e_xlsx() {
+        let data = NaiveDate::from_ymd_opt(2025, 2, 7).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let linhas = vec![
+            vec!["Jorge".into(), (&BigDecimal::from_str("1467.5").unwrap()).into(), data.into(), 2i64.into()],
+            vec!["Zé; Filho".into(), Celula::Vazia, Option::<NaiveDateTime>::None.into(), 0.5.into()],
+        ];
+
+        let csv = String::from_utf8(csv(Some(&["txNomeParlamentar", "vlrLiquido", "datEmissao", "numMes"]), &linhas).unwrap()).unwrap();
+        assert_eq!(csv, "txNomeParlamentar;vlrLiquido;datEmissao;numMes\nJorge;1467.50;2025-02-07T00:00:00;2\n\"Zé; Filho\";;;0.5\n");
+
+        let planilha = xlsx(&["txNomeParlamentar", "vlrLiquido", "datEmissao", "numMes"], linhas).unwrap();
+        assert!(planilha.starts_with(b"PK"));
+    }
+
+    #[test]
+    fn escreve_um_objeto_por_linha_em_ndjson() {
+        let itens = [json!({"uf": "PB", "soma": "1467.50"}), json!({"uf": "RJ", "soma": null})];
+
+        assert_eq!(String::from_utf8(ndjson(&itens).unwrap()).unwrap(), "{\"soma\":\"1467.50\",\"uf\":\"PB\"}\n{\"soma\":null,\"uf\":\"RJ\"}\n");
+    }
+
+    #[test]
+    fn exporta_metricas_monetarias_como_valores() {
+        let tabela = Tabela {
+            colunas: vec!["uf".to_string(), "soma".to_string(), "quantidade".to_string()],
+            linhas: vec![vec![json!("PB"), json!("1467.50"), json!(2)]],
+            privacidade: Privacidade::Mascarada,
+        };
+
+        assert_eq!(
+            linhas_tabela(&tabela),
+            [vec![Celula::Texto("PB".to_string()), Celula::Valor(BigDecimal::from_str("1467.50").unwrap()), Celula::Inteiro(2)]]
+        );
+    }
+}
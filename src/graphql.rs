@@ -0,0 +1,561 @@
+use std::{collections::{BTreeMap, HashMap}, str::FromStr};
+
+use actix_web::web;
+use anyhow::Context as _;
+use async_graphql::{dataloader::{DataLoader, Loader}, Context, EmptyMutation, EmptySubscription, Enum, ErrorExtensions, InputObject, Object, OutputType, Schema, SimpleObject};
+use bigdecimal::BigDecimal;
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{r2d2::ConnectionManager, OptionalExtension, PgConnection};
+use r2d2::Pool;
+
+use crate::{error::ApiError, models::{money, pagination::{Cursor, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE}, ClienteFornecedor, Deputado, DeputyFilter, DeputyOrder, DespesaExportada, Direction, Expense, ExpenseFilter, ExpenseOrder, FornecedorComTotal, Soma}, privacy::{self, Privacidade}, types::{Cpf, DocumentoFornecedor, Uf}};
+
+// API GraphQL sobre os deputados, as despesas e os fornecedores. Os objetos relacionados (o
+// deputado e o fornecedor de uma despesa, as despesas e os totais de um deputado, os clientes de um
+// fornecedor) são carregados em lote por um DataLoader, de modo que uma listagem com objetos
+// aninhados faz uma consulta por campo, e não uma por item. Os CPFs seguem a política de
+// privacidade das demais respostas, com a privacidade da requisição informada a execute; veja
+// privacy.
+
+type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+pub type CeapSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+// Limites das consultas, que impedem que uma única requisição percorra o banco de dados inteiro
+// por meio de objetos aninhados. Cada campo conta um na complexidade, e as listas contam tantas
+// vezes quantos itens podem trazer.
+const MAX_DEPTH: usize = 8;
+const MAX_COMPLEXITY: usize = 2000;
+
+// Número de despesas de cada deputado retornadas, por padrão, no campo despesas do deputado.
+const DEFAULT_LATEST: i64 = 10;
+
+// Número de clientes de cada fornecedor retornados, por padrão, no campo clientes do fornecedor.
+const DEFAULT_CLIENTS: i64 = 10;
+
+// Complexidade de uma lista de até `itens` itens, limitados como nos resolvers.
+fn complexidade(itens: i64, child_complexity: usize) -> usize {
+    (itens.clamp(1, MAX_PAGE_SIZE) as usize).saturating_mul(child_complexity)
+}
+
+pub fn schema() -> CeapSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+// Executa uma requisição. O DataLoader é criado a cada requisição, para que os objetos carregados
+// não sejam reaproveitados entre requisições.
+pub async fn execute(schema: &CeapSchema, pool: DbPool, privacidade: Privacidade, request: async_graphql::Request) -> async_graphql::Response {
+    let carregador = DataLoader::new(Carregador { pool: pool.clone() }, actix_web::rt::spawn);
+
+    schema.execute(request.data(pool).data(carregador).data(privacidade)).await
+}
+
+// Privacidade da requisição; sem ela, os CPFs são mascarados.
+fn privacidade(ctx: &Context<'_>) -> Privacidade {
+    ctx.data_opt::<Privacidade>().copied().unwrap_or_default()
+}
+
+// Converte os erros para o formato do GraphQL, com a mensagem e o código de ApiError; como nas
+// respostas HTTP, a causa dos erros internos é registrada no log, mas não é exposta.
+fn erro(e: ApiError) -> async_graphql::Error {
+    match &e {
+        ApiError::Internal(e) => log::error!("{e:#}"),
+        ApiError::Unavailable => log::warn!("no database connection available"),
+        _ => {},
+    }
+
+    let code = e.code();
+    async_graphql::Error::new(e.to_string()).extend_with(|_, extensoes| extensoes.set("code", code))
+}
+
+// Executa a consulta numa conexão do pool, fora do executor assíncrono.
+async fn consulta<T, F>(pool: &DbPool, f: F) -> async_graphql::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut PgConnection) -> Result<T, diesel::result::Error> + Send + 'static,
+{
+    let pool = pool.clone();
+    let result = web::block(move || {
+        let connection = &mut pool.get().with_context(|| "database error")?;
+
+        f(connection).with_context(|| "database error")
+    })
+    .await;
+
+    match result {
+        Ok(Ok(valor)) => Ok(valor),
+        Ok(Err(e)) => Err(erro(e.into())),
+        Err(e) => Err(erro(e.into())),
+    }
+}
+
+fn dinheiro(valor: &BigDecimal) -> String {
+    money::to_cents(valor).to_plain_string()
+}
+
+fn tamanho_da_pagina(page_size: Option<i64>) -> i64 {
+    page_size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+// Chaves do DataLoader; cada uma corresponde a uma consulta em lote.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct DeputadoPorId(i32);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct TotalDoDeputado(i32);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct DespesasDoDeputado {
+    deputado_id: i32,
+    limite: i64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct FornecedorPorDocumento(String);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ClientesDoFornecedor(String);
+
+struct Carregador {
+    pool: DbPool,
+}
+
+impl Loader<DeputadoPorId> for Carregador {
+    type Value = Deputado;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, chaves: &[DeputadoPorId]) -> Result<HashMap<DeputadoPorId, Deputado>, Self::Error> {
+        let ids: Vec<i32> = chaves.iter().map(|chave| chave.0).collect();
+        let deputados = consulta(&self.pool, move |connection| Deputado::by_ids(connection, &ids)).await?;
+
+        Ok(deputados.into_iter().map(|deputado| (DeputadoPorId(deputado.id), deputado)).collect())
+    }
+}
+
+impl Loader<TotalDoDeputado> for Carregador {
+    type Value = (BigDecimal, i64);
+    type Error = async_graphql::Error;
+
+    async fn load(&self, chaves: &[TotalDoDeputado]) -> Result<HashMap<TotalDoDeputado, (BigDecimal, i64)>, Self::Error> {
+        let ids: Vec<i32> = chaves.iter().map(|chave| chave.0).collect();
+        let totais = consulta(&self.pool, move |connection| Deputado::totals(connection, &ids)).await?;
+
+        Ok(totais.into_iter().map(|(id, total, despesas)| (TotalDoDeputado(id), (total, despesas))).collect())
+    }
+}
+
+impl Loader<DespesasDoDeputado> for Carregador {
+    type Value = Vec<DespesaExportada>;
+    type Error = async_graphql::Error;
+
+    // As chaves de um mesmo lote costumam ter o mesmo limite, já que vêm do mesmo campo; é feita
+    // uma consulta por limite.
+    async fn load(&self, chaves: &[DespesasDoDeputado]) -> Result<HashMap<DespesasDoDeputado, Vec<DespesaExportada>>, Self::Error> {
+        let mut por_limite: BTreeMap<i64, Vec<i32>> = BTreeMap::new();
+        for chave in chaves {
+            por_limite.entry(chave.limite).or_default().push(chave.deputado_id);
+        }
+
+        let mut resultado: HashMap<DespesasDoDeputado, Vec<DespesaExportada>> = chaves.iter().map(|chave| (chave.clone(), Vec::new())).collect();
+        for (limite, ids) in por_limite {
+            let despesas = consulta(&self.pool, move |connection| Expense::latest_by_deputies(connection, &ids, limite)).await?;
+            for despesa in despesas {
+                resultado.entry(DespesasDoDeputado { deputado_id: despesa.deputado_id, limite }).or_default().push(despesa);
+            }
+        }
+
+        Ok(resultado)
+    }
+}
+
+impl Loader<FornecedorPorDocumento> for Carregador {
+    type Value = FornecedorComTotal;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, chaves: &[FornecedorPorDocumento]) -> Result<HashMap<FornecedorPorDocumento, FornecedorComTotal>, Self::Error> {
+        let documentos: Vec<String> = chaves.iter().map(|chave| chave.0.clone()).collect();
+        let fornecedores = consulta(&self.pool, move |connection| FornecedorComTotal::get_many(connection, &documentos)).await?;
+
+        Ok(fornecedores.into_iter().map(|fornecedor| (FornecedorPorDocumento(fornecedor.documento.to_string()), fornecedor)).collect())
+    }
+}
+
+impl Loader<ClientesDoFornecedor> for Carregador {
+    type Value = Vec<ClienteFornecedor>;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, chaves: &[ClientesDoFornecedor]) -> Result<HashMap<ClientesDoFornecedor, Vec<ClienteFornecedor>>, Self::Error> {
+        let documentos: Vec<String> = chaves.iter().map(|chave| chave.0.clone()).collect();
+        let clientes = consulta(&self.pool, move |connection| ClienteFornecedor::list_many(connection, &documentos)).await?;
+
+        let mut resultado: HashMap<ClientesDoFornecedor, Vec<ClienteFornecedor>> = HashMap::new();
+        for (documento, cliente) in clientes {
+            resultado.entry(ClientesDoFornecedor(documento)).or_default().push(cliente);
+        }
+
+        Ok(resultado)
+    }
+}
+
+fn carregador<'a>(ctx: &Context<'a>) -> &'a DataLoader<Carregador> {
+    ctx.data_unchecked::<DataLoader<Carregador>>()
+}
+
+fn pool<'a>(ctx: &Context<'a>) -> &'a DbPool {
+    ctx.data_unchecked::<DbPool>()
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(name = "Direcao", remote = "crate::models::Direction")]
+enum Direcao {
+    Asc,
+    Desc,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(name = "OrdemDespesas", remote = "crate::models::ExpenseOrder")]
+enum OrdemDespesas {
+    Data,
+    Valor,
+    Fornecedor,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(name = "OrdemDeputados", remote = "crate::models::DeputyOrder")]
+enum OrdemDeputados {
+    Nome,
+    Uf,
+    Partido,
+    Total,
+}
+
+// Filtros da listagem de deputados; veja DeputyFilter.
+#[derive(InputObject, Default)]
+struct FiltroDeputados {
+    uf: Option<String>,
+    partido: Option<String>,
+    nome: Option<String>,
+    ordem: Option<OrdemDeputados>,
+    direcao: Option<Direcao>,
+}
+
+impl FiltroDeputados {
+    fn filtro(self) -> Result<DeputyFilter, ApiError> {
+        Ok(DeputyFilter {
+            uf: self.uf.as_deref().map(Uf::new).transpose()?,
+            partido: self.partido,
+            nome: self.nome,
+            ordem: self.ordem.map(DeputyOrder::from).unwrap_or_default(),
+            direcao: self.direcao.map(Direction::from).unwrap_or_default(),
+        })
+    }
+}
+
+// Filtros da busca de despesas; veja ExpenseFilter. Os valores são informados como strings, no
+// mesmo formato das respostas ("1467.50").
+#[derive(InputObject, Default)]
+struct FiltroDespesas {
+    uf: Option<String>,
+    cpf: Option<String>,
+    partido: Option<String>,
+    fornecedor: Option<String>,
+    categoria: Option<String>,
+    emissao_de: Option<NaiveDate>,
+    emissao_ate: Option<NaiveDate>,
+    competencia_de: Option<NaiveDate>,
+    competencia_ate: Option<NaiveDate>,
+    valor_min: Option<String>,
+    valor_max: Option<String>,
+    ordem: Option<OrdemDespesas>,
+    direcao: Option<Direcao>,
+}
+
+fn valor(valor: Option<&str>) -> Result<Option<BigDecimal>, ApiError> {
+    valor
+        .map(|valor| {
+            BigDecimal::from_str(valor.trim())
+                .map(|valor| money::to_cents(&valor))
+                .map_err(|_| ApiError::BadRequest(format!("Valor inválido: {valor:?}.")))
+        })
+        .transpose()
+}
+
+impl FiltroDespesas {
+    fn filtro(self) -> Result<ExpenseFilter, ApiError> {
+        Ok(ExpenseFilter {
+            uf: self.uf.as_deref().map(Uf::new).transpose()?,
+            cpf: self.cpf.as_deref().map(Cpf::new).transpose()?,
+            partido: self.partido,
+            fornecedor: self.fornecedor,
+            categoria: self.categoria,
+            emissao_de: self.emissao_de,
+            emissao_ate: self.emissao_ate,
+            competencia_de: self.competencia_de,
+            competencia_ate: self.competencia_ate,
+            valor_min: valor(self.valor_min.as_deref())?,
+            valor_max: valor(self.valor_max.as_deref())?,
+            ordem: self.ordem.map(ExpenseOrder::from).unwrap_or_default(),
+            direcao: self.direcao.map(Direction::from).unwrap_or_default(),
+        })
+    }
+}
+
+// Página de uma listagem paginada por número.
+#[derive(SimpleObject)]
+#[graphql(concrete(name = "PaginaDeputados", params(Deputado)), concrete(name = "PaginaFornecedores", params(FornecedorComTotal)))]
+struct PaginaNumerada<T: OutputType> {
+    dados: Vec<T>,
+    total: i64,
+    page: u32,
+    page_size: i64,
+}
+
+// Página de despesas, paginada por cursor, como em GET /despesas.
+#[derive(SimpleObject)]
+struct PaginaDespesas {
+    dados: Vec<DespesaExportada>,
+    total: i64,
+    page_size: i64,
+    next: Option<String>,
+    prev: Option<String>,
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    // Deputado pelo identificador ou pelo CPF.
+    async fn deputado(&self, ctx: &Context<'_>, chave: String) -> async_graphql::Result<Option<Deputado>> {
+        if Deputado::chave_e_cpf(&chave) {
+            privacidade(ctx).consulta_por_cpf().map_err(erro)?;
+        }
+
+        consulta(pool(ctx), move |connection| Deputado::find(connection, &chave).optional()).await
+    }
+
+    #[graphql(complexity = "complexidade(page_size.unwrap_or(DEFAULT_PAGE_SIZE), child_complexity)")]
+    async fn deputados(
+        &self,
+        ctx: &Context<'_>,
+        filtro: Option<FiltroDeputados>,
+        page: Option<u32>,
+        page_size: Option<i64>) -> async_graphql::Result<PaginaNumerada<Deputado>> {
+        let filtro = filtro.unwrap_or_default().filtro().map_err(erro)?;
+        let page_size = tamanho_da_pagina(page_size);
+
+        let pagina = consulta(pool(ctx), move |connection| Deputado::list(connection, &filtro, page.unwrap_or(1), page_size)).await?;
+
+        Ok(PaginaNumerada {
+            dados: pagina.dados.into_iter().map(|deputado| deputado.deputado).collect(),
+            total: pagina.total,
+            page: pagina.page,
+            page_size: pagina.page_size,
+        })
+    }
+
+    // Despesas que satisfazem os filtros, paginadas pelo cursor retornado em next ou prev.
+    #[graphql(complexity = "complexidade(page_size.unwrap_or(DEFAULT_PAGE_SIZE), child_complexity)")]
+    async fn despesas(
+        &self,
+        ctx: &Context<'_>,
+        filtro: Option<FiltroDespesas>,
+        cursor: Option<String>,
+        page_size: Option<i64>) -> async_graphql::Result<PaginaDespesas> {
+        let filtro = filtro.unwrap_or_default().filtro().map_err(erro)?;
+        if filtro.cpf.is_some() {
+            privacidade(ctx).consulta_por_cpf().map_err(erro)?;
+        }
+        let cursor = match cursor {
+            Some(token) => Some(
+                Cursor::decode(&token)
+                    .filter(|cursor| cursor.matches(filtro.ordem, filtro.direcao))
+                    .ok_or_else(|| erro(ApiError::BadRequest("Cursor inválido.".to_string())))?
+            ),
+            None => None,
+        };
+        let page_size = tamanho_da_pagina(page_size);
+
+        let pagina = consulta(pool(ctx), move |connection| Expense::search::<DespesaExportada>(connection, &filtro, cursor.as_ref(), page_size)).await?;
+
+        Ok(PaginaDespesas { dados: pagina.dados, total: pagina.total, page_size: pagina.page_size, next: pagina.next, prev: pagina.prev })
+    }
+
+    // Fornecedores, do que mais recebeu ao que menos recebeu, opcionalmente filtrados pelo nome ou
+    // pelo documento.
+    #[graphql(complexity = "complexidade(page_size.unwrap_or(DEFAULT_PAGE_SIZE), child_complexity)")]
+    async fn fornecedores(
+        &self,
+        ctx: &Context<'_>,
+        q: Option<String>,
+        page: Option<u32>,
+        page_size: Option<i64>) -> async_graphql::Result<PaginaNumerada<FornecedorComTotal>> {
+        let page_size = tamanho_da_pagina(page_size);
+        let privacidade = privacidade(ctx);
+
+        let pagina = consulta(pool(ctx), move |connection| FornecedorComTotal::list(connection, q.as_deref(), privacidade, page.unwrap_or(1), page_size)).await?;
+
+        Ok(PaginaNumerada { dados: pagina.dados, total: pagina.total, page: pagina.page, page_size: pagina.page_size })
+    }
+
+    // Fornecedor pelo documento, CNPJ ou CPF, com ou sem formatação.
+    async fn fornecedor(&self, ctx: &Context<'_>, documento: String) -> async_graphql::Result<Option<FornecedorComTotal>> {
+        let documento = DocumentoFornecedor::new(&documento).map_err(|e| erro(e.into()))?;
+        if documento.as_str().len() == 11 {
+            privacidade(ctx).consulta_por_cpf().map_err(erro)?;
+        }
+
+        carregador(ctx).load_one(FornecedorPorDocumento(documento.to_string())).await
+    }
+
+    // Soma de todas as despesas.
+    async fn soma(&self, ctx: &Context<'_>) -> async_graphql::Result<String> {
+        let soma = consulta(pool(ctx), Soma::sum_all).await?;
+
+        Ok(dinheiro(&soma.soma))
+    }
+}
+
+#[Object]
+impl Deputado {
+    async fn id(&self) -> i32 {
+        self.id
+    }
+
+    async fn nome(&self) -> &str {
+        &self.nome
+    }
+
+    async fn uf(&self) -> &str {
+        self.uf.as_str()
+    }
+
+    async fn cpf(&self, ctx: &Context<'_>) -> String {
+        privacy::cpf(self.cpf.as_str(), privacidade(ctx)).into_owned()
+    }
+
+    async fn partido(&self) -> Option<&str> {
+        self.partido.as_deref()
+    }
+
+    // Total das despesas do deputado.
+    async fn total(&self, ctx: &Context<'_>) -> async_graphql::Result<String> {
+        let total = carregador(ctx).load_one(TotalDoDeputado(self.id)).await?;
+
+        Ok(dinheiro(&total.map(|(total, _)| total).unwrap_or_default()))
+    }
+
+    async fn quantidade_despesas(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        let total = carregador(ctx).load_one(TotalDoDeputado(self.id)).await?;
+
+        Ok(total.map_or(0, |(_, despesas)| despesas))
+    }
+
+    // Despesas mais recentes do deputado, da mais recente à mais antiga.
+    #[graphql(complexity = "complexidade(ultimas, child_complexity)")]
+    async fn despesas(&self, ctx: &Context<'_>, #[graphql(default_with = "DEFAULT_LATEST")] ultimas: i64) -> async_graphql::Result<Vec<DespesaExportada>> {
+        let chave = DespesasDoDeputado { deputado_id: self.id, limite: ultimas.clamp(1, MAX_PAGE_SIZE) };
+
+        Ok(carregador(ctx).load_one(chave).await?.unwrap_or_default())
+    }
+}
+
+#[Object(name = "Despesa")]
+impl DespesaExportada {
+    async fn id(&self) -> i32 {
+        self.expense_id
+    }
+
+    async fn data_emissao(&self) -> Option<NaiveDateTime> {
+        self.data_emissao
+    }
+
+    // Mês ao qual a despesa se refere ("2025-02").
+    async fn competencia(&self) -> String {
+        self.competencia.format("%Y-%m").to_string()
+    }
+
+    async fn categoria(&self) -> &str {
+        &self.categoria
+    }
+
+    async fn valor_liquido(&self) -> String {
+        dinheiro(&self.valor_liquido)
+    }
+
+    async fn url_documento(&self) -> Option<&str> {
+        self.url_documento.as_deref()
+    }
+
+    // Nome do fornecedor como consta da despesa; o nome canônico está em fornecedor.
+    async fn nome_fornecedor(&self) -> &str {
+        &self.fornecedor
+    }
+
+    async fn documento_fornecedor(&self, ctx: &Context<'_>) -> Option<String> {
+        self.fornecedor_documento.as_ref().map(|documento| privacy::documento(documento.as_str(), privacidade(ctx)).into_owned())
+    }
+
+    async fn deputado(&self, ctx: &Context<'_>) -> async_graphql::Result<Deputado> {
+        carregador(ctx).load_one(DeputadoPorId(self.deputado_id)).await?
+            .ok_or_else(|| erro(ApiError::NotFound("Deputado não encontrado.".to_string())))
+    }
+
+    // Fornecedor identificado pelo documento; nulo para as despesas sem documento.
+    async fn fornecedor(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<FornecedorComTotal>> {
+        match &self.fornecedor_documento {
+            Some(documento) => carregador(ctx).load_one(FornecedorPorDocumento(documento.to_string())).await,
+            None => Ok(None),
+        }
+    }
+}
+
+#[Object(name = "Fornecedor")]
+impl FornecedorComTotal {
+    async fn documento(&self, ctx: &Context<'_>) -> String {
+        privacy::documento(self.documento.as_str(), privacidade(ctx)).into_owned()
+    }
+
+    async fn nome(&self) -> &str {
+        &self.nome
+    }
+
+    async fn total(&self) -> String {
+        dinheiro(&self.total)
+    }
+
+    async fn quantidade_despesas(&self) -> i64 {
+        self.despesas
+    }
+
+    async fn quantidade_deputados(&self) -> i64 {
+        self.deputados
+    }
+
+    // Deputados que pagaram o fornecedor, do que mais pagou ao que menos pagou, limitados aos
+    // `limite` primeiros.
+    #[graphql(complexity = "complexidade(limite, child_complexity)")]
+    async fn clientes(&self, ctx: &Context<'_>, #[graphql(default_with = "DEFAULT_CLIENTS")] limite: i64) -> async_graphql::Result<Vec<ClienteFornecedor>> {
+        let mut clientes = carregador(ctx).load_one(ClientesDoFornecedor(self.documento.to_string())).await?.unwrap_or_default();
+        clientes.truncate(limite.clamp(1, MAX_PAGE_SIZE) as usize);
+
+        Ok(clientes)
+    }
+}
+
+#[Object(name = "Cliente")]
+impl ClienteFornecedor {
+    async fn deputado(&self) -> &Deputado {
+        &self.deputado
+    }
+
+    async fn total(&self) -> String {
+        dinheiro(&self.total)
+    }
+
+    async fn quantidade_despesas(&self) -> i64 {
+        self.despesas
+    }
+}
@@ -1,21 +1,30 @@
-use std::io;
-use std::collections::HashMap;
+use std::io::{self, Cursor, Read};
+use std::collections::{HashMap, HashSet};
 
 use anyhow::Error;
 use anyhow::{bail, Context};
-use chrono::{NaiveTime, NaiveDate, NaiveDateTime};
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
 use diesel::prelude::*;
 use diesel::PgConnection;
+use serde::Serialize;
+use utoipa::ToSchema;
 
 use crate::models::*;
 use crate::schema;
-use crate::validate::valida_cpf;
+use crate::types::Cpf;
 
-pub fn process_csv<T>(connection: &mut PgConnection, reader: T) -> Result<(), Error>
+// Resumo de uma unidade de importação: quantos deputados e despesas constavam do arquivo.
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct ImportSummary {
+    pub deputados: usize,
+    pub despesas: u64,
+}
+
+pub fn process_csv<T>(connection: &mut PgConnection, reader: T) -> Result<ImportSummary, Error>
 where
     T: io::Read
     {
-    let mut cache: HashMap<String, i32> = HashMap::new();
+    let mut cache: HashMap<Cpf, i32> = HashMap::new();
     let mut rdr =
     csv::ReaderBuilder::new()
         .has_headers(true)
@@ -29,6 +38,8 @@ where
         .clone();
 
     let mut expenses = Vec::new();
+    let mut competencias: HashSet<NaiveDate> = HashSet::new();
+    let mut anos: HashSet<i32> = HashSet::new();
     
     let mut num_despesas: u64 = 0;
 
@@ -53,11 +64,17 @@ where
         } else {
             continue;
         };
+        // O mesmo deputado pode aparecer com o CPF formatado ou sem os zeros à esquerda; as buscas
+        // usam sempre a forma canônica.
+        let dep_cpf = match Cpf::new(dep_cpf) {
+            Ok(cpf) => cpf,
+            Err(e) => bail!("{e}"),
+        };
         
-        let current_id = if let Some(id) = cache.get(dep_cpf) {
+        let current_id = if let Some(id) = cache.get(&dep_cpf) {
             *id
-        } else if let Ok(id) = get_id_by_cpf(connection, dep_cpf) {
-                cache.insert(dep_cpf.to_string(), id);
+        } else if let Ok(id) = get_id_by_cpf(connection, &dep_cpf) {
+                cache.insert(dep_cpf, id);
                 // println!("CPF {dep_cpf} has already been registered with id {id}.");
                 id
         } else {
@@ -76,21 +93,33 @@ where
             .deserialize(Some(&headers))
             .with_context(|| "failed to deserialize expense.")?;
     
-        let date_expense = if let Some(data_emissao) = &expense.data_emissao {
-            NaiveDateTime::parse_from_str(&data_emissao, "%Y-%m-%dT%T").with_context(|| "date parsing error")?
-        } else {
-            NaiveDate::from_ymd_opt(expense.ano, expense.mes, 1)
-            .with_context(|| "could not parse emission date")?
-            .and_time(NaiveTime::from_hms_opt(0, 0, 0).unwrap())
+        let data_emissao = match &expense.data_emissao {
+            Some(data_emissao) => Some(
+                NaiveDateTime::parse_from_str(data_emissao, "%Y-%m-%dT%T").with_context(|| "date parsing error")?
+            ),
+            None => None,
         };
+        let competencia = NaiveDate::from_ymd_opt(expense.ano, expense.mes, 1)
+            .with_context(|| "could not parse competence month")?;
+
+        if anos.insert(competencia.year()) {
+            Expense::create_partition(connection, competencia.year())
+                .with_context(|| format!("failed to create the expense partition for {}", competencia.year()))?;
+        }
 
         expenses.push(NewExpense {
-            data_emissao: date_expense,
+            data_emissao,
+            competencia,
             deputado_id: current_id,
             fornecedor: expense.fornecedor,
+            fornecedor_documento: expense.fornecedor_documento
+                .map(|documento| documento.chars().filter(char::is_ascii_digit).collect::<String>())
+                .filter(|documento| !documento.is_empty()),
             valor_liquido: expense.valor_liquido,
             url_documento: expense.url_documento,
+            categoria: expense.categoria,
         });
+        competencias.insert(competencia);
         num_despesas += 1;
         // println!("Insert: {:?}", expense);
         if expenses.len() == 10000 {
@@ -102,18 +131,62 @@ where
         expenses.clear();
         }
     }
-    if expenses.len() > 0 {
+    if !expenses.is_empty() {
         diesel::insert_into(schema::expenses::table)
                 .values(&expenses)
                 // .returning(Expense::as_returning())
                 .execute(connection)
                 .with_context(|| "batch insertion failed.")?;
     }
+
+    let ids: Vec<i32> = cache.values().copied().collect();
+    let competencias: Vec<NaiveDate> = competencias.into_iter().collect();
+    refresh_rollups(connection, &ids, &competencias)
+        .with_context(|| "failed to refresh expense rollups.")?;
+    VersaoDados::bump(connection)
+        .with_context(|| "failed to bump the dataset version.")?;
+
     log::info!("Registrados {} deputados e {} despesas.", cache.len(), num_despesas);
-    Ok(())
+    Ok(ImportSummary { deputados: cache.len(), despesas: num_despesas })
+}
+
+// Extrai os CSVs contidos em um arquivo ZIP, retornando o nome e o conteúdo de cada um. Entradas
+// que não sejam CSV são ignoradas, e o conteúdo descompactado total não pode exceder `max_size`
+// bytes.
+pub fn extract_zip(bytes: &[u8], max_size: usize) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+        .with_context(|| "failed to read ZIP archive")?;
+    let mut files = Vec::new();
+    let mut total: usize = 0;
+
+    for i in 0..archive.len() {
+        let entry = archive
+            .by_index(i)
+            .with_context(|| "failed to read ZIP entry")?;
+
+        if !entry.is_file() || !entry.name().to_lowercase().ends_with(".csv") {
+            continue;
+        }
+
+        let name = entry.name().to_string();
+        let mut content = Vec::new();
+        entry
+            .take((max_size - total) as u64 + 1)
+            .read_to_end(&mut content)
+            .with_context(|| format!("failed to decompress {name}"))?;
+
+        total += content.len();
+        if total > max_size {
+            bail!("ZIP archive exceeds the maximum size of {max_size} bytes");
+        }
+
+        files.push((name, content));
+    }
+
+    Ok(files)
 }
 
-pub fn get_id_by_cpf(connection: &mut PgConnection, cpf: &str) -> Result<i32, Error> {
+pub fn get_id_by_cpf(connection: &mut PgConnection, cpf: &Cpf) -> Result<i32, Error> {
     use self::schema::deputados::dsl::deputados;
 
     Ok(
@@ -124,18 +197,13 @@ pub fn get_id_by_cpf(connection: &mut PgConnection, cpf: &str) -> Result<i32, Er
     )
 }
 
+// O CPF e a UF do deputado já foram validados ao desserializá-lo.
 fn insert_deputado(connection: &mut PgConnection, deputado: NovoDeputado) -> Result<Deputado, Error> {
-    if !valida_cpf(&deputado.cpf) {
-        bail!("invalid CPF.");
-    }
-    
-    Ok(
-        diesel::insert_into(schema::deputados::table)
-            .values(deputado)
-            .returning(Deputado::as_returning())
-            .get_result(connection)
-            .with_context(|| "failed to insert record.")?
-    )
+    diesel::insert_into(schema::deputados::table)
+        .values(deputado)
+        .returning(Deputado::as_returning())
+        .get_result(connection)
+        .with_context(|| "failed to insert record.")
 }
 
 #[cfg(test)]
@@ -153,6 +221,7 @@ mod tests {
     }
 
     use super::*;
+    use crate::types::Uf;
 
     fn get_connection() -> PgConnection{
         dotenv().ok();
@@ -187,24 +256,159 @@ mod tests {
         });
     }
 
-    fn deputado_com_cpf(cpf: String) -> NovoDeputado {
+    #[test]
+    fn preserva_data_de_emissao_ausente() {
+        use self::schema::expenses::dsl::*;
+
+        let connection = &mut get_connection();
+
+        connection.test_transaction(|connection| {
+            process_csv(connection, get_csv().as_bytes())?;
+
+            let sem_emissao: Vec<Expense> = expenses
+                .filter(data_emissao.is_null())
+                .select(Expense::as_select())
+                .load(connection)?;
+
+            assert_eq!(sem_emissao.len(), 1);
+            assert_eq!(sem_emissao[0].competencia, NaiveDate::from_ymd_opt(2025, 2, 1).unwrap());
+
+            Ok::<(), Error>(())
+        });
+    }
+
+    #[test]
+    fn soma_valores_com_centavos_exatos() {
+        use std::str::FromStr;
+        use bigdecimal::BigDecimal;
+
+        let header = get_csv().lines().next().unwrap();
+        let mut csv = header.to_string();
+        for valor in ["0.10", "0.20", "1467.35", "-0.05"] {
+            csv.push_str(&format!("\nJorge;22488012033;;;2023;PB;;57;1;Descrição;0;;Fornecedor;CNPJ-fornecedor;1984;0;2025-02-07T00:00:00;1467;0;{valor};2;2025;0;;;0;;;;0;0;"));
+        }
+
+        let connection = &mut get_connection();
+
+        connection.test_transaction(|connection| {
+            process_csv(connection, csv.as_bytes())?;
+
+            assert_eq!(Soma::sum_all(connection)?.soma, BigDecimal::from_str("1467.60")?);
+            assert_eq!(Soma::sum_by_cpf(connection, &Cpf::new("22488012033")?)?.soma, BigDecimal::from_str("1467.60")?);
+
+            Ok::<(), Error>(())
+        });
+    }
+
+    #[test]
+    fn atualiza_totais_apenas_das_competencias_importadas() {
+        use std::str::FromStr;
+        use bigdecimal::BigDecimal;
+        use self::schema::resumo_despesas::dsl::*;
+
+        let header = get_csv().lines().next().unwrap();
+        let linha = |valor: &str, mes: u32, descricao: &str| format!("\nJorge;22488012033;;;2023;PB;;57;1;{descricao};0;;Fornecedor;;1984;0;2025-02-07T00:00:00;1467;0;{valor};{mes};2025;0;;;0;;;;0;0;");
+
+        let connection = &mut get_connection();
+
+        connection.test_transaction(|connection| {
+            diesel::delete(resumo_despesas).execute(connection)?;
+
+            let csv = format!("{header}{}{}{}", linha("10.10", 1, "COMBUSTÍVEIS"), linha("5", 1, "COMBUSTÍVEIS"), linha("100", 1, "PASSAGEM AÉREA"));
+            process_csv(connection, csv.as_bytes())?;
+
+            let csv = format!("{header}{}", linha("0.25", 2, "COMBUSTÍVEIS"));
+            process_csv(connection, csv.as_bytes())?;
+
+            let linhas: Vec<(NaiveDate, String, BigDecimal, i64)> = resumo_despesas
+                .select((competencia, categoria, total, quantidade))
+                .order_by((competencia, categoria))
+                .load(connection)?;
+
+            assert_eq!(linhas, vec![
+                (NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(), "COMBUSTÍVEIS".to_string(), BigDecimal::from_str("15.10")?, 2),
+                (NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(), "PASSAGEM AÉREA".to_string(), BigDecimal::from_str("100.00")?, 1),
+                (NaiveDate::from_ymd_opt(2025, 2, 1).unwrap(), "COMBUSTÍVEIS".to_string(), BigDecimal::from_str("0.25")?, 1),
+            ]);
+
+            let soma_despesas: Option<BigDecimal> = schema::expenses::table
+                .select(diesel::dsl::sum(schema::expenses::valor_liquido))
+                .first(connection)?;
+            assert_eq!(Some(Soma::sum_all(connection)?.soma), soma_despesas);
+            assert_eq!(Soma::sum_by_cpf(connection, &Cpf::new("22488012033")?)?.soma, BigDecimal::from_str("115.35")?);
+
+            Ok::<(), Error>(())
+        });
+    }
+
+    #[test]
+    fn cria_particao_do_ano_sob_demanda() {
+        use self::schema::expenses::dsl::*;
+
+        let header = get_csv().lines().next().unwrap();
+        let csv = format!("{header}\nMaria;52998224725;;;2023;SP;;57;1;Descrição;0;;Fornecedor;;1984;0;;10;0;10;3;2099;0;;;0;;;;0;0;");
+
+        let connection = &mut get_connection();
+
+        connection.test_transaction(|connection| {
+            process_csv(connection, csv.as_bytes())?;
+
+            assert!(!Expense::create_partition(connection, 2099)?);
+            assert_eq!(expenses
+                .filter(competencia.eq(NaiveDate::from_ymd_opt(2099, 3, 1).unwrap()))
+                .count()
+                .get_result(connection), Ok(1));
+
+            Ok::<(), Error>(())
+        });
+    }
+
+    #[test]
+    fn identifica_deputado_pelo_cpf_canonico() {
+        use self::schema::deputados::dsl::*;
+
+        let header = get_csv().lines().next().unwrap();
+        let linha = |documento: &str| format!("\nMaria;{documento};;;2023;SP;;57;1;Descrição;0;;Fornecedor;;1984;0;;10;0;10;3;2025;0;;;0;;;;0;0;");
+
+        let connection = &mut get_connection();
+
+        connection.test_transaction(|connection| {
+            let csv = format!("{header}{}{}", linha("2673718613"), linha("026.737.186-13"));
+            process_csv(connection, csv.as_bytes())?;
+
+            let csv = format!("{header}{}", linha("02673718613"));
+            process_csv(connection, csv.as_bytes())?;
+
+            let cpfs: Vec<Cpf> = deputados
+                .filter(nome.eq("Maria"))
+                .select(cpf)
+                .load(connection)?;
+            assert_eq!(cpfs, vec![Cpf::new("02673718613")?]);
+
+            Ok::<(), Error>(())
+        })
+    }
+
+    fn deputado_com_cpf(cpf: &str) -> NovoDeputado {
         NovoDeputado {
             nome: "Teste".to_string(),
-            uf: "PB".to_string(),
-            cpf: cpf,
+            uf: Uf::new("PB").unwrap(),
+            cpf: Cpf::new(cpf).unwrap(),
             partido: Some("Partido Pirata".to_string()),
         }
     }
 
     #[test]
     fn rejeita_deputado_com_cpf_invalido() {
+        for cpf in ["", "123456789", "abc", "9755404260035"] {
+            assert!(Cpf::new(cpf).is_err(), "{cpf}");
+        }
+
         let connection = &mut get_connection();
 
         connection.test_transaction(|connection| {
-            assert!(insert_deputado(connection, deputado_com_cpf(String::from(""))).is_err());
-            assert!(insert_deputado(connection, deputado_com_cpf(String::from("123456789"))).is_err());
-            assert!(insert_deputado(connection, deputado_com_cpf(String::from("abc"))).is_err());
-            assert!(insert_deputado(connection, deputado_com_cpf(String::from("9755404260035"))).is_err());
+            let csv = get_csv().replace("22488012033", "12345678900");
+            assert!(process_csv(connection, csv.as_bytes()).is_err());
 
             Ok::<(), Error>(())
         })
@@ -215,10 +419,10 @@ mod tests {
         let connection = &mut get_connection();
 
         connection.test_transaction(|connection| {
-            assert!(insert_deputado(connection, deputado_com_cpf(String::from("2673718605"))).is_ok());
-            assert!(insert_deputado(connection, deputado_com_cpf(String::from("88293388005"))).is_ok());
-            assert!(insert_deputado(connection, deputado_com_cpf(String::from("70042234000"))).is_ok());
-            assert!(insert_deputado(connection, deputado_com_cpf(String::from("700422340"))).is_ok());
+            assert!(insert_deputado(connection, deputado_com_cpf("2673718613")).is_ok());
+            assert!(insert_deputado(connection, deputado_com_cpf("88293388005")).is_ok());
+            assert!(insert_deputado(connection, deputado_com_cpf("70042234000")).is_ok());
+            assert!(insert_deputado(connection, deputado_com_cpf("700422331")).is_ok());
 
             Ok::<(), Error>(())
         })
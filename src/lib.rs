@@ -1,9 +1,21 @@
-// pub mod error;
+pub mod auth;
+pub mod cache;
+pub mod error;
+pub mod export;
+pub mod graphql;
+pub mod openapi;
+pub mod privacy;
+pub mod config;
 pub mod models;
 pub mod schema;
 pub mod validate;
 pub mod import;
+pub mod resolution;
+pub mod resumable;
 pub mod routes;
+pub mod text;
+pub mod types;
+pub mod versioning;
 
 use anyhow::Context;
 use diesel::{prelude::*, r2d2::ConnectionManager};
@@ -24,10 +36,8 @@ pub fn build_connection_pool() -> anyhow::Result<Pool<ConnectionManager<PgConnec
 
     let manager = ConnectionManager::<PgConnection>::new(url);
     
-    Ok(
-        Pool::builder()
-            .test_on_check_out(true)
-            .build(manager)
-            .with_context(|| "failed to build connection pool")?
-    )
+    Pool::builder()
+        .test_on_check_out(true)
+        .build(manager)
+        .with_context(|| "failed to build connection pool")
 }
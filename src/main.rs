@@ -1,7 +1,7 @@
-use std::{io, process::exit};
+use std::{io, process::exit, sync::Arc, time::Duration};
 
-use actix_web::{web, App, HttpServer};
-use knex_selection_challenges::{build_connection_pool, routes::{import_csv, lista_deputados_por_uf, lista_despesas_por_cpf, lista_despesas_por_uf, soma_despesas, soma_todas_as_despesas}};
+use actix_web::{middleware, rt, web, App, HttpServer};
+use knex_selection_challenges::{build_connection_pool, cache::{self, ResponseCache}, config::{AdminConfig, PrivacyConfig, UploadConfig}, graphql, openapi, resumable::UploadStore, routes::{self, path_config, query_config, json_config}, versioning};
 
 #[actix_web::main]
 async fn main() -> io::Result<()>{
@@ -13,6 +13,31 @@ async fn main() -> io::Result<()>{
             exit(1);
         },
     };
+    let upload_config = match UploadConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e:?}");
+            exit(1);
+        },
+    };
+    let admin_config = AdminConfig::from_env();
+    let privacy_config = match PrivacyConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e:?}");
+            exit(1);
+        },
+    };
+    let schema = web::Data::new(graphql::schema());
+    let response_cache = web::Data::new(ResponseCache::default());
+    let upload_store = match UploadStore::new(&upload_config.dir) {
+        Ok(store) => web::Data::new(store),
+        Err(e) => {
+            eprintln!("{e:?}");
+            exit(1);
+        },
+    };
+    rt::spawn(expira_uploads(upload_store.clone().into_inner(), upload_config.ttl));
     
     // let file_name = env::var("FILE").unwrap();
 
@@ -20,15 +45,42 @@ async fn main() -> io::Result<()>{
 
     HttpServer::new(move || {
         App::new()
-        .service(import_csv)
-        .service(lista_deputados_por_uf)
-        .service(lista_despesas_por_cpf)
-        .service(lista_despesas_por_uf)
-        .service(soma_despesas)
-        .service(soma_todas_as_despesas)
+        .wrap(middleware::from_fn(cache::middleware))
+        .configure(routes::configure_graphql)
+        .service(openapi::docs())
+        .service(versioning::v1("/v1"))
+        .service(versioning::v2())
+        // Os endereços sem prefixo continuam servindo a v1.
+        .service(versioning::v1(""))
+        .app_data(path_config())
+        .app_data(query_config())
+        .app_data(json_config())
         .app_data(web::Data::new(pool.clone()))
+        .app_data(web::Data::new(upload_config.clone()))
+        .app_data(upload_store.clone())
+        .app_data(web::Data::new(admin_config.clone()))
+        .app_data(web::Data::new(privacy_config.clone()))
+        .app_data(schema.clone())
+        .app_data(response_cache.clone())
     })
     .bind(("0.0.0.0", 8080))?
     .run()
     .await
 }
+
+// Remove periodicamente os uploads retomáveis abandonados.
+async fn expira_uploads(store: Arc<UploadStore>, ttl: Duration) {
+    let mut interval = rt::time::interval(ttl.clamp(Duration::from_secs(60), Duration::from_secs(60 * 60)));
+
+    loop {
+        interval.tick().await;
+
+        let store = store.clone();
+        match web::block(move || store.expire(ttl)).await {
+            Ok(Ok(0)) => {},
+            Ok(Ok(removed)) => log::info!("Removidos {removed} uploads abandonados."),
+            Ok(Err(e)) => log::warn!("failed to expire uploads: {e:#}"),
+            Err(e) => log::warn!("failed to expire uploads: {e}"),
+        }
+    }
+}
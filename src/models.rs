@@ -1,5 +1,20 @@
+mod agregado;
+pub mod competencia;
 mod deputado;
 mod expense;
+mod fornecedor;
+pub mod money;
+pub mod pagination;
+mod ranking;
+mod resumo;
+mod search;
+mod versao;
 
+pub use agregado::*;
 pub use deputado::*;
-pub use expense::*;
\ No newline at end of file
+pub use expense::*;
+pub use fornecedor::*;
+pub use ranking::*;
+pub use resumo::*;
+pub use search::*;
+pub use versao::*;
\ No newline at end of file
@@ -0,0 +1,294 @@
+use std::{borrow::Cow, str::FromStr};
+
+use diesel::{dsl, pg::Pg, prelude::*, result::Error, sql_types::{Json, Text}};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use serde_json::Value;
+use utoipa::{openapi::{schema::{AdditionalProperties, ArrayBuilder, ObjectBuilder, Schema}, RefOr}, PartialSchema, ToSchema};
+
+use crate::{models::{despesa_com_deputado, ExpenseFilter}, privacy::{self, Privacidade}};
+
+// As agregações são montadas sobre a visão despesa_com_deputado, com os mesmos filtros da busca de
+// despesas. As dimensões e métricas vêm de listas fechadas, de modo que o SQL gerado nunca contém
+// texto informado pelo usuário; cada linha é lida como um array JSON, na ordem das colunas.
+
+// Dimensão pela qual as despesas são agrupadas.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Dimensao {
+    Uf,
+    Deputado,
+    Partido,
+    Fornecedor,
+    Categoria,
+    Ano,
+    Mes,
+}
+
+impl FromStr for Dimensao {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "uf" => Ok(Dimensao::Uf),
+            "deputado" => Ok(Dimensao::Deputado),
+            "partido" => Ok(Dimensao::Partido),
+            "fornecedor" => Ok(Dimensao::Fornecedor),
+            "categoria" => Ok(Dimensao::Categoria),
+            "ano" => Ok(Dimensao::Ano),
+            "mes" => Ok(Dimensao::Mes),
+            outra => Err(format!("Dimensão desconhecida: {outra}.")),
+        }
+    }
+}
+
+impl Dimensao {
+    // Colunas do resultado e as expressões correspondentes. O deputado é identificado pelo CPF,
+    // acompanhado do nome, e o mês é representado como AAAA-MM, como as competências.
+    fn colunas(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Dimensao::Uf => &[("uf", "uf")],
+            Dimensao::Deputado => &[("cpf", "cpf"), ("deputado", "nome")],
+            Dimensao::Partido => &[("partido", "partido")],
+            Dimensao::Fornecedor => &[("fornecedor", "fornecedor_canonico")],
+            Dimensao::Categoria => &[("categoria", "categoria")],
+            Dimensao::Ano => &[("ano", "EXTRACT(YEAR FROM competencia)::integer")],
+            Dimensao::Mes => &[("mes", "to_char(competencia, 'YYYY-MM')")],
+        }
+    }
+}
+
+// Métrica calculada para cada grupo. Os valores monetários são arredondados para centavos e
+// representados como strings, como nas demais respostas.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Metrica {
+    Soma,
+    Quantidade,
+    Media,
+    Minimo,
+    Maximo,
+}
+
+impl FromStr for Metrica {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "sum" => Ok(Metrica::Soma),
+            "count" => Ok(Metrica::Quantidade),
+            "avg" => Ok(Metrica::Media),
+            "min" => Ok(Metrica::Minimo),
+            "max" => Ok(Metrica::Maximo),
+            outra => Err(format!("Métrica desconhecida: {outra}.")),
+        }
+    }
+}
+
+impl Metrica {
+    fn coluna(self) -> (&'static str, &'static str) {
+        match self {
+            Metrica::Soma => ("soma", "ROUND(SUM(valor_liquido), 2)::text"),
+            Metrica::Quantidade => ("quantidade", "COUNT(*)"),
+            Metrica::Media => ("media", "ROUND(AVG(valor_liquido), 2)::text"),
+            Metrica::Minimo => ("minimo", "ROUND(MIN(valor_liquido), 2)::text"),
+            Metrica::Maximo => ("maximo", "ROUND(MAX(valor_liquido), 2)::text"),
+        }
+    }
+}
+
+// Agrupamento das despesas pelas dimensões informadas, com as métricas de cada grupo. Sem
+// dimensões, há um único grupo com todas as despesas.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Agregacao {
+    pub dimensoes: Vec<Dimensao>,
+    pub metricas: Vec<Metrica>,
+}
+
+impl Agregacao {
+    // Lê as listas de dimensões e de métricas, separadas por vírgulas; itens repetidos são
+    // ignorados, e as métricas padrão são a soma e a quantidade.
+    pub fn parse(dimensoes: Option<&str>, metricas: Option<&str>) -> Result<Self, String> {
+        fn lista<T: FromStr<Err = String> + PartialEq>(valor: &str) -> Result<Vec<T>, String> {
+            let mut itens = Vec::new();
+            for item in valor.split(',').filter(|item| !item.trim().is_empty()) {
+                let item = item.parse()?;
+                if !itens.contains(&item) {
+                    itens.push(item);
+                }
+            }
+            Ok(itens)
+        }
+
+        let dimensoes = lista(dimensoes.unwrap_or_default())?;
+        let metricas = lista(metricas.unwrap_or("sum,count"))?;
+        if metricas.is_empty() {
+            return Err("Informe ao menos uma métrica.".to_string());
+        }
+
+        Ok(Self { dimensoes, metricas })
+    }
+
+    pub fn run(&self, connection: &mut PgConnection, filtro: &ExpenseFilter) -> Result<Tabela, Error> {
+        let grupos: Vec<&str> = self.dimensoes.iter()
+            .flat_map(|dimensao| dimensao.colunas().iter().map(|(_, expressao)| *expressao))
+            .collect();
+        let colunas: Vec<(&str, &str)> = self.dimensoes.iter()
+            .flat_map(|dimensao| dimensao.colunas().iter().copied())
+            .chain(self.metricas.iter().map(|metrica| metrica.coluna()))
+            .collect();
+
+        let selecao = colunas.iter().map(|(_, expressao)| *expressao).collect::<Vec<_>>().join(", ");
+        let agrupamento = if grupos.is_empty() { "()".to_string() } else { grupos.join(", ") };
+
+        // Consultas já encapsuladas (boxed) não podem ser agrupadas; os filtros selecionam as
+        // despesas numa subconsulta, e a consulta externa as agrupa.
+        let filtradas = filtro.apply(despesa_com_deputado::table.into_boxed::<Pg>())
+            .select(despesa_com_deputado::expense_id);
+        let query = despesa_com_deputado::table
+            .filter(despesa_com_deputado::expense_id.eq_any(filtradas))
+            .group_by(dsl::sql::<Text>(&agrupamento))
+            .select(dsl::sql::<Json>(&format!("json_build_array({selecao})")))
+            .into_boxed();
+        let query = if grupos.is_empty() { query } else { query.order_by(dsl::sql::<Text>(&grupos.join(", "))) };
+
+        let linhas: Vec<Value> = query.load(connection)?;
+
+        Ok(Tabela {
+            colunas: colunas.iter().map(|(nome, _)| nome.to_string()).collect(),
+            linhas: linhas.into_iter()
+                .map(|linha| match linha {
+                    Value::Array(valores) => valores,
+                    outro => vec![outro],
+                })
+                .collect(),
+            privacidade: Privacidade::Mascarada,
+        })
+    }
+}
+
+// Resultado de uma agregação, em formato de tabela: uma linha por grupo, com os valores na ordem
+// das colunas. Em JSON, é serializado como uma lista de objetos, um por linha. Os CPFs dos
+// deputados são exibidos com a privacidade da tabela, mascarados por padrão.
+#[derive(Debug, PartialEq)]
+pub struct Tabela {
+    pub colunas: Vec<String>,
+    pub linhas: Vec<Vec<Value>>,
+    pub privacidade: Privacidade,
+}
+
+impl Tabela {
+    pub fn itens(&self) -> Vec<Linha<'_>> {
+        self.linhas.iter().map(|linha| Linha(&self.colunas, linha, self.privacidade)).collect()
+    }
+
+    // Valores das linhas como exibidos nas respostas, com os CPFs dos deputados sujeitos à política
+    // de privacidade.
+    pub fn valores(&self) -> impl Iterator<Item = Vec<Cow<'_, Value>>> {
+        self.linhas.iter().map(|linha| self.colunas.iter().zip(linha).map(|(coluna, valor)| publico(coluna, valor, self.privacidade)).collect())
+    }
+
+    pub fn to_csv(&self) -> Result<Vec<u8>, csv::Error> {
+        let mut writer = csv::WriterBuilder::new().delimiter(b';').from_writer(Vec::new());
+
+        writer.write_record(&self.colunas)?;
+        for linha in self.valores() {
+            writer.write_record(linha.iter().map(|valor| match valor.as_ref() {
+                Value::Null => String::new(),
+                Value::String(texto) => texto.clone(),
+                outro => outro.to_string(),
+            }))?;
+        }
+
+        writer.into_inner().map_err(|e| e.into_error().into())
+    }
+}
+
+// Valor de uma coluna como exibido nas respostas; veja privacy.
+fn publico<'a>(coluna: &str, valor: &'a Value, privacidade: Privacidade) -> Cow<'a, Value> {
+    match valor {
+        Value::String(cpf) if coluna == "cpf" => Cow::Owned(Value::String(privacy::cpf(cpf, privacidade).into_owned())),
+        valor => Cow::Borrowed(valor),
+    }
+}
+
+// Linha de uma tabela, serializada como um objeto com os valores de cada coluna, na ordem das colunas.
+pub struct Linha<'a>(&'a [String], &'a [Value], Privacidade);
+
+// As colunas da tabela dependem das dimensões e das métricas pedidas; no esquema OpenAPI, cada
+// linha é um objeto com quaisquer campos.
+impl PartialSchema for Tabela {
+    fn schema() -> RefOr<Schema> {
+        ArrayBuilder::new()
+            .items(ObjectBuilder::new().additional_properties(Some(AdditionalProperties::FreeForm(true))))
+            .examples([serde_json::json!([{"partido": "PSB", "mes": "2025-01", "soma": "120.50", "quantidade": 1}])])
+            .into()
+    }
+}
+
+impl ToSchema for Tabela {}
+
+impl Serialize for Linha<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (coluna, valor) in self.0.iter().zip(self.1) {
+            map.serialize_entry(coluna, &publico(coluna, valor, self.2))?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for Tabela {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.itens())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::privacy::Protegido;
+
+    #[test]
+    fn le_dimensoes_e_metricas() {
+        let agregacao = Agregacao::parse(Some("uf, mes,uf"), Some("sum,avg")).unwrap();
+        assert_eq!(agregacao.dimensoes, [Dimensao::Uf, Dimensao::Mes]);
+        assert_eq!(agregacao.metricas, [Metrica::Soma, Metrica::Media]);
+
+        let padrao = Agregacao::parse(None, None).unwrap();
+        assert!(padrao.dimensoes.is_empty());
+        assert_eq!(padrao.metricas, [Metrica::Soma, Metrica::Quantidade]);
+
+        assert!(Agregacao::parse(Some("uf; DROP TABLE expenses"), None).is_err());
+        assert!(Agregacao::parse(None, Some("median")).is_err());
+        assert!(Agregacao::parse(None, Some(",")).is_err());
+    }
+
+    #[test]
+    fn serializa_tabela_em_json_e_csv() {
+        let tabela = Tabela {
+            colunas: vec!["uf".to_string(), "soma".to_string(), "quantidade".to_string()],
+            linhas: vec![vec![json!("PB"), json!("1467.50"), json!(2)], vec![json!(null), json!("10.00"), json!(1)]],
+            privacidade: Privacidade::Mascarada,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&tabela).unwrap(),
+            r#"[{"uf":"PB","soma":"1467.50","quantidade":2},{"uf":null,"soma":"10.00","quantidade":1}]"#
+        );
+        assert_eq!(String::from_utf8(tabela.to_csv().unwrap()).unwrap(), "uf;soma;quantidade\nPB;1467.50;2\n;10.00;1\n");
+    }
+
+    #[test]
+    fn mascara_os_cpfs_dos_deputados() {
+        let tabela = Tabela {
+            colunas: vec!["cpf".to_string(), "deputado".to_string()],
+            linhas: vec![vec![json!("22488012033"), json!("Jorge")]],
+            privacidade: Privacidade::Mascarada,
+        };
+
+        assert_eq!(serde_json::to_string(&tabela).unwrap(), r#"[{"cpf":"***.880.120-**","deputado":"Jorge"}]"#);
+        assert_eq!(String::from_utf8(tabela.to_csv().unwrap()).unwrap(), "cpf;deputado\n***.880.120-**;Jorge\n");
+        let tabela = tabela.protegido(Privacidade::Revelada);
+        assert_eq!(serde_json::to_string(&tabela).unwrap(), r#"[{"cpf":"22488012033","deputado":"Jorge"}]"#);
+    }
+}
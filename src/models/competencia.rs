@@ -0,0 +1,9 @@
+use chrono::NaiveDate;
+use serde::Serializer;
+
+// A competência de uma despesa é o mês ao qual ela se refere. No banco de dados, é armazenada como
+// o primeiro dia desse mês; em JSON, é serializada apenas como ano e mês ("2025-02"). Use com
+// `#[serde(serialize_with = "competencia::serialize")]`.
+pub fn serialize<S: Serializer>(value: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&value.format("%Y-%m"))
+}
@@ -1,19 +1,25 @@
-use diesel::prelude::*;
+use std::collections::BTreeMap;
+
+use bigdecimal::BigDecimal;
+use chrono::{Datelike, NaiveDateTime};
+use diesel::{dsl, pg::Pg, prelude::*, result::Error, sql_types::{BigInt, Nullable, Numeric, Timestamp}};
 use serde::{Serialize,Deserialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{models::{despesa_com_deputado, money, pagination::{PaginaNumerada, MAX_PAGE_SIZE}, search::{f_unaccent, lower}, Direction}, text, types::{Cpf, Uf}};
 
-#[derive(Queryable, Selectable, Serialize, Debug)]
+#[derive(Queryable, Selectable, Serialize, Clone, Debug, ToSchema)]
 #[diesel(table_name = crate::schema::deputados)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct Deputado {
-    #[serde(skip)]
     pub id: i32,
     pub nome: String,
-    pub uf: String,
-    pub cpf: String,
+    pub uf: Uf,
+    pub cpf: Cpf,
     pub partido: Option<String>,
 }
 
-use crate::schema::deputados;
+use crate::schema::{deputados, expenses, resumo_despesas};
 
 #[derive(Insertable, Deserialize, Debug)]
 #[diesel(table_name = deputados)]
@@ -21,19 +27,268 @@ pub struct NovoDeputado {
     #[serde(rename = "txNomeParlamentar")]
     pub nome: String,
     #[serde(rename = "sgUF")]
-    pub uf: String,
-    pub cpf: String,
+    pub uf: Uf,
+    pub cpf: Cpf,
     #[serde(rename = "sgPartido")]
     pub partido: Option<String>,
 }
 
+// Campo pelo qual os deputados são ordenados; o identificador desempata a ordenação.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeputyOrder {
+    #[default]
+    Nome,
+    Uf,
+    Partido,
+    Total,
+}
+
+// Filtros da listagem de deputados, todos opcionais. O nome é buscado pelo início, sem diferenciar
+// maiúsculas e minúsculas nem acentos.
+#[derive(Deserialize, Clone, Default, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeputyFilter {
+    pub uf: Option<Uf>,
+    pub partido: Option<String>,
+    pub nome: Option<String>,
+    #[serde(default)]
+    #[param(inline)]
+    pub ordem: DeputyOrder,
+    #[serde(default)]
+    #[param(inline)]
+    pub direcao: Direction,
+}
+
+// Deputado acompanhado do total das suas despesas.
+#[derive(Queryable, Serialize, Debug, ToSchema)]
+pub struct DeputadoComTotal {
+    #[serde(flatten)]
+    pub deputado: Deputado,
+    #[serde(with = "money")]
+    #[schema(value_type = String, example = "1467.50")]
+    pub total: BigDecimal,
+}
+
+// Total das despesas do deputado, obtido dos totais pré-calculados.
+fn total_gasto() -> diesel::expression::SqlLiteral<Numeric> {
+    dsl::sql::<Numeric>("(SELECT COALESCE(SUM(total), 0) FROM resumo_despesas WHERE resumo_despesas.deputado_id = deputados.id)")
+}
+
 impl Deputado {
-    pub fn get_all_by_uf(connection: &mut PgConnection, uf_busca: &str) -> Result<Vec<Self>, diesel::result::Error> {
-        use crate::schema::deputados::dsl::*;
+    // Lista os deputados que satisfazem os filtros, na ordem escolhida, paginados por número.
+    pub fn list(connection: &mut PgConnection, filtro: &DeputyFilter, page: u32, page_size: i64) -> Result<PaginaNumerada<DeputadoComTotal>, Error> {
+        let page = page.max(1);
+        let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+
+        let total = Self::filter(filtro).count().get_result(connection)?;
+        let dados = Self::sorted(filtro)
+            .select((Deputado::as_select(), total_gasto()))
+            .limit(page_size)
+            .offset(page_size * (page as i64 - 1))
+            .load(connection)?;
 
-        deputados
-            .filter(uf.eq(uf_busca))
+        Ok(PaginaNumerada { dados, total, page, page_size })
+    }
+
+    // Como list, mas sem paginação; usada nas exportações.
+    pub fn all(connection: &mut PgConnection, filtro: &DeputyFilter) -> Result<Vec<DeputadoComTotal>, Error> {
+        Self::sorted(filtro)
+            .select((Deputado::as_select(), total_gasto()))
+            .load(connection)
+    }
+
+    fn filter(filtro: &DeputyFilter) -> deputados::BoxedQuery<'_, Pg> {
+        let mut query = deputados::table.into_boxed();
+
+        if let Some(busca) = &filtro.uf {
+            query = query.filter(deputados::uf.eq(busca));
+        }
+        if let Some(busca) = &filtro.partido {
+            query = query.filter(deputados::partido.eq(busca.trim().to_uppercase()));
+        }
+        if let Some(busca) = &filtro.nome {
+            let padrao = format!("{}%", text::normalize(busca.trim()).replace('%', "\\%").replace('_', "\\_"));
+            query = query.filter(f_unaccent(lower(deputados::nome)).like(padrao));
+        }
+
+        query
+    }
+
+    fn sorted(filtro: &DeputyFilter) -> deputados::BoxedQuery<'_, Pg> {
+        let query = Self::filter(filtro);
+
+        match (filtro.ordem, filtro.direcao) {
+            (DeputyOrder::Nome, Direction::Asc) => query.order_by((deputados::nome.asc(), deputados::id.asc())),
+            (DeputyOrder::Nome, Direction::Desc) => query.order_by((deputados::nome.desc(), deputados::id.desc())),
+            (DeputyOrder::Uf, Direction::Asc) => query.order_by((deputados::uf.asc(), deputados::nome.asc(), deputados::id.asc())),
+            (DeputyOrder::Uf, Direction::Desc) => query.order_by((deputados::uf.desc(), deputados::nome.desc(), deputados::id.desc())),
+            (DeputyOrder::Partido, Direction::Asc) => query.order_by((deputados::partido.asc(), deputados::nome.asc(), deputados::id.asc())),
+            (DeputyOrder::Partido, Direction::Desc) => query.order_by((deputados::partido.desc(), deputados::nome.desc(), deputados::id.desc())),
+            (DeputyOrder::Total, Direction::Asc) => query.order_by((total_gasto().asc(), deputados::id.asc())),
+            (DeputyOrder::Total, Direction::Desc) => query.order_by((total_gasto().desc(), deputados::id.desc())),
+        }
+    }
+
+    // Busca os deputados com os identificadores informados, em qualquer ordem.
+    pub fn by_ids(connection: &mut PgConnection, ids: &[i32]) -> Result<Vec<Self>, Error> {
+        deputados::table
+            .filter(deputados::id.eq_any(ids))
             .select(Deputado::as_select())
             .load(connection)
     }
-}
\ No newline at end of file
+
+    // Total e número de despesas de cada um dos deputados informados, obtidos dos totais
+    // pré-calculados; os deputados sem despesas não constam do resultado.
+    pub fn totals(connection: &mut PgConnection, ids: &[i32]) -> Result<Vec<(i32, BigDecimal, i64)>, Error> {
+        resumo_despesas::table
+            .filter(resumo_despesas::deputado_id.eq_any(ids))
+            .group_by(resumo_despesas::deputado_id)
+            .select((
+                resumo_despesas::deputado_id,
+                dsl::sum(resumo_despesas::total).assume_not_null(),
+                dsl::sql::<BigInt>("SUM(quantidade)::bigint"),
+            ))
+            .load(connection)
+    }
+
+    // Busca o deputado pelo identificador ou, caso o valor informado não seja um identificador
+    // válido, pelo CPF; um valor que não seja nenhum dos dois não corresponde a nenhum deputado.
+    // Valores com nove dígitos ou mais são sempre CPFs, possivelmente sem os zeros à esquerda.
+    pub fn find(connection: &mut PgConnection, chave: &str) -> Result<Self, Error> {
+        let chave = chave.trim();
+        let query = deputados::table.select(Deputado::as_select());
+
+        match chave.parse::<i32>() {
+            Ok(id) if !Self::chave_e_cpf(chave) => query.filter(deputados::id.eq(id)).first(connection),
+            _ => {
+                let cpf = Cpf::new(chave).map_err(|_| Error::NotFound)?;
+                query.filter(deputados::cpf.eq(cpf)).first(connection)
+            },
+        }
+    }
+
+    // Indica se a chave informada a find é buscada como CPF.
+    pub fn chave_e_cpf(chave: &str) -> bool {
+        let chave = chave.trim();
+
+        chave.len() >= 9 || chave.parse::<i32>().is_err()
+    }
+}
+
+// Número de fornecedores listados no perfil do deputado.
+const TOP_SUPPLIERS: i64 = 10;
+
+// Total e número de despesas de um ano, de uma categoria ou de um fornecedor.
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct Total<T> {
+    #[serde(flatten)]
+    pub chave: T,
+    #[serde(with = "money")]
+    #[schema(value_type = String, example = "1467.50")]
+    pub total: BigDecimal,
+    pub despesas: i64,
+}
+
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct Ano {
+    pub ano: i32,
+}
+
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct Categoria {
+    pub categoria: String,
+}
+
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct Fornecedor {
+    pub fornecedor: String,
+}
+
+// Perfil de um deputado: a sua identificação, os totais das suas despesas por ano e por categoria,
+// os fornecedores que mais receberam dele, e as datas da primeira e da última despesa. Na ausência
+// da data de emissão, a data de uma despesa é o início da sua competência.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Perfil {
+    #[serde(flatten)]
+    pub deputado: Deputado,
+    #[serde(with = "money")]
+    #[schema(value_type = String, example = "1467.50")]
+    pub total: BigDecimal,
+    pub despesas: i64,
+    pub primeira_despesa: Option<NaiveDateTime>,
+    pub ultima_despesa: Option<NaiveDateTime>,
+    pub por_ano: Vec<Total<Ano>>,
+    pub por_categoria: Vec<Total<Categoria>>,
+    pub principais_fornecedores: Vec<Total<Fornecedor>>,
+}
+
+impl Perfil {
+    pub fn get(connection: &mut PgConnection, chave: &str) -> Result<Self, Error> {
+        let deputado = Deputado::find(connection, chave)?;
+
+        // Os totais por ano e por categoria são obtidos dos totais pré-calculados, agregados por
+        // competência e categoria.
+        let totais: Vec<(chrono::NaiveDate, String, BigDecimal, i64)> = resumo_despesas::table
+            .filter(resumo_despesas::deputado_id.eq(deputado.id))
+            .select((resumo_despesas::competencia, resumo_despesas::categoria, resumo_despesas::total, resumo_despesas::quantidade))
+            .load(connection)?;
+
+        let mut por_ano: BTreeMap<i32, (BigDecimal, i64)> = BTreeMap::new();
+        let mut por_categoria: BTreeMap<String, (BigDecimal, i64)> = BTreeMap::new();
+        for (competencia, categoria, total, quantidade) in totais {
+            let ano = por_ano.entry(competencia.year()).or_default();
+            ano.0 += &total;
+            ano.1 += quantidade;
+
+            let categoria = por_categoria.entry(categoria).or_default();
+            categoria.0 += total;
+            categoria.1 += quantidade;
+        }
+
+        let total = por_ano.values().map(|(total, _)| total).sum();
+        let despesas = por_ano.values().map(|(_, despesas)| despesas).sum();
+
+        let por_ano = por_ano.into_iter()
+            .map(|(ano, (total, despesas))| Total { chave: Ano { ano }, total, despesas })
+            .collect();
+        let mut por_categoria: Vec<Total<Categoria>> = por_categoria.into_iter()
+            .map(|(categoria, (total, despesas))| Total { chave: Categoria { categoria }, total, despesas })
+            .collect();
+        por_categoria.sort_by(|a, b| b.total.cmp(&a.total));
+
+        let principais_fornecedores = {
+            use self::despesa_com_deputado::dsl::*;
+
+            despesa_com_deputado
+                .filter(deputado_id.eq(deputado.id))
+                .group_by(fornecedor_canonico)
+                .select((fornecedor_canonico, dsl::sum(valor_liquido).assume_not_null(), dsl::count_star()))
+                .order_by((dsl::sum(valor_liquido).desc(), fornecedor_canonico))
+                .limit(TOP_SUPPLIERS)
+                .load::<(String, BigDecimal, i64)>(connection)?
+                .into_iter()
+                .map(|(canonico, soma, quantidade)| Total { chave: Fornecedor { fornecedor: canonico }, total: soma, despesas: quantidade })
+                .collect()
+        };
+
+        let (primeira_despesa, ultima_despesa) = expenses::table
+            .filter(expenses::deputado_id.eq(deputado.id))
+            .select((
+                dsl::sql::<Nullable<Timestamp>>("MIN(COALESCE(data_emissao, competencia::timestamp))"),
+                dsl::sql::<Nullable<Timestamp>>("MAX(COALESCE(data_emissao, competencia::timestamp))"),
+            ))
+            .first(connection)?;
+
+        Ok(Self {
+            deputado,
+            total,
+            despesas,
+            primeira_despesa,
+            ultima_despesa,
+            por_ano,
+            por_categoria,
+            principais_fornecedores,
+        })
+    }
+}
@@ -1,37 +1,54 @@
-use chrono::NaiveDateTime;
-use diesel::{dsl::sum, prelude::*, result::Error};
+use bigdecimal::BigDecimal;
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime, NaiveTime};
+use diesel::{dsl, pg::Pg, prelude::*, query_builder::{AstPass, QueryFragment, QueryId}, result::Error, sql_types::{Array, BigInt, Integer, Timestamp}};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-use crate::{models::deputado::Deputado, schema::expenses};
+use crate::{models::{competencia, deputado::Deputado, money, pagination::{Chave, Cursor, Links, Ordenavel, Pagina, MAX_PAGE_SIZE}, resumo, VersaoDados}, schema::expenses, types::{Cpf, DocumentoFornecedor, Uf}};
+
+// Número de despesas lidas de cada vez nas exportações.
+const EXPORT_BATCH_SIZE: i64 = 1000;
+
+// A tabela de despesas é particionada pelo ano da competência; veja a migração partition_expenses.
+define_sql_function!(fn cria_particao_despesas(ano: Integer) -> Bool);
+define_sql_function!(fn remove_particao_despesas(ano: Integer) -> Nullable<BigInt>);
 
 #[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq)]
 #[diesel(belongs_to(Deputado))]
 #[diesel(table_name = expenses)]
 pub struct Expense {
     pub id: i32,
-    pub data_emissao: NaiveDateTime,
+    pub data_emissao: Option<NaiveDateTime>,
     pub fornecedor: String,
-    pub valor_liquido: f32,
+    pub valor_liquido: BigDecimal,
     pub url_documento: Option<String>,
     pub deputado_id: i32,
+    pub competencia: NaiveDate,
+    pub fornecedor_documento: Option<String>,
+    pub categoria: String,
 }
 
 #[derive(Insertable, Debug)]
 #[diesel(table_name = expenses)]
 pub struct NewExpense {
     pub fornecedor: String,
-    pub valor_liquido: f32,
-    pub data_emissao: NaiveDateTime,
+    pub fornecedor_documento: Option<String>,
+    pub valor_liquido: BigDecimal,
+    pub data_emissao: Option<NaiveDateTime>,
+    pub competencia: NaiveDate,
     pub url_documento: Option<String>,
     pub deputado_id: i32,
+    pub categoria: String,
 }
 
 #[derive(Deserialize)]
 pub struct ExpenseFromCsv {
     #[serde(rename = "txtFornecedor")]
     pub fornecedor: String,
-    #[serde(rename = "vlrLiquido")]
-    pub valor_liquido: f32,
+    #[serde(rename = "txtCNPJCPF")]
+    pub fornecedor_documento: Option<String>,
+    #[serde(rename = "vlrLiquido", with = "money")]
+    pub valor_liquido: BigDecimal,
     #[serde(rename = "datEmissao")]
     pub data_emissao: Option<String>,
     #[serde(rename = "numMes")]
@@ -40,23 +57,32 @@ pub struct ExpenseFromCsv {
     pub ano: i32,
     #[serde(rename = "urlDocumento")]
     pub url_documento: Option<String>,
+    #[serde(rename = "txtDescricao")]
+    pub categoria: String,
 }
 
 diesel::table! {
     despesa_com_deputado (expense_id) {
         expense_id -> Int4,
         data_emissao -> Nullable<Timestamp>,
+        competencia -> Date,
         fornecedor -> Varchar,
-        valor_liquido -> Float4,
+        valor_liquido -> Numeric,
         url_documento -> Nullable<Varchar>,
         nome -> Varchar,
         cpf -> Varchar,
         #[max_length = 2]
         uf -> Bpchar,
+        fornecedor_documento -> Nullable<Varchar>,
+        grupo_fornecedor_id -> Nullable<Int4>,
+        fornecedor_canonico -> Varchar,
+        categoria -> Varchar,
+        deputado_id -> Int4,
+        partido -> Nullable<Varchar>,
     }
 }
 
-#[derive(Debug, Queryable, Selectable, Serialize, Identifiable, Associations, PartialEq)]
+#[derive(Debug, Queryable, QueryableByName, Selectable, Serialize, Identifiable, Associations, PartialEq, ToSchema)]
 #[diesel(belongs_to(Expense))]
 #[diesel(primary_key(expense_id))]
 #[diesel(table_name = despesa_com_deputado)]
@@ -64,14 +90,22 @@ diesel::table! {
 pub struct DespesaSemDeputado {
     pub expense_id: i32,
     pub data_emissao: Option<NaiveDateTime>,
+    #[serde(serialize_with = "competencia::serialize")]
+    #[schema(value_type = String, example = "2025-02")]
+    pub competencia: NaiveDate,
     pub fornecedor: String,
-    pub valor_liquido: f32,
+    #[serde(with = "money")]
+    #[schema(value_type = String, example = "1467.50")]
+    pub valor_liquido: BigDecimal,
     pub url_documento: Option<String>,
+    pub categoria: String,
+    #[serde(skip)]
+    pub fornecedor_canonico: String,
     // pub nome: String,
     // pub cpf: String,
 }
 
-#[derive(Debug, Queryable, Selectable, Serialize, Identifiable, Associations, PartialEq)]
+#[derive(Debug, Queryable, QueryableByName, Selectable, Serialize, Identifiable, Associations, PartialEq, ToSchema)]
 #[diesel(belongs_to(Expense))]
 #[diesel(primary_key(expense_id))]
 #[diesel(table_name = despesa_com_deputado)]
@@ -79,70 +113,483 @@ pub struct DespesaSemDeputado {
 pub struct DespesaComDeputado {
     pub expense_id: i32,
     pub data_emissao: Option<NaiveDateTime>,
+    #[serde(serialize_with = "competencia::serialize")]
+    #[schema(value_type = String, example = "2025-02")]
+    pub competencia: NaiveDate,
     pub fornecedor: String,
-    pub valor_liquido: f32,
+    #[serde(with = "money")]
+    #[schema(value_type = String, example = "1467.50")]
+    pub valor_liquido: BigDecimal,
     pub url_documento: Option<String>,
+    pub categoria: String,
+    #[serde(skip)]
+    pub fornecedor_canonico: String,
     pub nome: String,
-    pub cpf: String,
+    pub cpf: Cpf,
 }
 
-impl Expense {
-    pub fn get_expenses_by_cpf(connection: &mut PgConnection, cpf_busca: &str, mut page: u32) -> Result<Vec<DespesaSemDeputado>, Error> {
+// Despesa com os campos exportados em CSV e em XLSX; veja export. É também a despesa da API
+// GraphQL, que usa o deputado e o documento do fornecedor para resolver os objetos relacionados.
+#[derive(Debug, Queryable, QueryableByName, Selectable, Clone, PartialEq)]
+#[diesel(table_name = despesa_com_deputado)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct DespesaExportada {
+    pub expense_id: i32,
+    pub deputado_id: i32,
+    pub nome: String,
+    pub cpf: Cpf,
+    pub uf: Uf,
+    pub partido: Option<String>,
+    pub categoria: String,
+    pub fornecedor: String,
+    pub fornecedor_documento: Option<DocumentoFornecedor>,
+    pub fornecedor_canonico: String,
+    pub data_emissao: Option<NaiveDateTime>,
+    pub valor_liquido: BigDecimal,
+    pub competencia: NaiveDate,
+    pub url_documento: Option<String>,
+}
+
+// Data pela qual as despesas são filtradas: a de emissão do documento fiscal, que pode estar
+// ausente, ou a competência, isto é, o mês ao qual a despesa se refere.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DateField {
+    Emissao,
+    #[default]
+    Competencia,
+}
+
+// Intervalo de datas, inclusivo, usado para filtrar as despesas. Ao filtrar pela competência,
+// considera-se o mês inteiro das datas informadas.
+#[derive(Deserialize, Clone, Default, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DateFilter {
+    #[serde(default)]
+    #[param(inline)]
+    pub data: DateField,
+    pub de: Option<NaiveDate>,
+    pub ate: Option<NaiveDate>,
+}
+
+impl DateFilter {
+    pub fn apply<'a>(&self, mut query: despesa_com_deputado::BoxedQuery<'a, Pg>) -> despesa_com_deputado::BoxedQuery<'a, Pg> {
         use self::despesa_com_deputado::dsl::*;
-        if page == 0 { page = 1; }
-
-        Ok(
-            despesa_com_deputado
-            .filter(cpf.eq(cpf_busca))
-            .select(DespesaSemDeputado::as_select())
-            .limit(20)
-            .offset(20 * (page as i64 - 1))
-            .load(connection)?
-        )
+
+        match self.data {
+            DateField::Emissao => {
+                if let Some(de) = self.de {
+                    query = query.filter(data_emissao.ge(de.and_hms_opt(0, 0, 0).unwrap()));
+                }
+                if let Some(ate) = self.ate.and_then(|ate| ate.checked_add_days(Days::new(1))) {
+                    query = query.filter(data_emissao.lt(ate.and_hms_opt(0, 0, 0).unwrap()));
+                }
+            },
+            DateField::Competencia => {
+                if let Some(de) = self.de.and_then(|de| de.with_day(1)) {
+                    query = query.filter(competencia.ge(de));
+                }
+                if let Some(ate) = self.ate {
+                    query = query.filter(competencia.le(ate));
+                }
+            },
+        }
+
+        query
     }
+}
 
-    pub fn get_expenses_by_uf(connection: &mut PgConnection, uf_busca: &str, mut page: u32) -> Result<Vec<DespesaComDeputado>, Error> {
-        use self::despesa_com_deputado::dsl::*;
-        if page == 0 { page = 1; }
-
-        Ok(
-            despesa_com_deputado
-            .filter(uf.eq(uf_busca))
-            .select(DespesaComDeputado::as_select())
-            .limit(20)
-            .offset(20 * (page as i64 - 1))
-            .load(connection)?
-        )
+// Campo pelo qual as despesas são ordenadas. A ordenação por data usa a competência e, dentro de
+// cada competência, a data de emissão, ou o início da competência quando esta estiver ausente.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpenseOrder {
+    #[default]
+    Data,
+    Valor,
+    Fornecedor,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl Direction {
+    fn reverse(self) -> Self {
+        match self {
+            Direction::Asc => Direction::Desc,
+            Direction::Desc => Direction::Asc,
+        }
     }
+}
+
+// Filtros da busca de despesas; todos são opcionais, e podem ser combinados livremente. O
+// fornecedor é buscado como parte do nome, tanto no nome original quanto no canônico; os
+// intervalos de datas e de valores são inclusivos.
+#[derive(Deserialize, Clone, Default, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExpenseFilter {
+    pub uf: Option<Uf>,
+    pub cpf: Option<Cpf>,
+    pub partido: Option<String>,
+    pub fornecedor: Option<String>,
+    pub categoria: Option<String>,
+    pub emissao_de: Option<NaiveDate>,
+    pub emissao_ate: Option<NaiveDate>,
+    pub competencia_de: Option<NaiveDate>,
+    pub competencia_ate: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "money::deserialize_option")]
+    #[param(value_type = Option<String>)]
+    pub valor_min: Option<BigDecimal>,
+    #[serde(default, deserialize_with = "money::deserialize_option")]
+    #[param(value_type = Option<String>)]
+    pub valor_max: Option<BigDecimal>,
+    #[serde(default)]
+    #[param(inline)]
+    pub ordem: ExpenseOrder,
+    #[serde(default)]
+    #[param(inline)]
+    pub direcao: Direction,
+}
 
-    pub fn sum_all_by_cpf(connection: &mut PgConnection, cpf_busca: &str) -> Result<f32, Error> {
+impl ExpenseFilter {
+    pub fn apply<'a>(&self, mut query: despesa_com_deputado::BoxedQuery<'a, Pg>) -> despesa_com_deputado::BoxedQuery<'a, Pg> {
         use self::despesa_com_deputado::dsl::*;
 
-        let result: Option<f32> = 
-            despesa_com_deputado
-            .filter(cpf.eq(cpf_busca))
-            .select(sum(valor_liquido))
-            .first(connection)?;
+        if let Some(busca) = &self.uf {
+            query = query.filter(uf.eq(busca.clone()));
+        }
+        if let Some(busca) = &self.cpf {
+            query = query.filter(cpf.eq(busca.clone()));
+        }
+        if let Some(busca) = &self.partido {
+            query = query.filter(partido.eq(busca.trim().to_uppercase()));
+        }
+        if let Some(busca) = &self.fornecedor {
+            let padrao = format!("%{}%", busca.trim().replace('%', "\\%").replace('_', "\\_"));
+            query = query.filter(fornecedor.ilike(padrao.clone()).or(fornecedor_canonico.ilike(padrao)));
+        }
+        if let Some(busca) = &self.categoria {
+            query = query.filter(categoria.eq(busca.trim().to_string()));
+        }
+
+        query = DateFilter { data: DateField::Emissao, de: self.emissao_de, ate: self.emissao_ate }.apply(query);
+        query = DateFilter { data: DateField::Competencia, de: self.competencia_de, ate: self.competencia_ate }.apply(query);
 
-        if let Some(s) = result {
-            Ok(s)
-        } else {
-            Err(Error::NotFound)
+        if let Some(minimo) = &self.valor_min {
+            query = query.filter(valor_liquido.ge(minimo.clone()));
+        }
+        if let Some(maximo) = &self.valor_max {
+            query = query.filter(valor_liquido.le(maximo.clone()));
         }
+
+        query
     }
+}
 
-    pub fn sum_all(connection: &mut PgConnection) -> Result<f32, Error> {
-        use crate::schema::expenses::dsl::*;
+// Data usada na ordenação por data; veja ExpenseOrder.
+fn data_de_ordenacao() -> diesel::expression::SqlLiteral<Timestamp> {
+    dsl::sql::<Timestamp>("COALESCE(data_emissao, competencia::timestamp)")
+}
 
-        let result: Option<f32> = 
-            expenses
-            .select(sum(valor_liquido))
-            .first(connection)?;
+// Ordena a consulta pelo campo escolhido; o identificador da despesa desempata a ordenação, para que
+// a paginação seja estável.
+fn order<'a>(query: despesa_com_deputado::BoxedQuery<'a, Pg>, ordem: ExpenseOrder, direcao: Direction) -> despesa_com_deputado::BoxedQuery<'a, Pg> {
+    use self::despesa_com_deputado::dsl::*;
 
-        if let Some(s) = result {
-            Ok(s)
-        } else {
-            Err(Error::NotFound)
-        }
+    match (ordem, direcao) {
+        (ExpenseOrder::Data, Direction::Asc) => query.order_by((competencia.asc(), data_de_ordenacao().asc(), expense_id.asc())),
+        (ExpenseOrder::Data, Direction::Desc) => query.order_by((competencia.desc(), data_de_ordenacao().desc(), expense_id.desc())),
+        (ExpenseOrder::Valor, Direction::Asc) => query.order_by((valor_liquido.asc(), expense_id.asc())),
+        (ExpenseOrder::Valor, Direction::Desc) => query.order_by((valor_liquido.desc(), expense_id.desc())),
+        (ExpenseOrder::Fornecedor, Direction::Asc) => query.order_by((fornecedor_canonico.asc(), expense_id.asc())),
+        (ExpenseOrder::Fornecedor, Direction::Desc) => query.order_by((fornecedor_canonico.desc(), expense_id.desc())),
+    }
+}
+
+// Restringe a consulta às despesas posteriores à chave na ordenação, caso `maior` seja verdadeiro,
+// ou às anteriores a ela.
+fn seek<'a>(query: despesa_com_deputado::BoxedQuery<'a, Pg>, chave: &Chave, maior: bool) -> despesa_com_deputado::BoxedQuery<'a, Pg> {
+    use self::despesa_com_deputado::dsl::*;
+
+    match (chave.clone(), maior) {
+        (Chave::Data { competencia: c, data, id }, true) => query.filter(
+            competencia.gt(c).or(competencia.eq(c).and(
+                data_de_ordenacao().gt(data).or(data_de_ordenacao().eq(data).and(expense_id.gt(id)))
+            ))
+        ),
+        (Chave::Data { competencia: c, data, id }, false) => query.filter(
+            competencia.lt(c).or(competencia.eq(c).and(
+                data_de_ordenacao().lt(data).or(data_de_ordenacao().eq(data).and(expense_id.lt(id)))
+            ))
+        ),
+        (Chave::Valor { valor, id }, true) => query.filter(
+            valor_liquido.gt(valor.clone()).or(valor_liquido.eq(valor).and(expense_id.gt(id)))
+        ),
+        (Chave::Valor { valor, id }, false) => query.filter(
+            valor_liquido.lt(valor.clone()).or(valor_liquido.eq(valor).and(expense_id.lt(id)))
+        ),
+        (Chave::Fornecedor { fornecedor: valor_chave, id }, true) => query.filter(
+            fornecedor_canonico.gt(valor_chave.clone()).or(fornecedor_canonico.eq(valor_chave).and(expense_id.gt(id)))
+        ),
+        (Chave::Fornecedor { fornecedor: valor_chave, id }, false) => query.filter(
+            fornecedor_canonico.lt(valor_chave.clone()).or(fornecedor_canonico.eq(valor_chave).and(expense_id.lt(id)))
+        ),
+    }
+}
+
+// Busca uma página das despesas retornadas por `query`, na ordem informada, a partir do cursor.
+// `query` é chamada duas vezes, para contar as despesas e para buscar a página; `load` seleciona os
+// campos da página.
+fn paginate<'a, T: Ordenavel>(
+    connection: &mut PgConnection,
+    query: impl Fn() -> despesa_com_deputado::BoxedQuery<'a, Pg>,
+    ordem: ExpenseOrder,
+    direcao: Direction,
+    cursor: Option<&Cursor>,
+    page_size: i64,
+    load: impl FnOnce(&mut PgConnection, despesa_com_deputado::BoxedQuery<'a, Pg>) -> Result<Vec<T>, Error>) -> Result<Pagina<T>, Error> {
+    let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+    let total: i64 = query().count().get_result(connection)?;
+
+    let anterior = cursor.is_some_and(|cursor| cursor.anterior);
+    let mut consulta = query();
+    if let Some(cursor) = cursor {
+        consulta = seek(consulta, &cursor.chave, (direcao == Direction::Asc) != anterior);
+    }
+    let sentido = if anterior { direcao.reverse() } else { direcao };
+
+    // Uma despesa além do tamanho da página indica que há mais páginas nesse sentido.
+    let mut dados = load(connection, order(consulta, ordem, sentido).limit(page_size + 1))?;
+    let mais = dados.len() as i64 > page_size;
+    dados.truncate(page_size as usize);
+    if anterior {
+        dados.reverse();
+    }
+
+    let (tem_proxima, tem_anterior) = if anterior { (true, mais) } else { (mais, cursor.is_some()) };
+    let cursor_de = |item: Option<&T>, anterior: bool| {
+        item.map(|item| Cursor { chave: item.chave(ordem), direcao, anterior }.encode())
+    };
+
+    Ok(Pagina {
+        next: if tem_proxima { cursor_de(dados.last(), false) } else { None },
+        prev: if tem_anterior { cursor_de(dados.first(), true) } else { None },
+        dados,
+        total,
+        page_size,
+        links: Links::default(),
+    })
+}
+
+fn chave(ordem: ExpenseOrder, expense_id: i32, competencia: NaiveDate, data_emissao: Option<NaiveDateTime>, valor: &BigDecimal, fornecedor: &str) -> Chave {
+    match ordem {
+        ExpenseOrder::Data => Chave::Data {
+            competencia,
+            data: data_emissao.unwrap_or(competencia.and_time(NaiveTime::MIN)),
+            id: expense_id,
+        },
+        ExpenseOrder::Valor => Chave::Valor { valor: valor.clone(), id: expense_id },
+        ExpenseOrder::Fornecedor => Chave::Fornecedor { fornecedor: fornecedor.to_string(), id: expense_id },
+    }
+}
+
+// Formato em que as despesas são lidas nas listagens e nas exportações; nestas, `declara` abre o
+// cursor das exportações sobre a consulta, com as colunas do formato, e os lotes são lidos dele
+// por nome.
+pub trait LinhaDespesa: Ordenavel + QueryableByName<Pg> + Sized + 'static {
+    fn load(connection: &mut PgConnection, query: despesa_com_deputado::BoxedQuery<'_, Pg>) -> Result<Vec<Self>, Error>;
+
+    fn declara(connection: &mut PgConnection, query: despesa_com_deputado::BoxedQuery<'_, Pg>) -> Result<usize, Error>;
+}
+
+impl LinhaDespesa for DespesaComDeputado {
+    fn load(connection: &mut PgConnection, query: despesa_com_deputado::BoxedQuery<'_, Pg>) -> Result<Vec<Self>, Error> {
+        query.select(Self::as_select()).load(connection)
+    }
+
+    fn declara(connection: &mut PgConnection, query: despesa_com_deputado::BoxedQuery<'_, Pg>) -> Result<usize, Error> {
+        DeclaraCursor(query.select(Self::as_select())).execute(connection)
+    }
+}
+
+impl LinhaDespesa for DespesaSemDeputado {
+    fn load(connection: &mut PgConnection, query: despesa_com_deputado::BoxedQuery<'_, Pg>) -> Result<Vec<Self>, Error> {
+        query.select(Self::as_select()).load(connection)
+    }
+
+    fn declara(connection: &mut PgConnection, query: despesa_com_deputado::BoxedQuery<'_, Pg>) -> Result<usize, Error> {
+        DeclaraCursor(query.select(Self::as_select())).execute(connection)
+    }
+}
+
+impl LinhaDespesa for DespesaExportada {
+    fn load(connection: &mut PgConnection, query: despesa_com_deputado::BoxedQuery<'_, Pg>) -> Result<Vec<Self>, Error> {
+        query.select(Self::as_select()).load(connection)
+    }
+
+    fn declara(connection: &mut PgConnection, query: despesa_com_deputado::BoxedQuery<'_, Pg>) -> Result<usize, Error> {
+        DeclaraCursor(query.select(Self::as_select())).execute(connection)
+    }
+}
+
+// Nome do cursor das exportações; cada exportação usa uma conexão própria, e o cursor dura apenas
+// a sua transação.
+const EXPORT_CURSOR: &str = "exportacao";
+
+// Declara o cursor das exportações sobre uma consulta, mantendo os seus parâmetros.
+struct DeclaraCursor<Q>(Q);
+
+impl<Q: QueryFragment<Pg>> QueryFragment<Pg> for DeclaraCursor<Q> {
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+        out.push_sql("DECLARE ");
+        out.push_identifier(EXPORT_CURSOR)?;
+        out.push_sql(" NO SCROLL CURSOR FOR ");
+        self.0.walk_ast(out.reborrow())
+    }
+}
+
+impl<Q> QueryId for DeclaraCursor<Q> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<Q> RunQueryDsl<PgConnection> for DeclaraCursor<Q> {}
+
+impl Ordenavel for DespesaComDeputado {
+    fn chave(&self, ordem: ExpenseOrder) -> Chave {
+        chave(ordem, self.expense_id, self.competencia, self.data_emissao, &self.valor_liquido, &self.fornecedor_canonico)
+    }
+}
+
+impl Ordenavel for DespesaExportada {
+    fn chave(&self, ordem: ExpenseOrder) -> Chave {
+        chave(ordem, self.expense_id, self.competencia, self.data_emissao, &self.valor_liquido, &self.fornecedor_canonico)
+    }
+}
+
+impl Ordenavel for DespesaSemDeputado {
+    fn chave(&self, ordem: ExpenseOrder) -> Chave {
+        chave(ordem, self.expense_id, self.competencia, self.data_emissao, &self.valor_liquido, &self.fornecedor_canonico)
+    }
+}
+
+impl Expense {
+    // Busca uma página das despesas que satisfazem os filtros, na ordem escolhida.
+    pub fn search<T: LinhaDespesa>(connection: &mut PgConnection, filtro: &ExpenseFilter, cursor: Option<&Cursor>, page_size: i64) -> Result<Pagina<T>, Error> {
+        paginate(
+            connection,
+            || filtro.apply(despesa_com_deputado::table.into_boxed()),
+            filtro.ordem,
+            filtro.direcao,
+            cursor,
+            page_size,
+            T::load,
+        )
+    }
+
+    // Busca as despesas mais recentes de cada um dos deputados informados, até `limite` por
+    // deputado, numa única consulta, ordenadas por deputado e da mais recente à mais antiga.
+    pub fn latest_by_deputies(connection: &mut PgConnection, ids: &[i32], limite: i64) -> Result<Vec<DespesaExportada>, Error> {
+        diesel::sql_query(
+            "SELECT * FROM (
+                SELECT *, row_number() OVER (
+                    PARTITION BY deputado_id
+                    ORDER BY competencia DESC, COALESCE(data_emissao, competencia::timestamp) DESC, expense_id DESC
+                ) AS posicao
+                FROM despesa_com_deputado
+                WHERE deputado_id = ANY($1)
+            ) despesas
+            WHERE posicao <= $2
+            ORDER BY deputado_id, posicao"
+        )
+        .bind::<Array<Integer>, _>(ids)
+        .bind::<BigInt, _>(limite)
+        .load(connection)
+    }
+
+    // Percorre todas as despesas que satisfazem os filtros, na ordem escolhida, sem o limite de
+    // tamanho das páginas. As despesas são lidas em lotes de um cursor, numa única transação, de
+    // modo que a exportação reflete um mesmo momento dos dados, o da declaração do cursor; cada lote
+    // é entregue a `lote`, que retorna falso para interromper a leitura, e o próximo só é lido
+    // depois que o anterior é entregue.
+    pub fn export<T: LinhaDespesa>(connection: &mut PgConnection, filtro: &ExpenseFilter, periodo: &DateFilter, mut lote: impl FnMut(Vec<T>) -> bool) -> Result<(), Error> {
+        connection.transaction(|connection| {
+            let query = periodo.apply(filtro.apply(despesa_com_deputado::table.into_boxed()));
+            T::declara(connection, order(query, filtro.ordem, filtro.direcao))?;
+
+            let fetch = format!("FETCH {EXPORT_BATCH_SIZE} FROM {EXPORT_CURSOR}");
+            loop {
+                let dados: Vec<T> = diesel::sql_query(&fetch).load(connection)?;
+                let fim = (dados.len() as i64) < EXPORT_BATCH_SIZE;
+
+                if !lote(dados) || fim {
+                    break;
+                }
+            }
+
+            // O cursor seria fechado ao fim da transação, mas não ao fim de um savepoint, caso a
+            // exportação seja executada dentro de outra transação.
+            diesel::sql_query(format!("CLOSE {EXPORT_CURSOR}")).execute(connection)?;
+
+            Ok(())
+        })
+    }
+
+    // Cria a partição das despesas com competência no ano informado, caso ainda não exista,
+    // retornando verdadeiro caso a tenha criado.
+    pub fn create_partition(connection: &mut PgConnection, ano: i32) -> Result<bool, Error> {
+        diesel::select(cria_particao_despesas(ano)).get_result(connection)
+    }
+
+    // Remove todas as despesas com competência no ano informado, descartando a sua partição, e os
+    // totais correspondentes. Retorna o número de despesas removidas, ou NotFound caso não haja
+    // partição para o ano.
+    pub fn purge_year(connection: &mut PgConnection, ano: i32) -> Result<i64, Error> {
+        connection.transaction(|connection| {
+            let removidas: Option<i64> = diesel::select(remove_particao_despesas(ano)).get_result(connection)?;
+            let removidas = removidas.ok_or(Error::NotFound)?;
+
+            resumo::purge_rollups(connection, ano)?;
+            VersaoDados::bump(connection)?;
+
+            Ok(removidas)
+        })
+    }
+
+    // Lista as despesas do deputado com o CPF informado, por data, paginadas por cursor.
+    pub fn get_expenses_by_cpf(connection: &mut PgConnection, cpf_busca: &Cpf, periodo: &DateFilter, cursor: Option<&Cursor>, page_size: i64) -> Result<Pagina<DespesaSemDeputado>, Error> {
+        let filtro = ExpenseFilter { cpf: Some(cpf_busca.clone()), ..Default::default() };
+
+        paginate(
+            connection,
+            || periodo.apply(filtro.apply(despesa_com_deputado::table.into_boxed())),
+            ExpenseOrder::Data,
+            Direction::Asc,
+            cursor,
+            page_size,
+            |connection, query| query.select(DespesaSemDeputado::as_select()).load(connection),
+        )
+    }
+
+    // Lista as despesas de uma unidade federativa, por data, paginadas por cursor.
+    pub fn get_expenses_by_uf(connection: &mut PgConnection, uf_busca: &Uf, periodo: &DateFilter, cursor: Option<&Cursor>, page_size: i64) -> Result<Pagina<DespesaComDeputado>, Error> {
+        let filtro = ExpenseFilter { uf: Some(uf_busca.clone()), ..Default::default() };
+
+        paginate(
+            connection,
+            || periodo.apply(filtro.apply(despesa_com_deputado::table.into_boxed())),
+            ExpenseOrder::Data,
+            Direction::Asc,
+            cursor,
+            page_size,
+            |connection, query| query.select(DespesaComDeputado::as_select()).load(connection),
+        )
     }
 }
\ No newline at end of file
@@ -0,0 +1,451 @@
+use std::collections::HashMap;
+
+use bigdecimal::BigDecimal;
+use diesel::{dsl, pg::Pg, prelude::*, result::Error, sql_types::{Bool, Integer, Nullable, Text}};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{models::{despesa_com_deputado, money, pagination::{PaginaNumerada, MAX_PAGE_SIZE}, Ano, Categoria, Deputado, Total, VersaoDados}, privacy::Privacidade, schema::{deputados, fornecedores_agrupados, grupos_fornecedores}, types::{Cnpj, DocumentoFornecedor}};
+
+// Grupo de variantes do nome de um mesmo fornecedor. `nome` é o nome canônico, usado em todas as
+// agregações por fornecedor, e `confianca` é a menor confiança entre as variantes do grupo.
+#[derive(Queryable, Selectable, Identifiable, Serialize, Debug, PartialEq, ToSchema)]
+#[diesel(table_name = grupos_fornecedores)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct GrupoFornecedor {
+    pub id: i32,
+    pub nome: String,
+    pub documento: Option<Cnpj>,
+    pub confianca: f32,
+    pub manual: bool,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = grupos_fornecedores)]
+pub struct NovoGrupoFornecedor {
+    pub nome: String,
+    pub documento: Option<Cnpj>,
+    pub confianca: f32,
+    pub manual: bool,
+}
+
+// Variante do nome de um fornecedor, tal como aparece nas despesas, e o grupo ao qual pertence.
+// `confianca` indica o quão certa é a associação da variante ao grupo, de 0 a 1.
+#[derive(Queryable, Selectable, Identifiable, Associations, Insertable, Serialize, Debug, PartialEq, ToSchema)]
+#[diesel(belongs_to(GrupoFornecedor, foreign_key = grupo_id))]
+#[diesel(primary_key(fornecedor))]
+#[diesel(table_name = fornecedores_agrupados)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct FornecedorAgrupado {
+    pub fornecedor: String,
+    #[serde(skip)]
+    pub grupo_id: i32,
+    pub confianca: f32,
+    pub manual: bool,
+}
+
+// Grupo de fornecedores acompanhado das suas variantes.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct GrupoComVariantes {
+    #[serde(flatten)]
+    pub grupo: GrupoFornecedor,
+    pub variantes: Vec<FornecedorAgrupado>,
+}
+
+impl GrupoFornecedor {
+    // Lista os grupos, vinte por página, em ordem alfabética, opcionalmente apenas aqueles cujo
+    // nome canônico contém o texto informado.
+    pub fn list(connection: &mut PgConnection, busca: Option<&str>, mut page: u32) -> Result<Vec<GrupoComVariantes>, Error> {
+        use crate::schema::grupos_fornecedores::dsl::*;
+        if page == 0 { page = 1; }
+
+        let mut query = grupos_fornecedores.into_boxed();
+        if let Some(busca) = busca {
+            query = query.filter(nome.ilike(format!("%{}%", busca.replace('%', "\\%").replace('_', "\\_"))));
+        }
+
+        let grupos = query
+            .order_by((nome, id))
+            .limit(20)
+            .offset(20 * (page as i64 - 1))
+            .select(GrupoFornecedor::as_select())
+            .load(connection)?;
+
+        Self::with_variants(connection, grupos)
+    }
+
+    pub fn get(connection: &mut PgConnection, grupo: i32) -> Result<GrupoComVariantes, Error> {
+        let grupo = grupos_fornecedores::table
+            .find(grupo)
+            .select(GrupoFornecedor::as_select())
+            .first(connection)?;
+
+        Ok(Self::with_variants(connection, vec![grupo])?.remove(0))
+    }
+
+    fn with_variants(connection: &mut PgConnection, grupos: Vec<GrupoFornecedor>) -> Result<Vec<GrupoComVariantes>, Error> {
+        let variantes = FornecedorAgrupado::belonging_to(&grupos)
+            .select(FornecedorAgrupado::as_select())
+            .order_by(fornecedores_agrupados::fornecedor)
+            .load(connection)?;
+
+        Ok(
+            variantes
+                .grouped_by(&grupos)
+                .into_iter()
+                .zip(grupos)
+                .map(|(variantes, grupo)| GrupoComVariantes { grupo, variantes })
+                .collect()
+        )
+    }
+
+    // Une os grupos `origens` ao grupo `destino`, movendo suas variantes para ele e removendo-os. O
+    // grupo resultante e todas as suas variantes passam a ser manuais, com confiança máxima.
+    pub fn merge(connection: &mut PgConnection, destino: i32, origens: &[i32]) -> Result<GrupoComVariantes, Error> {
+        connection.transaction(|connection| {
+            use crate::schema::fornecedores_agrupados::dsl as agrupados;
+            use crate::schema::grupos_fornecedores::dsl as grupos;
+
+            // Garante que o grupo de destino existe antes de alterar os demais.
+            Self::get(connection, destino)?;
+
+            let origens: Vec<i32> = origens.iter().copied().filter(|origem| *origem != destino).collect();
+
+            diesel::update(agrupados::fornecedores_agrupados.filter(agrupados::grupo_id.eq_any(&origens)))
+                .set(agrupados::grupo_id.eq(destino))
+                .execute(connection)?;
+
+            diesel::delete(grupos::grupos_fornecedores.filter(grupos::id.eq_any(&origens)))
+                .execute(connection)?;
+
+            Self::mark_manual(connection, destino)?;
+            VersaoDados::bump(connection)?;
+
+            Self::get(connection, destino)
+        })
+    }
+
+    // Separa as variantes `fornecedores` do grupo `origem`, criando com elas um novo grupo manual,
+    // cujo nome canônico é `nome` ou, na sua ausência, a primeira variante. Retorna NotFound caso
+    // nenhuma das variantes pertença ao grupo, ou caso todas pertençam, o que o deixaria vazio.
+    pub fn split(connection: &mut PgConnection, origem: i32, fornecedores: &[String], nome: Option<String>) -> Result<GrupoComVariantes, Error> {
+        connection.transaction(|connection| {
+            use crate::schema::fornecedores_agrupados::dsl as agrupados;
+
+            let grupo_origem = Self::get(connection, origem)?;
+            let variantes: Vec<&String> = fornecedores
+                .iter()
+                .filter(|fornecedor| grupo_origem.variantes.iter().any(|v| &v.fornecedor == *fornecedor))
+                .collect();
+
+            if variantes.is_empty() || variantes.len() == grupo_origem.variantes.len() {
+                return Err(Error::NotFound);
+            }
+
+            let novo: GrupoFornecedor = diesel::insert_into(grupos_fornecedores::table)
+                .values(NovoGrupoFornecedor {
+                    nome: nome.unwrap_or_else(|| variantes[0].clone()),
+                    documento: None,
+                    confianca: 1.0,
+                    manual: true,
+                })
+                .returning(GrupoFornecedor::as_returning())
+                .get_result(connection)?;
+
+            diesel::update(agrupados::fornecedores_agrupados.filter(agrupados::fornecedor.eq_any(&variantes)))
+                .set(agrupados::grupo_id.eq(novo.id))
+                .execute(connection)?;
+
+            Self::mark_manual(connection, origem)?;
+            Self::mark_manual(connection, novo.id)?;
+            VersaoDados::bump(connection)?;
+
+            Self::get(connection, novo.id)
+        })
+    }
+
+    fn mark_manual(connection: &mut PgConnection, grupo: i32) -> Result<(), Error> {
+        use crate::schema::fornecedores_agrupados::dsl as agrupados;
+        use crate::schema::grupos_fornecedores::dsl as grupos;
+
+        diesel::update(grupos::grupos_fornecedores.find(grupo))
+            .set((grupos::manual.eq(true), grupos::confianca.eq(1.0)))
+            .execute(connection)?;
+
+        diesel::update(agrupados::fornecedores_agrupados.filter(agrupados::grupo_id.eq(grupo)))
+            .set((agrupados::manual.eq(true), agrupados::confianca.eq(1.0)))
+            .execute(connection)?;
+
+        Ok(())
+    }
+}
+
+// Os fornecedores, do ponto de vista das despesas, são identificados pelo documento (CNPJ ou CPF)
+// informado no arquivo da CEAP, armazenado apenas com os dígitos; despesas sem documento não são
+// atribuídas a nenhum fornecedor. O nome de um fornecedor é o nome canônico do seu grupo.
+
+// Fornecedor acompanhado do total recebido, do número de despesas e do número de deputados que
+// lhe pagaram.
+#[derive(Queryable, Serialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct FornecedorComTotal {
+    pub documento: DocumentoFornecedor,
+    pub nome: String,
+    #[serde(with = "money")]
+    #[schema(value_type = String, example = "1467.50")]
+    pub total: BigDecimal,
+    pub despesas: i64,
+    pub deputados: i64,
+}
+
+// Perfil de um fornecedor: os seus totais, os nomes com que aparece nas despesas, e os totais por
+// ano e por categoria.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PerfilFornecedor {
+    #[serde(flatten)]
+    pub fornecedor: FornecedorComTotal,
+    pub nomes: Vec<String>,
+    pub por_ano: Vec<Total<Ano>>,
+    pub por_categoria: Vec<Total<Categoria>>,
+}
+
+// Deputado que pagou um fornecedor, com o total pago e o número de despesas.
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub struct ClienteFornecedor {
+    #[serde(flatten)]
+    pub deputado: Deputado,
+    #[serde(with = "money")]
+    #[schema(value_type = String, example = "1467.50")]
+    pub total: BigDecimal,
+    pub despesas: i64,
+}
+
+// Restringe as despesas às dos fornecedores com alguma despesa cujo nome, original ou canônico,
+// contém o texto buscado, ou cujo documento começa por ele. Com os CPFs mascarados, apenas os CNPJs
+// são buscados pelo documento: a busca por um prefixo revelaria os CPFs que começam por ele.
+fn busca_fornecedores(busca: &str, privacidade: Privacidade) -> Box<dyn BoxableExpression<despesa_com_deputado::table, Pg, SqlType = Nullable<Bool>>> {
+    use self::despesa_com_deputado::dsl::*;
+
+    let variantes = diesel::alias!(crate::models::despesa_com_deputado as variantes);
+    let padrao = format!("%{}%", busca.replace('%', "\\%").replace('_', "\\_"));
+    let digitos: String = busca.chars().filter(char::is_ascii_digit).collect();
+    let documento = match privacidade {
+        _ if digitos.is_empty() => padrao.clone(),
+        // Cada _ corresponde a exatamente um caractere, de modo que só os documentos com 14 dígitos
+        // satisfazem o padrão.
+        Privacidade::Mascarada | Privacidade::Restrita => format!("{digitos}{}", "_".repeat(14usize.saturating_sub(digitos.len()))),
+        Privacidade::Revelada => format!("{digitos}%"),
+    };
+
+    Box::new(fornecedor_documento.eq_any(
+        variantes
+            .filter(
+                variantes.field(fornecedor).ilike(padrao.clone())
+                    .or(variantes.field(fornecedor_canonico).ilike(padrao))
+                    .or(variantes.field(fornecedor_documento).like(documento))
+            )
+            .select(variantes.field(fornecedor_documento))
+    ))
+}
+
+impl FornecedorComTotal {
+    // Lista os fornecedores, do que mais recebeu ao que menos recebeu, paginados por número.
+    pub fn list(connection: &mut PgConnection, busca: Option<&str>, privacidade: Privacidade, page: u32, page_size: i64) -> Result<PaginaNumerada<Self>, Error> {
+        use self::despesa_com_deputado::dsl::*;
+
+        let page = page.max(1);
+        let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+
+        let mut contagem = despesa_com_deputado
+            .filter(fornecedor_documento.is_not_null())
+            .select(dsl::count_distinct(fornecedor_documento))
+            .into_boxed();
+        if let Some(busca) = busca.map(str::trim).filter(|busca| !busca.is_empty()) {
+            contagem = contagem.filter(busca_fornecedores(busca, privacidade));
+        }
+
+        let total = contagem.get_result(connection)?;
+        let dados = Self::load(connection, busca, privacidade, Some((page_size, page_size * (page as i64 - 1))))?;
+
+        Ok(PaginaNumerada { dados, total, page, page_size })
+    }
+
+    // Como list, mas sem paginação; usada nas exportações.
+    pub fn all(connection: &mut PgConnection, busca: Option<&str>, privacidade: Privacidade) -> Result<Vec<Self>, Error> {
+        Self::load(connection, busca, privacidade, None)
+    }
+
+    // Fornecedores em ordem decrescente de total, opcionalmente limitados a uma página, informada
+    // como (limite, deslocamento).
+    fn load(connection: &mut PgConnection, busca: Option<&str>, privacidade: Privacidade, pagina: Option<(i64, i64)>) -> Result<Vec<Self>, Error> {
+        use self::despesa_com_deputado::dsl::*;
+
+        // Consultas já encapsuladas (boxed) não podem ser agrupadas; a busca é aplicada depois.
+        let mut query = despesa_com_deputado
+            .filter(fornecedor_documento.is_not_null())
+            .group_by(fornecedor_documento)
+            .select(colunas())
+            .order_by((dsl::sum(valor_liquido).desc(), fornecedor_documento))
+            .into_boxed();
+        if let Some(busca) = busca.map(str::trim).filter(|busca| !busca.is_empty()) {
+            query = query.filter(busca_fornecedores(busca, privacidade));
+        }
+        if let Some((limite, deslocamento)) = pagina {
+            query = query.limit(limite).offset(deslocamento);
+        }
+
+        query.load(connection)
+    }
+
+    pub fn get(connection: &mut PgConnection, documento: &DocumentoFornecedor) -> Result<Self, Error> {
+        use self::despesa_com_deputado::dsl::*;
+
+        despesa_com_deputado
+            .filter(fornecedor_documento.eq(documento))
+            .group_by(fornecedor_documento)
+            .select(colunas())
+            .first(connection)
+    }
+
+    // Como get, para vários documentos numa única consulta; os documentos sem despesas não constam
+    // do resultado.
+    pub fn get_many(connection: &mut PgConnection, documentos: &[String]) -> Result<Vec<Self>, Error> {
+        use self::despesa_com_deputado::dsl::*;
+
+        despesa_com_deputado
+            .filter(fornecedor_documento.eq_any(documentos))
+            .group_by(fornecedor_documento)
+            .select(colunas())
+            .load(connection)
+    }
+}
+
+// Nome de um fornecedor, nas consultas agrupadas pelo documento: o nome canônico do grupo ao qual o
+// documento pertence, atribuído pela resolução de fornecedores; caso o documento não pertença a
+// nenhum grupo, o nome canônico mais frequente nas suas despesas.
+fn nome_fornecedor() -> diesel::expression::SqlLiteral<Text> {
+    dsl::sql(
+        "COALESCE(\
+            (SELECT grupos_fornecedores.nome FROM grupos_fornecedores \
+                WHERE grupos_fornecedores.documento = despesa_com_deputado.fornecedor_documento \
+                ORDER BY grupos_fornecedores.manual DESC, grupos_fornecedores.id LIMIT 1), \
+            mode() WITHIN GROUP (ORDER BY despesa_com_deputado.fornecedor_canonico))"
+    )
+}
+
+#[allow(clippy::type_complexity)]
+fn colunas() -> (
+    dsl::AssumeNotNull<despesa_com_deputado::fornecedor_documento>,
+    diesel::expression::SqlLiteral<Text>,
+    dsl::AssumeNotNull<diesel::helper_types::sum<despesa_com_deputado::valor_liquido>>,
+    dsl::count_star,
+    dsl::count_distinct<despesa_com_deputado::deputado_id>,
+) {
+    use self::despesa_com_deputado::dsl::*;
+
+    (
+        fornecedor_documento.assume_not_null(),
+        nome_fornecedor(),
+        dsl::sum(valor_liquido).assume_not_null(),
+        dsl::count_star(),
+        dsl::count_distinct(deputado_id),
+    )
+}
+
+impl PerfilFornecedor {
+    pub fn get(connection: &mut PgConnection, documento: &DocumentoFornecedor) -> Result<Self, Error> {
+        use self::despesa_com_deputado::dsl::*;
+
+        let fornecedor_total = FornecedorComTotal::get(connection, documento)?;
+
+        let nomes = despesa_com_deputado
+            .filter(fornecedor_documento.eq(documento))
+            .select(fornecedor)
+            .distinct()
+            .order_by(fornecedor)
+            .load(connection)?;
+
+        let ano = dsl::sql::<Integer>("EXTRACT(YEAR FROM competencia)::integer");
+        let por_ano = despesa_com_deputado
+            .filter(fornecedor_documento.eq(documento))
+            .group_by(ano.clone())
+            .select((ano.clone(), dsl::sum(valor_liquido).assume_not_null(), dsl::count_star()))
+            .order_by(ano)
+            .load::<(i32, BigDecimal, i64)>(connection)?
+            .into_iter()
+            .map(|(chave, soma, quantidade)| Total { chave: Ano { ano: chave }, total: soma, despesas: quantidade })
+            .collect();
+
+        let por_categoria = despesa_com_deputado
+            .filter(fornecedor_documento.eq(documento))
+            .group_by(categoria)
+            .select((categoria, dsl::sum(valor_liquido).assume_not_null(), dsl::count_star()))
+            .order_by((dsl::sum(valor_liquido).desc(), categoria))
+            .load::<(String, BigDecimal, i64)>(connection)?
+            .into_iter()
+            .map(|(chave, soma, quantidade)| Total { chave: Categoria { categoria: chave }, total: soma, despesas: quantidade })
+            .collect();
+
+        Ok(Self { fornecedor: fornecedor_total, nomes, por_ano, por_categoria })
+    }
+}
+
+impl ClienteFornecedor {
+    // Lista os deputados que pagaram o fornecedor, do que mais pagou ao que menos pagou. Retorna
+    // NotFound caso o fornecedor não tenha despesas.
+    pub fn list(connection: &mut PgConnection, documento: &DocumentoFornecedor) -> Result<Vec<Self>, Error> {
+        use self::despesa_com_deputado::dsl::*;
+
+        let totais: Vec<(i32, BigDecimal, i64)> = despesa_com_deputado
+            .filter(fornecedor_documento.eq(documento))
+            .group_by(deputado_id)
+            .select((deputado_id, dsl::sum(valor_liquido).assume_not_null(), dsl::count_star()))
+            .order_by((dsl::sum(valor_liquido).desc(), deputado_id))
+            .load(connection)?;
+        if totais.is_empty() {
+            return Err(Error::NotFound);
+        }
+
+        let ids: Vec<i32> = totais.iter().map(|(id, _, _)| *id).collect();
+        let mut deputados: HashMap<i32, Deputado> = deputados::table
+            .filter(deputados::id.eq_any(&ids))
+            .select(Deputado::as_select())
+            .load(connection)?
+            .into_iter()
+            .map(|deputado| (deputado.id, deputado))
+            .collect();
+
+        Ok(
+            totais.into_iter()
+                .filter_map(|(id, soma, quantidade)| Some(Self { deputado: deputados.remove(&id)?, total: soma, despesas: quantidade }))
+                .collect()
+        )
+    }
+
+    // Como list, para vários fornecedores numa única consulta, retornando os clientes
+    // acompanhados do documento do fornecedor; os fornecedores sem despesas não constam do
+    // resultado.
+    pub fn list_many(connection: &mut PgConnection, documentos: &[String]) -> Result<Vec<(String, Self)>, Error> {
+        use self::despesa_com_deputado::dsl::*;
+
+        let totais: Vec<(String, i32, BigDecimal, i64)> = despesa_com_deputado
+            .filter(fornecedor_documento.eq_any(documentos))
+            .group_by((fornecedor_documento, deputado_id))
+            .select((fornecedor_documento.assume_not_null(), deputado_id, dsl::sum(valor_liquido).assume_not_null(), dsl::count_star()))
+            .order_by((fornecedor_documento, dsl::sum(valor_liquido).desc(), deputado_id))
+            .load(connection)?;
+
+        let ids: Vec<i32> = totais.iter().map(|(_, id, _, _)| *id).collect();
+        let deputados: HashMap<i32, Deputado> = Deputado::by_ids(connection, &ids)?
+            .into_iter()
+            .map(|deputado| (deputado.id, deputado))
+            .collect();
+
+        Ok(
+            totais.into_iter()
+                .filter_map(|(documento, id, soma, quantidade)| {
+                    Some((documento, Self { deputado: deputados.get(&id)?.clone(), total: soma, despesas: quantidade }))
+                })
+                .collect()
+        )
+    }
+}
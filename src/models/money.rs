@@ -0,0 +1,74 @@
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, RoundingMode};
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+// Valores monetários são armazenados como NUMERIC(14, 2) e representados como BigDecimal, para que
+// somas sobre milhões de despesas não acumulem erros de arredondamento. Em JSON, são serializados
+// como strings em ponto fixo com duas casas decimais ("1467.50"), preservando os centavos
+// exatamente; use com `#[serde(with = "money")]`.
+
+// Arredonda um valor para centavos.
+pub fn to_cents(value: &BigDecimal) -> BigDecimal {
+    value.with_scale_round(2, RoundingMode::HalfEven)
+}
+
+pub fn serialize<S: Serializer>(value: &BigDecimal, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&to_cents(value).to_plain_string())
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigDecimal, D::Error> {
+    let value = String::deserialize(deserializer)?;
+
+    BigDecimal::from_str(value.trim())
+        .map(|value| to_cents(&value))
+        .map_err(de::Error::custom)
+}
+
+// Como `deserialize`, para valores opcionais; use com
+// `#[serde(default, deserialize_with = "money::deserialize_option")]`.
+pub fn deserialize_option<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<BigDecimal>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| BigDecimal::from_str(value.trim()).map(|value| to_cents(&value)).map_err(de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Valor {
+        #[serde(with = "super")]
+        valor: BigDecimal,
+    }
+
+    #[test]
+    fn serializa_com_duas_casas_decimais() {
+        let valor = Valor { valor: BigDecimal::from(1467) };
+        assert_eq!(serde_json::to_string(&valor).unwrap(), r#"{"valor":"1467.00"}"#);
+
+        let valor = Valor { valor: BigDecimal::from_str("-0.5").unwrap() };
+        assert_eq!(serde_json::to_string(&valor).unwrap(), r#"{"valor":"-0.50"}"#);
+    }
+
+    #[test]
+    fn soma_sem_perda_de_precisao() {
+        let total = (0..1_000_000)
+            .map(|_| BigDecimal::from_str("0.10").unwrap())
+            .sum::<BigDecimal>();
+
+        let valor = Valor { valor: total };
+        assert_eq!(serde_json::to_string(&valor).unwrap(), r#"{"valor":"100000.00"}"#);
+    }
+
+    #[test]
+    fn le_valor_em_texto() {
+        let valor: Valor = serde_json::from_str(r#"{"valor":"1467.456"}"#).unwrap();
+        assert_eq!(valor.valor, BigDecimal::from_str("1467.46").unwrap());
+
+        assert!(serde_json::from_str::<Valor>(r#"{"valor":"abc"}"#).is_err());
+    }
+}
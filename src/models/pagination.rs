@@ -0,0 +1,121 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use bigdecimal::BigDecimal;
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::{money, Direction, ExpenseOrder};
+
+// As listagens de despesas são paginadas por cursor: em vez do número da página, cada resposta traz
+// tokens opacos que identificam a última (ou a primeira) despesa retornada, a partir da qual a
+// página seguinte (ou a anterior) é buscada. Ao contrário de OFFSET, o custo de buscar uma página
+// não cresce com a sua profundidade, e as páginas não se alteram quando novas despesas são
+// importadas.
+
+// Tamanho padrão e tamanho máximo de uma página.
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+// Valores de ordenação de uma despesa, de acordo com a ordem da listagem. O identificador da despesa
+// desempata a ordenação. Na ordenação por data, a data de emissão ausente é substituída pelo início
+// da competência.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Chave {
+    Data { competencia: NaiveDate, data: NaiveDateTime, id: i32 },
+    Valor { #[serde(with = "money")] valor: BigDecimal, id: i32 },
+    Fornecedor { fornecedor: String, id: i32 },
+}
+
+impl Chave {
+    pub fn ordem(&self) -> ExpenseOrder {
+        match self {
+            Chave::Data { .. } => ExpenseOrder::Data,
+            Chave::Valor { .. } => ExpenseOrder::Valor,
+            Chave::Fornecedor { .. } => ExpenseOrder::Fornecedor,
+        }
+    }
+}
+
+// Itens de uma listagem paginada por cursor.
+pub trait Ordenavel {
+    fn chave(&self, ordem: ExpenseOrder) -> Chave;
+}
+
+// Posição a partir da qual uma página é buscada: as despesas após `chave` ou, caso `anterior`
+// seja verdadeiro, as despesas antes dela.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Cursor {
+    pub chave: Chave,
+    pub direcao: Direction,
+    pub anterior: bool,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    pub fn decode(token: &str) -> Option<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(token).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    // Indica se o cursor foi gerado por uma listagem com a mesma ordenação.
+    pub fn matches(&self, ordem: ExpenseOrder, direcao: Direction) -> bool {
+        self.chave.ordem() == ordem && self.direcao == direcao
+    }
+}
+
+// Links para as páginas vizinhas, com os mesmos parâmetros da requisição.
+#[derive(Serialize, Debug, Default, ToSchema)]
+pub struct Links {
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+// Página de uma listagem: os itens, o total de itens que satisfazem os filtros, e os cursores das
+// páginas seguinte e anterior, ausentes quando não há tais páginas.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Pagina<T> {
+    pub dados: Vec<T>,
+    pub total: i64,
+    pub page_size: i64,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+    pub links: Links,
+}
+
+// Página de uma listagem paginada por número, usada nas listagens curtas, como a de deputados, em
+// que o custo de OFFSET é desprezível e a ordenação pode ser por valores calculados.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PaginaNumerada<T> {
+    pub dados: Vec<T>,
+    pub total: i64,
+    pub page: u32,
+    pub page_size: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn codifica_e_decodifica_cursor() {
+        let cursor = Cursor {
+            chave: Chave::Valor { valor: BigDecimal::from_str("1467.5").unwrap(), id: 42 },
+            direcao: Direction::Desc,
+            anterior: true,
+        };
+
+        let token = cursor.encode();
+        assert!(token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(Cursor::decode(&token), Some(cursor.clone()));
+        assert!(cursor.matches(ExpenseOrder::Valor, Direction::Desc));
+        assert!(!cursor.matches(ExpenseOrder::Data, Direction::Desc));
+
+        assert_eq!(Cursor::decode("invalido"), None);
+    }
+}
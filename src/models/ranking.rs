@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use chrono::{Datelike, Months, NaiveDate};
+use diesel::{dsl, prelude::*, result::Error, sql_types::Numeric};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{models::{competencia, despesa_com_deputado, money, Deputado}, schema::deputados, types::Uf};
+
+// Os rankings de deputados são calculados a partir dos totais pré-calculados em resumo_despesas; o
+// de fornecedores, a partir das despesas, já que os totais não guardam o fornecedor. Cada posição
+// traz a participação no total do período e, quando o período é informado, a variação em relação
+// ao período imediatamente anterior, com o mesmo número de meses.
+
+// Número padrão e número máximo de posições de um ranking.
+const DEFAULT_RANKING_SIZE: i64 = 10;
+const MAX_RANKING_SIZE: i64 = 100;
+
+// Intervalo de competências, inclusivo.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, ToSchema)]
+pub struct Periodo {
+    #[serde(serialize_with = "competencia::serialize")]
+    #[schema(value_type = String, example = "2025-02")]
+    pub de: NaiveDate,
+    #[serde(serialize_with = "competencia::serialize")]
+    #[schema(value_type = String, example = "2025-02")]
+    pub ate: NaiveDate,
+}
+
+impl Periodo {
+    pub fn new(de: NaiveDate, ate: NaiveDate) -> Option<Self> {
+        let de = de.with_day(1)?;
+        let ate = ate.with_day(1)?;
+
+        (de <= ate).then_some(Self { de, ate })
+    }
+
+    pub fn ano(ano: i32) -> Option<Self> {
+        Self::new(NaiveDate::from_ymd_opt(ano, 1, 1)?, NaiveDate::from_ymd_opt(ano, 12, 1)?)
+    }
+
+    fn meses(&self) -> u32 {
+        ((self.ate.year() - self.de.year()) * 12 + self.ate.month() as i32 - self.de.month() as i32 + 1) as u32
+    }
+
+    // Período imediatamente anterior a este, com o mesmo número de meses.
+    pub fn anterior(&self) -> Option<Self> {
+        Self::new(
+            self.de.checked_sub_months(Months::new(self.meses()))?,
+            self.de.checked_sub_months(Months::new(1))?,
+        )
+    }
+}
+
+// Filtros dos rankings: o período, informado como um ano ou como um intervalo de competências, a
+// UF e a categoria das despesas, e o número de posições.
+#[derive(Deserialize, Clone, Default, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RankingFilter {
+    pub ano: Option<i32>,
+    pub de: Option<NaiveDate>,
+    pub ate: Option<NaiveDate>,
+    pub uf: Option<Uf>,
+    pub categoria: Option<String>,
+    pub limite: Option<i64>,
+}
+
+impl RankingFilter {
+    pub fn periodo(&self) -> Result<Option<Periodo>, String> {
+        match (self.ano, self.de, self.ate) {
+            (None, None, None) => Ok(None),
+            (Some(ano), None, None) => Periodo::ano(ano).map(Some).ok_or_else(|| "Ano inválido.".to_string()),
+            (None, Some(de), Some(ate)) => Periodo::new(de, ate).map(Some).ok_or_else(|| "Período inválido.".to_string()),
+            _ => Err("Informe o ano, ou o início e o fim do período.".to_string()),
+        }
+    }
+
+    fn limite(&self) -> i64 {
+        self.limite.unwrap_or(DEFAULT_RANKING_SIZE).clamp(1, MAX_RANKING_SIZE)
+    }
+}
+
+// Valor de uma posição no período anterior, e a variação em relação a ele. A variação percentual
+// está ausente quando não houve despesas no período anterior.
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct Variacao {
+    #[serde(with = "money")]
+    #[schema(value_type = String, example = "1467.50")]
+    pub valor: BigDecimal,
+    #[serde(with = "money")]
+    #[schema(value_type = String, example = "1467.50")]
+    pub variacao: BigDecimal,
+    pub percentual: Option<f64>,
+}
+
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct Posicao<T> {
+    pub posicao: usize,
+    #[serde(flatten)]
+    pub item: T,
+    #[serde(with = "money")]
+    #[schema(value_type = String, example = "1467.50")]
+    pub valor: BigDecimal,
+    // Percentual do total do período.
+    pub participacao: f64,
+    pub anterior: Option<Variacao>,
+}
+
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct Ranking<T> {
+    pub periodo: Option<Periodo>,
+    pub periodo_anterior: Option<Periodo>,
+    #[serde(with = "money")]
+    #[schema(value_type = String, example = "1467.50")]
+    pub total: BigDecimal,
+    pub itens: Vec<Posicao<T>>,
+}
+
+// Fornecedor de um ranking, identificado pelo nome canônico do seu grupo.
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct FornecedorRanking {
+    pub fornecedor: String,
+}
+
+// Percentual de `parte` em `total`, com duas casas decimais.
+fn percentual(parte: &BigDecimal, total: &BigDecimal) -> Option<f64> {
+    if total.is_zero() {
+        return None;
+    }
+
+    (parte * BigDecimal::from(100) / total).round(2).to_f64()
+}
+
+// Monta o ranking a partir dos totais do período, já ordenados, e dos totais do período anterior.
+fn rank<K: Eq + Hash, T>(
+    periodo: Option<Periodo>,
+    total: BigDecimal,
+    linhas: Vec<(K, BigDecimal)>,
+    anteriores: Option<HashMap<K, BigDecimal>>,
+    mut item: impl FnMut(&K) -> Option<T>) -> Ranking<T> {
+    let itens = linhas.into_iter()
+        .filter_map(|(chave, valor)| {
+            let anterior = anteriores.as_ref().map(|anteriores| {
+                let anterior = anteriores.get(&chave).cloned().unwrap_or_default();
+                Variacao {
+                    variacao: &valor - &anterior,
+                    percentual: percentual(&(&valor - &anterior), &anterior),
+                    valor: anterior,
+                }
+            });
+
+            Some(Posicao {
+                posicao: 0,
+                item: item(&chave)?,
+                participacao: percentual(&valor, &total).unwrap_or_default(),
+                valor,
+                anterior,
+            })
+        })
+        .enumerate()
+        .map(|(indice, posicao)| Posicao { posicao: indice + 1, ..posicao })
+        .collect();
+
+    Ranking {
+        periodo,
+        periodo_anterior: periodo.and_then(|periodo| periodo.anterior()),
+        total,
+        itens,
+    }
+}
+
+impl Ranking<Deputado> {
+    // Ranking dos deputados pelo total das suas despesas, opcionalmente restritas a uma categoria.
+    pub fn deputies(connection: &mut PgConnection, filtro: &RankingFilter, periodo: Option<Periodo>) -> Result<Self, Error> {
+        let linhas = deputy_totals(connection, filtro, periodo, None)?;
+        let total = linhas.first().map(|(_, _, total)| total.clone()).unwrap_or_default();
+        let linhas: Vec<(i32, BigDecimal)> = linhas.into_iter().map(|(id, valor, _)| (id, valor)).collect();
+        let ids: Vec<i32> = linhas.iter().map(|(id, _)| *id).collect();
+
+        let anteriores = match periodo.and_then(|periodo| periodo.anterior()) {
+            Some(anterior) => Some(
+                deputy_totals(connection, filtro, Some(anterior), Some(&ids))?
+                    .into_iter()
+                    .map(|(id, valor, _)| (id, valor))
+                    .collect()
+            ),
+            None => None,
+        };
+
+        let mut deputados: HashMap<i32, Deputado> = deputados::table
+            .filter(deputados::id.eq_any(&ids))
+            .select(Deputado::as_select())
+            .load(connection)?
+            .into_iter()
+            .map(|deputado| (deputado.id, deputado))
+            .collect();
+
+        Ok(rank(periodo, total, linhas, anteriores, |id| deputados.remove(id)))
+    }
+}
+
+// Totais por deputado no período, em ordem decrescente, acompanhados do total de todos os
+// deputados. Caso `ids` seja informado, retorna apenas os totais desses deputados, sem limite.
+fn deputy_totals(connection: &mut PgConnection, filtro: &RankingFilter, periodo: Option<Periodo>, ids: Option<&[i32]>) -> Result<Vec<(i32, BigDecimal, BigDecimal)>, Error> {
+    use crate::schema::resumo_despesas::dsl::*;
+
+    let mut query = resumo_despesas
+        .group_by(deputado_id)
+        .select((deputado_id, dsl::sum(total).assume_not_null(), dsl::sql::<Numeric>("SUM(SUM(total)) OVER ()")))
+        .order_by((dsl::sum(total).desc(), deputado_id))
+        .into_boxed();
+
+    if let Some(periodo) = periodo {
+        query = query.filter(competencia.between(periodo.de, periodo.ate));
+    }
+    if let Some(busca) = &filtro.uf {
+        query = query.filter(uf.eq(busca.clone()));
+    }
+    if let Some(busca) = &filtro.categoria {
+        query = query.filter(categoria.eq(busca.trim().to_string()));
+    }
+    match ids {
+        Some(ids) => query = query.filter(deputado_id.eq_any(ids)),
+        None => query = query.limit(filtro.limite()),
+    }
+
+    query.load(connection)
+}
+
+impl Ranking<FornecedorRanking> {
+    // Ranking dos fornecedores pelo total recebido dos deputados.
+    pub fn suppliers(connection: &mut PgConnection, filtro: &RankingFilter, periodo: Option<Periodo>) -> Result<Self, Error> {
+        let linhas = supplier_totals(connection, filtro, periodo, None)?;
+        let total = linhas.first().map(|(_, _, total)| total.clone()).unwrap_or_default();
+        let linhas: Vec<(String, BigDecimal)> = linhas.into_iter().map(|(nome, valor, _)| (nome, valor)).collect();
+        let nomes: Vec<String> = linhas.iter().map(|(nome, _)| nome.clone()).collect();
+
+        let anteriores = match periodo.and_then(|periodo| periodo.anterior()) {
+            Some(anterior) => Some(
+                supplier_totals(connection, filtro, Some(anterior), Some(&nomes))?
+                    .into_iter()
+                    .map(|(nome, valor, _)| (nome, valor))
+                    .collect()
+            ),
+            None => None,
+        };
+
+        Ok(rank(periodo, total, linhas, anteriores, |nome| Some(FornecedorRanking { fornecedor: nome.clone() })))
+    }
+}
+
+// Como deputy_totals, para os fornecedores, agrupados pelo nome canônico.
+fn supplier_totals(connection: &mut PgConnection, filtro: &RankingFilter, periodo: Option<Periodo>, nomes: Option<&[String]>) -> Result<Vec<(String, BigDecimal, BigDecimal)>, Error> {
+    use self::despesa_com_deputado::dsl::*;
+
+    let mut query = despesa_com_deputado
+        .group_by(fornecedor_canonico)
+        .select((fornecedor_canonico, dsl::sum(valor_liquido).assume_not_null(), dsl::sql::<Numeric>("SUM(SUM(valor_liquido)) OVER ()")))
+        .order_by((dsl::sum(valor_liquido).desc(), fornecedor_canonico))
+        .into_boxed();
+
+    if let Some(periodo) = periodo {
+        query = query.filter(competencia.between(periodo.de, periodo.ate));
+    }
+    if let Some(busca) = &filtro.uf {
+        query = query.filter(uf.eq(busca.clone()));
+    }
+    if let Some(busca) = &filtro.categoria {
+        query = query.filter(categoria.eq(busca.trim().to_string()));
+    }
+    match nomes {
+        Some(nomes) => query = query.filter(fornecedor_canonico.eq_any(nomes)),
+        None => query = query.limit(filtro.limite()),
+    }
+
+    query.load(connection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(ano: i32, mes: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(ano, mes, 1).unwrap()
+    }
+
+    #[test]
+    fn calcula_periodo_anterior() {
+        let ano = Periodo::ano(2025).unwrap();
+        assert_eq!(ano.anterior(), Periodo::ano(2024));
+
+        let trimestre = Periodo::new(data(2025, 1), NaiveDate::from_ymd_opt(2025, 3, 31).unwrap()).unwrap();
+        assert_eq!(trimestre.anterior(), Periodo::new(data(2024, 10), data(2024, 12)));
+
+        assert_eq!(Periodo::new(data(2025, 3), data(2025, 1)), None);
+    }
+
+    #[test]
+    fn le_periodo_do_filtro() {
+        let filtro = RankingFilter { ano: Some(2025), ..Default::default() };
+        assert_eq!(filtro.periodo(), Ok(Periodo::ano(2025)));
+
+        let filtro = RankingFilter { de: Some(data(2025, 1)), ..Default::default() };
+        assert!(filtro.periodo().is_err());
+
+        assert_eq!(RankingFilter::default().periodo(), Ok(None));
+    }
+}
@@ -0,0 +1,127 @@
+use bigdecimal::BigDecimal;
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{dsl, prelude::*, result::Error};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{models::money, schema::{deputados, expenses, resumo_despesas, resumo_despesas_estado}, types::Cpf};
+
+// Os totais das despesas são mantidos em resumo_despesas, agregados por deputado, competência e
+// categoria; as somas por deputado, UF, mês ou categoria são obtidas deles sem percorrer todas as
+// despesas. Os totais são recalculados ao fim de cada importação, apenas para os deputados e
+// competências presentes no arquivo importado.
+
+// Soma das despesas, acompanhada do momento da última atualização dos totais dos quais foi obtida.
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct Soma {
+    #[serde(with = "money")]
+    #[schema(value_type = String, example = "1467.50")]
+    pub soma: BigDecimal,
+    pub atualizado_em: NaiveDateTime,
+}
+
+impl Soma {
+    // Sem despesas, a soma é zero.
+    pub fn sum_all(connection: &mut PgConnection) -> Result<Self, Error> {
+        use crate::schema::resumo_despesas::dsl::*;
+
+        let soma: Option<BigDecimal> = resumo_despesas
+            .select(dsl::sum(total))
+            .first(connection)?;
+
+        Ok(Self { soma: soma.unwrap_or_default(), atualizado_em: updated_at(connection)? })
+    }
+
+    pub fn sum_by_cpf(connection: &mut PgConnection, cpf_busca: &Cpf) -> Result<Self, Error> {
+        let soma: Option<BigDecimal> = resumo_despesas::table
+            .inner_join(deputados::table)
+            .filter(deputados::cpf.eq(cpf_busca))
+            .select(dsl::sum(resumo_despesas::total))
+            .first(connection)?;
+
+        Ok(Self { soma: soma.ok_or(Error::NotFound)?, atualizado_em: updated_at(connection)? })
+    }
+}
+
+fn updated_at(connection: &mut PgConnection) -> Result<NaiveDateTime, Error> {
+    resumo_despesas_estado::table
+        .select(resumo_despesas_estado::atualizado_em)
+        .first(connection)
+}
+
+diesel::allow_columns_to_appear_in_same_group_by_clause!(
+    expenses::deputado_id,
+    expenses::competencia,
+    expenses::categoria,
+    deputados::uf,
+);
+
+// Recalcula os totais dos deputados e competências informados a partir das despesas, e registra o
+// momento da atualização. Deve ser executada na mesma transação em que as despesas são inseridas.
+pub fn refresh_rollups(connection: &mut PgConnection, ids: &[i32], competencias: &[NaiveDate]) -> Result<usize, Error> {
+    diesel::delete(
+        resumo_despesas::table
+            .filter(resumo_despesas::deputado_id.eq_any(ids))
+            .filter(resumo_despesas::competencia.eq_any(competencias))
+    )
+    .execute(connection)?;
+
+    let linhas = diesel::insert_into(resumo_despesas::table)
+        .values(
+            expenses::table
+                .inner_join(deputados::table)
+                .filter(expenses::deputado_id.eq_any(ids))
+                .filter(expenses::competencia.eq_any(competencias))
+                .group_by((expenses::deputado_id, deputados::uf, expenses::competencia, expenses::categoria))
+                .select((
+                    expenses::deputado_id,
+                    deputados::uf,
+                    expenses::competencia,
+                    expenses::categoria,
+                    dsl::sum(expenses::valor_liquido).assume_not_null(),
+                    dsl::count_star(),
+                ))
+        )
+        .into_columns((
+            resumo_despesas::deputado_id,
+            resumo_despesas::uf,
+            resumo_despesas::competencia,
+            resumo_despesas::categoria,
+            resumo_despesas::total,
+            resumo_despesas::quantidade,
+        ))
+        .execute(connection)?;
+
+    touch(connection)?;
+
+    Ok(linhas)
+}
+
+// Remove os totais das competências do ano informado, cujas despesas foram removidas.
+pub fn purge_rollups(connection: &mut PgConnection, ano: i32) -> Result<usize, Error> {
+    let inicio = NaiveDate::from_ymd_opt(ano, 1, 1).ok_or(Error::NotFound)?;
+    let fim = NaiveDate::from_ymd_opt(ano + 1, 1, 1).ok_or(Error::NotFound)?;
+
+    let linhas = diesel::delete(
+        resumo_despesas::table
+            .filter(resumo_despesas::competencia.ge(inicio))
+            .filter(resumo_despesas::competencia.lt(fim))
+    )
+    .execute(connection)?;
+
+    touch(connection)?;
+
+    Ok(linhas)
+}
+
+// Registra o momento da atualização dos totais.
+fn touch(connection: &mut PgConnection) -> Result<(), Error> {
+    diesel::insert_into(resumo_despesas_estado::table)
+        .values((resumo_despesas_estado::id.eq(1), resumo_despesas_estado::atualizado_em.eq(dsl::now)))
+        .on_conflict(resumo_despesas_estado::id)
+        .do_update()
+        .set(resumo_despesas_estado::atualizado_em.eq(dsl::now))
+        .execute(connection)?;
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use bigdecimal::BigDecimal;
+use diesel::{dsl, pg::Pg, prelude::*, result::Error, sql_types::Text};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{models::{money, Deputado}, text};
+
+define_sql_function!(fn lower(x: Text) -> Text);
+define_sql_function!(fn f_unaccent(x: Text) -> Text);
+define_sql_function!(fn word_similarity(a: Text, b: Text) -> Float4);
+
+// `a <% b` é verdadeiro quando alguma parte de `b` é semelhante a `a`, segundo o limite
+// pg_trgm.word_similarity_threshold; ao contrário de comparar word_similarity() diretamente, pode
+// usar os índices de trigramas.
+diesel::infix_operator!(WordSimilarTo, " <% ", backend: Pg);
+
+fn word_similar_to<T, U>(left: T, right: U) -> WordSimilarTo<T, U> {
+    WordSimilarTo::new(left, right)
+}
+
+// Fornecedor encontrado em uma busca, com o número e o total das despesas pagas a ele. `grupo` é o
+// grupo de fornecedores ao qual pertence, caso a resolução de fornecedores já o tenha agrupado.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct FornecedorEncontrado {
+    pub nome: String,
+    pub grupo: Option<i32>,
+    pub destaque: String,
+    pub relevancia: f32,
+    pub despesas: i64,
+    #[serde(with = "money")]
+    #[schema(value_type = String, example = "1467.50")]
+    pub total: BigDecimal,
+}
+
+// Deputado encontrado em uma busca.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct DeputadoEncontrado {
+    #[serde(flatten)]
+    pub deputado: Deputado,
+    pub destaque: String,
+    pub relevancia: f32,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ResultadoBusca {
+    pub fornecedores: Vec<FornecedorEncontrado>,
+    pub deputados: Vec<DeputadoEncontrado>,
+}
+
+// Busca fornecedores e deputados cujo nome seja semelhante ao texto buscado, tolerando diferenças
+// de acentuação e pequenos erros de digitação, e os retorna ordenados pela semelhança.
+pub fn search(connection: &mut PgConnection, busca: &str, limite: i64) -> Result<ResultadoBusca, Error> {
+    let termo = text::normalize(busca);
+
+    let fornecedores = {
+        use crate::models::despesa_com_deputado::dsl::*;
+
+        // As variantes do nome de um mesmo fornecedor são agrupadas sob seu nome canônico, e a
+        // relevância do grupo é a da variante mais semelhante à busca.
+        despesa_com_deputado
+            .filter(word_similar_to(termo.as_str().into_sql::<Text>(), f_unaccent(lower(fornecedor))))
+            .group_by((fornecedor_canonico, grupo_fornecedor_id))
+            .select((
+                fornecedor_canonico,
+                grupo_fornecedor_id,
+                dsl::max(word_similarity(termo.as_str(), f_unaccent(lower(fornecedor)))),
+                dsl::count_star(),
+                dsl::sum(valor_liquido),
+            ))
+            .order_by((dsl::max(word_similarity(termo.as_str(), f_unaccent(lower(fornecedor)))).desc(), fornecedor_canonico))
+            .limit(limite)
+            .load::<(String, Option<i32>, Option<f32>, i64, Option<BigDecimal>)>(connection)?
+    };
+
+    let deputados = {
+        use crate::schema::deputados::dsl::*;
+
+        deputados
+            .filter(word_similar_to(termo.as_str().into_sql::<Text>(), f_unaccent(lower(nome))))
+            .select((Deputado::as_select(), word_similarity(termo.as_str(), f_unaccent(lower(nome)))))
+            .order_by((word_similarity(termo.as_str(), f_unaccent(lower(nome))).desc(), nome))
+            .limit(limite)
+            .load::<(Deputado, f32)>(connection)?
+    };
+
+    Ok(ResultadoBusca {
+        fornecedores: fornecedores
+            .into_iter()
+            .map(|(nome, grupo, relevancia, despesas, total)| FornecedorEncontrado {
+                destaque: text::highlight(&nome, busca),
+                nome,
+                grupo,
+                relevancia: relevancia.unwrap_or_default(),
+                despesas,
+                total: total.unwrap_or_default(),
+            })
+            .collect(),
+        deputados: deputados
+            .into_iter()
+            .map(|(deputado, relevancia)| DeputadoEncontrado {
+                destaque: text::highlight(&deputado.nome, busca),
+                deputado,
+                relevancia,
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,35 @@
+use chrono::NaiveDateTime;
+use diesel::{dsl, prelude::*, result::Error, sql_types::Timestamp};
+
+use crate::schema::versao_dados;
+
+// Versão dos dados; veja a migração dataset_version. As respostas dos endpoints de leitura são
+// identificadas pela versão, e deixam de valer quando ela é incrementada.
+#[derive(Queryable, Selectable, Clone, Copy, Debug, PartialEq)]
+#[diesel(table_name = versao_dados)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct VersaoDados {
+    pub versao: i64,
+    // Em UTC.
+    pub atualizado_em: NaiveDateTime,
+}
+
+impl VersaoDados {
+    pub fn get(connection: &mut PgConnection) -> Result<Self, Error> {
+        versao_dados::table
+            .select(VersaoDados::as_select())
+            .first(connection)
+    }
+
+    // Incrementa a versão. Deve ser executada na mesma transação em que os dados são alterados, de
+    // modo que a nova versão só seja vista junto com os novos dados.
+    pub fn bump(connection: &mut PgConnection) -> Result<Self, Error> {
+        diesel::update(versao_dados::table)
+            .set((
+                versao_dados::versao.eq(versao_dados::versao + 1),
+                versao_dados::atualizado_em.eq(dsl::sql::<Timestamp>("timezone('utc', now())")),
+            ))
+            .returning(VersaoDados::as_returning())
+            .get_result(connection)
+    }
+}
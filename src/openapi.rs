@@ -0,0 +1,314 @@
+use std::mem;
+
+use actix_web::dev::HttpServiceFactory;
+use utoipa::{openapi::{path::{Operation, PathItem}, schema::{Object, ObjectBuilder, Schema}, security::{HttpAuthScheme, HttpBuilder, SecurityScheme}, Components, Deprecated, RefOr}, Modify, OpenApi};
+use utoipa_swagger_ui::{SwaggerUi, Url};
+
+use crate::routes;
+
+// Especificação OpenAPI dos endpoints, gerada a partir das anotações dos handlers e dos tipos das
+// respostas. Todo endpoint registrado deve constar de `paths`; veja o teste abaixo.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "CEAP", description = "Despesas da Cota para o Exercício da Atividade Parlamentar dos deputados federais."),
+    paths(
+        routes::soma_todas_as_despesas,
+        routes::soma_despesas,
+        routes::busca_despesas,
+        routes::agrega_despesas,
+        routes::lista_despesas_por_uf,
+        routes::lista_despesas_por_cpf,
+        routes::ranking_deputados,
+        routes::ranking_fornecedores,
+        routes::lista_deputados,
+        routes::perfil_deputado,
+        routes::lista_fornecedores,
+        routes::perfil_fornecedor,
+        routes::clientes_fornecedor,
+        routes::busca,
+        routes::import_csv,
+        routes::opcoes_upload,
+        routes::cria_upload,
+        routes::consulta_upload,
+        routes::envia_bloco,
+        routes::remove_upload,
+        routes::resolve_fornecedores,
+        routes::lista_grupos_fornecedores,
+        routes::une_grupos_fornecedores,
+        routes::separa_grupo_fornecedores,
+        routes::remove_despesas_do_ano,
+        routes::consulta_graphql,
+        routes::graphiql,
+    ),
+    modifiers(&TokenAdministrativo),
+    tags(
+        (name = "despesas", description = "Listagens, somas e agregações das despesas."),
+        (name = "rankings", description = "Rankings dos deputados e dos fornecedores."),
+        (name = "deputados", description = "Listagem e perfis dos deputados."),
+        (name = "fornecedores", description = "Listagem e perfis dos fornecedores, identificados pelo documento."),
+        (name = "busca", description = "Busca textual de fornecedores e deputados."),
+        (name = "importação", description = "Importação dos arquivos da CEAP."),
+        (name = "uploads", description = "Upload retomável, seguindo o protocolo tus."),
+        (name = "admin", description = "Administração, restrita ao portador do token administrativo."),
+        (name = "graphql", description = "API GraphQL sobre deputados, despesas e fornecedores."),
+    ),
+)]
+pub struct ApiDoc;
+
+// Os endpoints administrativos exigem o token configurado em ADMIN_TOKEN, no cabeçalho
+// Authorization: Bearer.
+struct TokenAdministrativo;
+
+impl Modify for TokenAdministrativo {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi.components.get_or_insert_with(Default::default).add_security_scheme(
+            "admin",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+// Caminhos dos endpoints da API GraphQL, que não é versionada.
+#[derive(Default)]
+struct CaminhosGraphql(Vec<String>);
+
+impl routes::Registro for CaminhosGraphql {
+    fn endpoint<S: HttpServiceFactory + utoipa::Path + 'static>(&mut self, _: S) -> &mut Self {
+        self.0.push(S::path());
+        self
+    }
+}
+
+fn caminhos_graphql() -> Vec<String> {
+    let mut caminhos = CaminhosGraphql::default();
+    routes::endpoints_graphql(&mut caminhos);
+
+    caminhos.0
+}
+
+fn operacoes(item: &mut PathItem) -> impl Iterator<Item = &mut Operation> {
+    [&mut item.get, &mut item.put, &mut item.post, &mut item.delete, &mut item.options, &mut item.head, &mut item.patch, &mut item.trace]
+        .into_iter()
+        .flatten()
+}
+
+// Especificação da v1, com os endereços sem prefixo, pelos quais é servida também sob /v1. As
+// operações da API REST são marcadas como obsoletas; veja versioning.
+pub fn v1() -> utoipa::openapi::OpenApi {
+    let mut openapi = ApiDoc::openapi();
+    openapi.info.description = Some("Despesas da Cota para o Exercício da Atividade Parlamentar dos deputados federais. Versão 1, obsoleta, servida sob /v1 e sem prefixo; use a v2, em /openapi/v2.json.".to_string());
+
+    let graphql = caminhos_graphql();
+    for (_, item) in openapi.paths.paths.iter_mut().filter(|(caminho, _)| !graphql.contains(caminho)) {
+        for operacao in operacoes(item) {
+            operacao.deprecated = Some(Deprecated::True);
+        }
+    }
+
+    openapi
+}
+
+// Especificação da v2: as operações da API REST sob /v2, com as respostas em JSON no envelope da
+// v2. O GraphQL, que não é versionado, consta apenas da especificação da v1.
+pub fn v2() -> utoipa::openapi::OpenApi {
+    let mut openapi = ApiDoc::openapi();
+    openapi.info.description = Some("Despesas da Cota para o Exercício da Atividade Parlamentar dos deputados federais. Versão 2, servida sob /v2: as respostas em JSON vêm em {\"data\": ...}, com \"meta\" e \"links\" nas listagens paginadas, e os erros em {\"error\": ...}.".to_string());
+    openapi.info.version = "2".to_string();
+
+    let componentes = openapi.components.clone().unwrap_or_default();
+    let graphql = caminhos_graphql();
+    openapi.paths.paths = mem::take(&mut openapi.paths.paths)
+        .into_iter()
+        .filter(|(caminho, _)| !graphql.contains(caminho))
+        .map(|(caminho, mut item)| {
+            for operacao in operacoes(&mut item) {
+                envolve(operacao, &componentes);
+            }
+
+            (format!("/v2{caminho}"), item)
+        })
+        .collect();
+
+    openapi
+}
+
+// Substitui os esquemas das respostas em JSON pelos do envelope da v2.
+fn envolve(operacao: &mut Operation, componentes: &Components) {
+    for (status, resposta) in operacao.responses.responses.iter_mut() {
+        let RefOr::T(resposta) = resposta else { continue };
+        let Some(conteudo) = resposta.content.get_mut("application/json") else { continue };
+        let Some(schema) = conteudo.schema.take() else { continue };
+
+        let envelope = if status.starts_with('2') {
+            dados(schema, componentes)
+        } else {
+            ObjectBuilder::new().property("error", schema).required("error").build()
+        };
+        conteudo.schema = Some(RefOr::T(Schema::Object(envelope)));
+    }
+}
+
+// Envelope de uma resposta de sucesso. As páginas, cujo esquema traz a lista `dados`, trazem os
+// itens em `data`, os links em `links` e os demais campos em `meta`.
+fn dados(schema: RefOr<Schema>, componentes: &Components) -> Object {
+    let pagina = match &schema {
+        RefOr::Ref(referencia) => referencia.ref_location
+            .strip_prefix("#/components/schemas/")
+            .and_then(|nome| componentes.schemas.get(nome)),
+        RefOr::T(_) => None,
+    };
+    match pagina {
+        Some(RefOr::T(Schema::Object(pagina))) if pagina.properties.contains_key("dados") => envelope_pagina(pagina),
+        _ => ObjectBuilder::new().property("data", schema).required("data").build(),
+    }
+}
+
+fn envelope_pagina(pagina: &Object) -> Object {
+    let mut envelope = ObjectBuilder::new();
+    let mut meta = ObjectBuilder::new();
+    for (nome, campo) in &pagina.properties {
+        envelope = match nome.as_str() {
+            "dados" => envelope.property("data", campo.clone()).required("data"),
+            "links" => envelope.property("links", campo.clone()).required("links"),
+            _ => {
+                meta = meta.property(nome, campo.clone());
+                if pagina.required.contains(nome) {
+                    meta = meta.required(nome);
+                }
+                envelope
+            },
+        };
+    }
+
+    envelope.property("meta", meta.build()).required("meta").build()
+}
+
+// Serve as especificações da v2, em /openapi/v2.json, e da v1, em /openapi.json, e a documentação
+// interativa (Swagger UI, embutida no binário) de ambas em /docs.
+pub fn docs() -> SwaggerUi {
+    SwaggerUi::new("/docs/{_:.*}")
+        .url(Url::with_primary("v2", "/openapi/v2.json", true), v2())
+        .url(Url::new("v1 (obsoleta)", "/openapi.json"), v1())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{dev::HttpServiceFactory, http::{Method, StatusCode}, test, App};
+    use utoipa::openapi::path::HttpMethod;
+
+    use super::*;
+
+    // Rotas registradas pelos endpoints da API, como pares (método, caminho).
+    #[derive(Default)]
+    struct Rotas(Vec<(HttpMethod, String)>);
+
+    impl routes::Registro for Rotas {
+        fn endpoint<S: HttpServiceFactory + utoipa::Path + 'static>(&mut self, _: S) -> &mut Self {
+            self.0.extend(S::methods().into_iter().map(|metodo| (metodo, S::path())));
+            self
+        }
+    }
+
+    fn rotas_registradas() -> Vec<(HttpMethod, String)> {
+        let mut rotas = Rotas::default();
+        routes::endpoints(&mut rotas);
+        routes::endpoints_graphql(&mut rotas);
+
+        rotas.0
+    }
+
+    // Endereço de exemplo de uma rota, com os parâmetros do caminho preenchidos.
+    fn exemplo(caminho: &str) -> String {
+        caminho
+            .split('/')
+            .map(|segmento| match segmento {
+                "{cpf}" => "22488012033",
+                "{documento}" => "02012862000160",
+                "{uf}" => "PB",
+                "{ano}" => "2025",
+                segmento if segmento.starts_with('{') => "1",
+                segmento => segmento,
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    #[actix_web::test]
+    async fn every_route_is_documented() {
+        let openapi = v1();
+        let rotas = rotas_registradas();
+        let graphql = caminhos_graphql();
+        let caminhos_v2 = v2().paths.paths;
+        for (_, caminho) in rotas.iter().filter(|(_, caminho)| !graphql.contains(caminho)) {
+            assert!(caminhos_v2.contains_key(&format!("/v2{caminho}")), "/v2{caminho} não consta da especificação da v2");
+        }
+        assert!(rotas.len() >= 27, "{}", rotas.len());
+
+        // As rotas são servidas pelos endpoints registrados nos endereços da especificação: uma
+        // rota inexistente resultaria em 404 sem corpo, ou em 405 para outro método.
+        let app = test::init_service(
+            App::new()
+                .configure(routes::configure)
+                .configure(routes::configure_graphql)
+        ).await;
+
+        for (metodo, caminho) in rotas {
+            let item = openapi.paths.paths.get(&caminho).unwrap_or_else(|| panic!("{caminho} não consta da especificação"));
+            let (operacao, metodo) = match metodo {
+                HttpMethod::Get => (&item.get, Method::GET),
+                HttpMethod::Post => (&item.post, Method::POST),
+                HttpMethod::Put => (&item.put, Method::PUT),
+                HttpMethod::Patch => (&item.patch, Method::PATCH),
+                HttpMethod::Delete => (&item.delete, Method::DELETE),
+                HttpMethod::Head => (&item.head, Method::HEAD),
+                HttpMethod::Options => (&item.options, Method::OPTIONS),
+                HttpMethod::Trace => (&item.trace, Method::TRACE),
+            };
+            assert!(operacao.is_some(), "{metodo} {caminho} não consta da especificação");
+
+            let req = test::TestRequest::default().method(metodo.clone()).uri(&exemplo(&caminho)).to_request();
+            let response = test::call_service(&app, req).await;
+            let status = response.status();
+            let corpo = test::read_body(response).await;
+            assert!(
+                status != StatusCode::METHOD_NOT_ALLOWED && !(status == StatusCode::NOT_FOUND && corpo.is_empty()),
+                "{metodo} {caminho} não é servido pelo endpoint registrado ({status})",
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn serve_spec_and_docs() {
+        let app = test::init_service(App::new().service(docs())).await;
+
+        let req = test::TestRequest::get().uri("/openapi.json").to_request();
+        let spec: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(spec["info"]["title"], "CEAP");
+        assert!(spec["components"]["schemas"]["DespesaComDeputado"].is_object());
+        assert_eq!(spec["paths"]["/despesas/cpf/{cpf}/soma"]["get"]["responses"]["200"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/Soma");
+
+        assert_eq!(spec["paths"]["/despesas"]["get"]["deprecated"], true);
+        assert!(spec["paths"]["/graphql"]["post"].get("deprecated").is_none());
+
+        let req = test::TestRequest::get().uri("/openapi/v2.json").to_request();
+        let spec: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert!(spec["paths"].get("/despesas").is_none());
+        assert!(spec["paths"].get("/graphql").is_none());
+        let resposta = &spec["paths"]["/v2/despesas/cpf/{cpf}/soma"]["get"]["responses"];
+        assert_eq!(resposta["200"]["content"]["application/json"]["schema"]["properties"]["data"]["$ref"], "#/components/schemas/Soma");
+        assert_eq!(resposta["404"]["content"]["application/json"]["schema"]["properties"]["error"]["$ref"], "#/components/schemas/ErrorBody");
+        let pagina = &spec["paths"]["/v2/despesas"]["get"]["responses"]["200"]["content"]["application/json"]["schema"];
+        assert!(pagina["properties"]["data"]["items"]["properties"]["valor_liquido"].is_object());
+        assert!(pagina["properties"]["meta"]["properties"]["total"].is_object());
+        assert!(pagina["properties"]["meta"]["properties"].get("dados").is_none());
+        assert_eq!(pagina["properties"]["links"]["$ref"], "#/components/schemas/Links");
+        let csv = &spec["paths"]["/v2/despesas"]["get"]["responses"]["200"]["content"]["text/csv"]["schema"];
+        assert_eq!(csv["type"], "string");
+
+        let req = test::TestRequest::get().uri("/docs/").to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = test::read_body(response).await;
+        assert!(String::from_utf8_lossy(&body).contains("swagger"));
+    }
+}
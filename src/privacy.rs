@@ -0,0 +1,407 @@
+use std::{borrow::Cow, convert::Infallible, future::{ready, Ready}};
+
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+
+use crate::{auth::{bearer_token, token_valido}, config::{AdminConfig, PrivacyConfig}, error::ApiError, models::{pagination::{Pagina, PaginaNumerada}, ClienteFornecedor, Deputado, DeputadoComTotal, DeputadoEncontrado, DespesaComDeputado, DespesaExportada, DespesaSemDeputado, FornecedorComTotal, FornecedorRanking, Perfil, PerfilFornecedor, Posicao, Ranking, ResultadoBusca, Tabela}, types::{Cpf, DocumentoFornecedor}};
+
+// Política de privacidade dos CPFs (LGPD). Os CPFs dos deputados e os documentos dos fornecedores
+// que são pessoas físicas são mascarados em todas as respostas, em JSON, em GraphQL e nas
+// exportações, exibindo apenas os seis dígitos centrais (***.456.789-**), exceto para as
+// requisições autenticadas; veja PrivacyConfig. Os CNPJs não são mascarados.
+//
+// A privacidade é extraída da requisição pelos handlers, que a aplicam aos valores das respostas
+// (veja Protegido) antes de serializá-los ou exportá-los. Os CPFs são mascarados por padrão: um
+// valor ao qual a privacidade da requisição não foi aplicada sai mascarado.
+//
+// As consultas por CPF, que revelariam se um CPF é o de um deputado ou o de um fornecedor, podem
+// ser restritas às requisições autenticadas; veja consulta_por_cpf.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Privacidade {
+    #[default]
+    Mascarada,
+    // Mascarada, com as consultas por CPF recusadas.
+    Restrita,
+    Revelada,
+}
+
+impl Privacidade {
+    // Recusa as consultas por CPF das requisições sem autenticação quando a restrição está
+    // habilitada; para elas, a resposta seria um teste de existência do CPF.
+    pub fn consulta_por_cpf(self) -> Result<(), ApiError> {
+        match self {
+            Privacidade::Mascarada | Privacidade::Revelada => Ok(()),
+            Privacidade::Restrita => Err(ApiError::Unauthorized("A consulta por CPF exige autenticação.".to_string())),
+        }
+    }
+}
+
+// Os CPFs são revelados às requisições com o token administrativo ou com um dos tokens de
+// CPF_ACCESS_TOKENS, ou a todas, caso a máscara esteja desabilitada. Um token inválido não é um
+// erro: a requisição é tratada como as demais.
+impl FromRequest for Privacidade {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Infallible>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(privacidade(req)))
+    }
+}
+
+fn privacidade(req: &HttpRequest) -> Privacidade {
+    let padrao = PrivacyConfig::default();
+    let config = req.app_data::<web::Data<PrivacyConfig>>().map_or(&padrao, |config| config.get_ref());
+    if !config.mascara_cpf {
+        return Privacidade::Revelada;
+    }
+
+    let admin = req.app_data::<web::Data<AdminConfig>>().and_then(|config| config.token.as_deref());
+    let autorizados = admin.into_iter().chain(config.tokens.iter().map(String::as_str));
+
+    match bearer_token(req.headers()) {
+        // Todos os tokens são comparados, para que o tempo da verificação não revele qual deles confere.
+        Some(informado) if autorizados.fold(false, |valido, token| token_valido(informado, token) | valido) => Privacidade::Revelada,
+        _ if config.restringe_consultas => Privacidade::Restrita,
+        _ => Privacidade::Mascarada,
+    }
+}
+
+// Mascara um CPF, exibindo apenas os seis dígitos centrais: 12345678909 é exibido como
+// ***.456.789-**.
+pub fn mascara_cpf(cpf: &str) -> String {
+    if cpf.len() != 11 || !cpf.bytes().all(|c| c.is_ascii_digit()) {
+        return "***.***.***-**".to_string();
+    }
+
+    format!("***.{}.{}-**", &cpf[3..6], &cpf[6..9])
+}
+
+// CPF como exibido com a privacidade informada.
+pub fn cpf(cpf: &str, privacidade: Privacidade) -> Cow<'_, str> {
+    match privacidade {
+        Privacidade::Revelada => Cow::Borrowed(cpf),
+        Privacidade::Mascarada | Privacidade::Restrita => Cow::Owned(mascara_cpf(cpf)),
+    }
+}
+
+// Documento de um fornecedor, armazenado apenas com os dígitos, como exibido com a privacidade
+// informada: os CPFs, com 11 dígitos, são tratados como os dos deputados, e os CNPJs, exibidos.
+pub fn documento(documento: &str, privacidade: Privacidade) -> Cow<'_, str> {
+    if documento.len() == 11 { cpf(documento, privacidade) } else { Cow::Borrowed(documento) }
+}
+
+// Valores que contêm CPFs, exibidos de acordo com a privacidade aplicada por `protege`.
+pub trait Protegido {
+    fn protege(&mut self, privacidade: Privacidade);
+
+    fn protegido(mut self, privacidade: Privacidade) -> Self where Self: Sized {
+        self.protege(privacidade);
+        self
+    }
+}
+
+impl<T: Protegido> Protegido for Vec<T> {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.iter_mut().for_each(|valor| valor.protege(privacidade));
+    }
+}
+
+impl<T: Protegido> Protegido for Option<T> {
+    fn protege(&mut self, privacidade: Privacidade) {
+        if let Some(valor) = self {
+            valor.protege(privacidade);
+        }
+    }
+}
+
+impl<T: Protegido> Protegido for Pagina<T> {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.dados.protege(privacidade);
+    }
+}
+
+impl<T: Protegido> Protegido for PaginaNumerada<T> {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.dados.protege(privacidade);
+    }
+}
+
+impl<T: Protegido> Protegido for Ranking<T> {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.itens.protege(privacidade);
+    }
+}
+
+impl<T: Protegido> Protegido for Posicao<T> {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.item.protege(privacidade);
+    }
+}
+
+impl Protegido for Deputado {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.cpf.protege(privacidade);
+    }
+}
+
+impl Protegido for DeputadoComTotal {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.deputado.protege(privacidade);
+    }
+}
+
+impl Protegido for Perfil {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.deputado.protege(privacidade);
+    }
+}
+
+impl Protegido for ClienteFornecedor {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.deputado.protege(privacidade);
+    }
+}
+
+impl Protegido for DeputadoEncontrado {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.deputado.protege(privacidade);
+    }
+}
+
+impl Protegido for FornecedorComTotal {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.documento.protege(privacidade);
+    }
+}
+
+impl Protegido for PerfilFornecedor {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.fornecedor.protege(privacidade);
+    }
+}
+
+impl Protegido for ResultadoBusca {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.deputados.protege(privacidade);
+    }
+}
+
+// Os fornecedores da busca e do ranking são identificados pelo nome, e não pelo documento.
+impl Protegido for FornecedorRanking {
+    fn protege(&mut self, _: Privacidade) {}
+}
+
+impl Protegido for DespesaComDeputado {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.cpf.protege(privacidade);
+    }
+}
+
+impl Protegido for DespesaSemDeputado {
+    fn protege(&mut self, _: Privacidade) {}
+}
+
+impl Protegido for DespesaExportada {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.cpf.protege(privacidade);
+        self.fornecedor_documento.protege(privacidade);
+    }
+}
+
+impl Protegido for Tabela {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.privacidade = privacidade;
+    }
+}
+
+impl Protegido for Cpf {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.exibe(privacidade);
+    }
+}
+
+impl Protegido for DocumentoFornecedor {
+    fn protege(&mut self, privacidade: Privacidade) {
+        self.exibe(privacidade);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::routes::{busca_despesas, clientes_fornecedor, lista_deputados, lista_despesas_por_cpf, lista_despesas_por_uf, lista_fornecedores, path_config, perfil_deputado, perfil_fornecedor, query_config, soma_despesas, tests::build_test_connection_pool};
+
+    fn get_csv() -> &'static str {
+        "txNomeParlamentar;cpf;ideCadastro;nuCarteiraParlamentar;nuLegislatura;sgUF;sgPartido;codLegislatura;numSubCota;txtDescricao;numEspecificacaoSubCota;txtDescricaoEspecificacao;txtFornecedor;txtCNPJCPF;txtNumero;indTipoDocumento;datEmissao;vlrDocumento;vlrGlosa;vlrLiquido;numMes;numAno;numParcela;txtPassageiro;txtTrecho;numLote;numRessarcimento;datPagamentoRestituicao;vlrRestituicao;nuDeputadoId;ideDocumento;urlDocumento
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;71838787089;1984;0;2025-01-10T00:00:00;1467;0;120.50;1;2025;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;PSB;57;1;PASSAGEM AÉREA;0;;TAM LINHAS AEREAS S/A;02.012.862/0001-60;1984;0;2025-02-10T00:00:00;1467;0;200;2;2025;0;;;0;;;;0;0;"
+    }
+
+    #[actix_web::test]
+    async fn mask_cpfs() {
+        assert_eq!(mascara_cpf("12345678909"), "***.456.789-**");
+        assert_eq!(mascara_cpf("123"), "***.***.***-**");
+
+        assert_eq!(cpf("22488012033", Privacidade::Mascarada), "***.880.120-**");
+        assert_eq!(documento("22488012033", Privacidade::Mascarada), "***.880.120-**");
+        assert_eq!(documento("02012862000160", Privacidade::Mascarada), "02012862000160");
+        assert_eq!(cpf("22488012033", Privacidade::Revelada), "22488012033");
+        assert_eq!(documento("22488012033", Privacidade::Revelada), "22488012033");
+
+        // Os valores aos quais a privacidade não foi aplicada saem mascarados.
+        let mut valor = DocumentoFornecedor::new("718.387.870-89").unwrap();
+        assert_eq!(serde_json::to_value(&valor).unwrap(), "***.387.870-**");
+        valor.protege(Privacidade::Revelada);
+        assert_eq!(serde_json::to_value(&valor).unwrap(), "71838787089");
+    }
+
+    #[actix_web::test]
+    async fn reveal_cpfs_to_authenticated_requests() {
+        let pool = build_test_connection_pool().unwrap();
+        crate::import::process_csv(&mut pool.get().unwrap(), get_csv().as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(lista_deputados)
+                .service(lista_fornecedores)
+                .service(lista_despesas_por_uf)
+                .app_data(query_config())
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(AdminConfig { token: Some("segredo".to_string()) }))
+                .app_data(web::Data::new(PrivacyConfig { tokens: vec!["leitor".to_string()], ..PrivacyConfig::default() }))
+        ).await;
+
+        let req = test::TestRequest::get().uri("/deputados").to_request();
+        let pagina: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(pagina["dados"][0]["cpf"], "***.880.120-**");
+
+        let req = test::TestRequest::get().uri("/fornecedores").to_request();
+        let pagina: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(pagina["dados"][0]["documento"], "02012862000160");
+        assert_eq!(pagina["dados"][1]["documento"], "***.387.870-**");
+
+        for token in ["segredo", "leitor"] {
+            let req = test::TestRequest::get().uri("/deputados")
+                .insert_header(("Authorization", format!("Bearer {token}")))
+                .to_request();
+            let pagina: Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(pagina["dados"][0]["cpf"], "22488012033");
+        }
+
+        let req = test::TestRequest::get().uri("/deputados").insert_header(("Authorization", "Bearer errado")).to_request();
+        let pagina: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(pagina["dados"][0]["cpf"], "***.880.120-**");
+
+        // As exportações transmitidas aos poucos, serializadas em outra thread, seguem a mesma política.
+        let req = test::TestRequest::get().uri("/despesas/uf/PB?format=csv").to_request();
+        let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+        assert!(body.contains("Jorge;***.880.120-**;PB;PSB;COMBUSTÍVEIS;POSTO IPIRANGA;***.387.870-**;"), "{body}");
+        assert!(body.contains(";02012862000160;"), "{body}");
+
+        let req = test::TestRequest::get().uri("/despesas/uf/PB?format=csv").insert_header(("Authorization", "Bearer leitor")).to_request();
+        let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+        assert!(body.contains("Jorge;22488012033;PB;PSB;COMBUSTÍVEIS;POSTO IPIRANGA;71838787089;"), "{body}");
+    }
+
+    #[actix_web::test]
+    async fn disable_masking() {
+        let pool = build_test_connection_pool().unwrap();
+        crate::import::process_csv(&mut pool.get().unwrap(), get_csv().as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(lista_deputados)
+                .app_data(query_config())
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(PrivacyConfig { mascara_cpf: false, ..PrivacyConfig::default() }))
+        ).await;
+
+        let req = test::TestRequest::get().uri("/deputados?format=ndjson").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let deputado: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(deputado["cpf"], "22488012033");
+    }
+
+    #[actix_web::test]
+    async fn look_up_cpfs() {
+        let pool = build_test_connection_pool().unwrap();
+        crate::import::process_csv(&mut pool.get().unwrap(), get_csv().as_bytes()).unwrap();
+
+        let servicos = || (
+            busca_despesas,
+            soma_despesas,
+            lista_despesas_por_cpf,
+            perfil_deputado,
+            lista_fornecedores,
+            clientes_fornecedor,
+            perfil_fornecedor,
+        );
+        let consultas = [
+            "/deputados/22488012033",
+            "/despesas/cpf/22488012033",
+            "/despesas/cpf/22488012033/soma",
+            "/despesas?cpf=22488012033",
+            "/fornecedores/71838787089",
+            "/fornecedores/71838787089/deputados",
+        ];
+
+        let app = test::init_service(
+            App::new()
+                .service(servicos())
+                .app_data(path_config())
+                .app_data(query_config())
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(PrivacyConfig { tokens: vec!["leitor".to_string()], ..PrivacyConfig::default() }))
+        ).await;
+
+        // A busca por um prefixo do documento encontra apenas os CNPJs.
+        let req = test::TestRequest::get().uri("/fornecedores?q=718").to_request();
+        let pagina: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(pagina["total"], 0);
+
+        let req = test::TestRequest::get().uri("/fornecedores?q=0201").to_request();
+        let pagina: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(pagina["total"], 1);
+        assert_eq!(pagina["dados"][0]["documento"], "02012862000160");
+
+        let req = test::TestRequest::get().uri("/fornecedores?q=718").insert_header(("Authorization", "Bearer leitor")).to_request();
+        let pagina: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(pagina["total"], 1);
+        assert_eq!(pagina["dados"][0]["documento"], "71838787089");
+
+        // As consultas por CPF são respondidas, com os CPFs mascarados.
+        for uri in consultas {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+            assert!(!body.contains("22488012033") && !body.contains("71838787089"), "{uri}: {body}");
+        }
+
+        let req = test::TestRequest::get().uri("/deputados/22488012033").to_request();
+        let perfil: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(perfil["cpf"], "***.880.120-**");
+
+        // Com a restrição habilitada, apenas as requisições autenticadas consultam por CPF.
+        let app = test::init_service(
+            App::new()
+                .service(servicos())
+                .app_data(path_config())
+                .app_data(query_config())
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(PrivacyConfig { tokens: vec!["leitor".to_string()], restringe_consultas: true, ..PrivacyConfig::default() }))
+        ).await;
+
+        for uri in consultas {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED, "{uri}");
+
+            let req = test::TestRequest::get().uri(uri).insert_header(("Authorization", "Bearer leitor")).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK, "{uri}");
+        }
+
+        let req = test::TestRequest::get().uri("/fornecedores/02012862000160").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Error};
+use diesel::{dsl, prelude::*, PgConnection};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::{FornecedorAgrupado, NovoGrupoFornecedor, VersaoDados};
+use crate::schema;
+use crate::text;
+use crate::types::Cnpj;
+
+// Resolução de fornecedores: agrupa as variantes do nome de um mesmo fornecedor que aparecem nas
+// despesas ("TAM", "TAM LINHAS AEREAS S/A", "Tam Linhas Aéreas"), para que as agregações por
+// fornecedor as somem juntas. Duas variantes são unidas quando têm o mesmo CNPJ válido, quando seus
+// nomes normalizados coincidem, ou quando seus nomes são semelhantes o bastante; uma variante cujo
+// nome é o início do nome de outras é unida a elas quando todas pertencem ao mesmo grupo.
+
+// Similaridade mínima entre os nomes normalizados de duas variantes para que sejam unidas.
+const SIMILARITY_THRESHOLD: f32 = 0.6;
+
+// Palavras que não distinguem fornecedores, e são ignoradas na comparação dos nomes.
+const STOPWORDS: &[&str] = &["ltda", "me", "epp", "eireli", "sa", "cia", "de", "da", "do", "das", "dos", "e"];
+
+// Palavras presentes em mais variantes do que isto são comuns demais ("comercio", "posto") para
+// indicar que duas variantes possam ser o mesmo fornecedor, e não são usadas para selecionar os
+// pares comparados.
+const MAX_BLOCK_SIZE: usize = 200;
+
+// Confiança atribuída a cada critério de união.
+const SAME_DOCUMENT: f32 = 1.0;
+const SAME_NAME: f32 = 0.95;
+const PREFIX: f32 = 0.7;
+
+// Variante do nome de um fornecedor, com seu documento e o número de despesas em que aparece.
+#[derive(Debug, Clone)]
+pub struct Variante {
+    pub nome: String,
+    pub documento: Option<String>,
+    pub despesas: i64,
+}
+
+// Grupo de variantes formado pela resolução, com a confiança da associação de cada variante.
+#[derive(Debug, PartialEq)]
+pub struct Grupo {
+    pub nome: String,
+    pub documento: Option<Cnpj>,
+    pub variantes: Vec<(String, f32)>,
+}
+
+impl Grupo {
+    pub fn confianca(&self) -> f32 {
+        self.variantes.iter().map(|(_, confianca)| *confianca).fold(1.0, f32::min)
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, ToSchema)]
+pub struct ResolutionSummary {
+    pub grupos: usize,
+    pub variantes: usize,
+}
+
+// Chave de comparação de um nome: as palavras normalizadas, sem acentos, pontuação, palavras de
+// uma letra e as palavras em STOPWORDS.
+pub fn chave(nome: &str) -> Vec<String> {
+    text::words(&text::normalize(nome))
+        .into_iter()
+        .map(|(_, word)| word.to_string())
+        .filter(|word| word.chars().count() > 1 && !STOPWORDS.contains(&word.as_str()))
+        .collect()
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+    // Maior confiança entre as uniões de que cada variante participou.
+    confianca: Vec<f32>,
+}
+
+impl UnionFind {
+    fn new(n: usize) -> Self {
+        Self { parent: (0..n).collect(), confianca: vec![0.0; n] }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+
+        let mut i = i;
+        while self.parent[i] != root {
+            let next = self.parent[i];
+            self.parent[i] = root;
+            i = next;
+        }
+
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize, confianca: f32) {
+        self.confianca[a] = self.confianca[a].max(confianca);
+        self.confianca[b] = self.confianca[b].max(confianca);
+
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[b] = a;
+        }
+    }
+}
+
+// Agrupa as variantes, retornando um grupo para cada fornecedor identificado, inclusive os que têm
+// uma única variante. O nome canônico de um grupo é a variante com mais despesas.
+pub fn cluster(variantes: &[Variante]) -> Vec<Grupo> {
+    let chaves: Vec<Vec<String>> = variantes.iter().map(|v| chave(&v.nome)).collect();
+    let nomes: Vec<String> = chaves.iter().map(|c| c.join(" ")).collect();
+    let trigramas: Vec<HashSet<String>> = nomes.iter().map(|n| text::trigrams(n)).collect();
+    let mut uf = UnionFind::new(variantes.len());
+
+    // Mesmo CNPJ válido.
+    let mut por_documento: HashMap<Cnpj, usize> = HashMap::new();
+    for (i, variante) in variantes.iter().enumerate() {
+        if let Some(documento) = variante.documento.as_deref().and_then(|d| Cnpj::new(d).ok()) {
+            match por_documento.get(&documento) {
+                Some(j) => uf.union(*j, i, SAME_DOCUMENT),
+                None => { por_documento.insert(documento, i); },
+            }
+        }
+    }
+
+    // Mesmo nome normalizado.
+    let mut por_nome: HashMap<&str, usize> = HashMap::new();
+    for (i, nome) in nomes.iter().enumerate() {
+        if nome.is_empty() {
+            continue;
+        }
+        match por_nome.get(nome.as_str()) {
+            Some(j) => uf.union(*j, i, SAME_NAME),
+            None => { por_nome.insert(nome, i); },
+        }
+    }
+
+    // Nomes semelhantes, comparando apenas as variantes que têm alguma palavra em comum.
+    let mut blocos: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, chave) in chaves.iter().enumerate() {
+        for palavra in chave.iter().filter(|p| p.chars().count() >= 3) {
+            blocos.entry(palavra).or_default().push(i);
+        }
+    }
+
+    let mut comparados = HashSet::new();
+    for bloco in blocos.values().filter(|bloco| bloco.len() <= MAX_BLOCK_SIZE) {
+        for (n, &i) in bloco.iter().enumerate() {
+            for &j in &bloco[n + 1..] {
+                if !comparados.insert((i.min(j), i.max(j))) {
+                    continue;
+                }
+
+                let similaridade = text::trigram_similarity(&trigramas[i], &trigramas[j]);
+                if similaridade >= SIMILARITY_THRESHOLD {
+                    uf.union(i, j, similaridade);
+                }
+            }
+        }
+    }
+
+    // Nomes que são o início de outros, desde que todos esses outros já estejam no mesmo grupo.
+    let mut por_primeira_palavra: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, chave) in chaves.iter().enumerate() {
+        if let Some(palavra) = chave.first() {
+            por_primeira_palavra.entry(palavra).or_default().push(i);
+        }
+    }
+
+    for (i, chave) in chaves.iter().enumerate() {
+        if chave.is_empty() || chave.iter().all(|palavra| palavra.chars().count() < 3) {
+            continue;
+        }
+
+        let maiores: Vec<usize> = por_primeira_palavra[chave[0].as_str()]
+            .iter()
+            .copied()
+            .filter(|&j| chaves[j].len() > chave.len() && chaves[j].starts_with(chave))
+            .collect();
+
+        let grupos: HashSet<usize> = maiores.iter().map(|&j| uf.find(j)).collect();
+        if grupos.len() == 1 {
+            uf.union(maiores[0], i, PREFIX);
+        }
+    }
+
+    let mut membros: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..variantes.len() {
+        let raiz = uf.find(i);
+        membros.entry(raiz).or_default().push(i);
+    }
+
+    let mut grupos: Vec<Grupo> = membros
+        .into_values()
+        .map(|membros| {
+            let canonico = *membros
+                .iter()
+                .max_by_key(|&&i| (variantes[i].despesas, variantes[i].nome.len()))
+                .unwrap();
+
+            let documento = membros
+                .iter()
+                .filter_map(|&i| variantes[i].documento.as_deref())
+                .find_map(|documento| Cnpj::new(documento).ok());
+
+            let mut grupo_variantes: Vec<(String, f32)> = membros
+                .iter()
+                .map(|&i| {
+                    let confianca = if membros.len() == 1 { 1.0 } else { uf.confianca[i] };
+                    (variantes[i].nome.clone(), confianca)
+                })
+                .collect();
+            grupo_variantes.sort_by(|a, b| a.0.cmp(&b.0));
+
+            Grupo { nome: variantes[canonico].nome.clone(), documento, variantes: grupo_variantes }
+        })
+        .collect();
+    grupos.sort_by(|a, b| a.nome.cmp(&b.nome));
+
+    grupos
+}
+
+// Executa a resolução sobre todas as variantes presentes nas despesas, substituindo os grupos
+// formados por execuções anteriores. Variantes e grupos manuais não são alterados.
+pub fn resolve_suppliers(connection: &mut PgConnection) -> Result<ResolutionSummary, Error> {
+    use schema::fornecedores_agrupados::dsl as agrupados;
+    use schema::grupos_fornecedores::dsl as grupos;
+    use schema::expenses::dsl as expenses;
+
+    connection.transaction(|connection| {
+        diesel::delete(agrupados::fornecedores_agrupados.filter(agrupados::manual.eq(false)))
+            .execute(connection)
+            .with_context(|| "failed to clear supplier groups")?;
+
+        diesel::delete(
+            grupos::grupos_fornecedores
+                .filter(grupos::manual.eq(false))
+                .filter(dsl::not(dsl::exists(
+                    agrupados::fornecedores_agrupados.filter(agrupados::grupo_id.eq(grupos::id))
+                )))
+        )
+        .execute(connection)
+        .with_context(|| "failed to clear supplier groups")?;
+
+        let variantes: Vec<Variante> = expenses::expenses
+            .filter(dsl::not(dsl::exists(
+                agrupados::fornecedores_agrupados.filter(agrupados::fornecedor.eq(expenses::fornecedor))
+            )))
+            .group_by(expenses::fornecedor)
+            .select((expenses::fornecedor, dsl::max(expenses::fornecedor_documento), dsl::count_star()))
+            .load::<(String, Option<String>, i64)>(connection)
+            .with_context(|| "failed to load suppliers")?
+            .into_iter()
+            .map(|(nome, documento, despesas)| Variante { nome, documento, despesas })
+            .collect();
+
+        let resolvidos = cluster(&variantes);
+        let summary = ResolutionSummary { grupos: resolvidos.len(), variantes: variantes.len() };
+
+        for lote in resolvidos.chunks(5000) {
+            let novos: Vec<NovoGrupoFornecedor> = lote
+                .iter()
+                .map(|grupo| NovoGrupoFornecedor {
+                    nome: grupo.nome.clone(),
+                    documento: grupo.documento.clone(),
+                    confianca: grupo.confianca(),
+                    manual: false,
+                })
+                .collect();
+
+            let ids: Vec<i32> = diesel::insert_into(grupos::grupos_fornecedores)
+                .values(&novos)
+                .returning(grupos::id)
+                .get_results(connection)
+                .with_context(|| "failed to insert supplier groups")?;
+
+            let membros: Vec<FornecedorAgrupado> = lote
+                .iter()
+                .zip(ids)
+                .flat_map(|(grupo, id)| {
+                    grupo.variantes.iter().map(move |(fornecedor, confianca)| FornecedorAgrupado {
+                        fornecedor: fornecedor.clone(),
+                        grupo_id: id,
+                        confianca: *confianca,
+                        manual: false,
+                    })
+                })
+                .collect();
+
+            for membros in membros.chunks(10000) {
+                diesel::insert_into(agrupados::fornecedores_agrupados)
+                    .values(membros)
+                    .execute(connection)
+                    .with_context(|| "failed to insert supplier group members")?;
+            }
+        }
+
+        VersaoDados::bump(connection).with_context(|| "failed to bump the dataset version")?;
+
+        log::info!("Resolvidas {} variantes de fornecedores em {} grupos.", summary.variantes, summary.grupos);
+        Ok(summary)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variante(nome: &str, documento: Option<&str>, despesas: i64) -> Variante {
+        Variante { nome: nome.to_string(), documento: documento.map(str::to_string), despesas }
+    }
+
+    fn nomes(grupo: &Grupo) -> Vec<&str> {
+        grupo.variantes.iter().map(|(nome, _)| nome.as_str()).collect()
+    }
+
+    #[test]
+    fn normaliza_chave() {
+        assert_eq!(chave("TAM LINHAS AÉREAS S/A"), vec!["tam", "linhas", "aereas"]);
+        assert_eq!(chave("Posto do Zé Ltda - ME"), vec!["posto", "ze"]);
+    }
+
+    #[test]
+    fn agrupa_variantes_do_mesmo_fornecedor() {
+        let grupos = cluster(&[
+            variante("TAM LINHAS AEREAS S/A", Some("02012862000160"), 10),
+            variante("Tam Linhas Aéreas", None, 3),
+            variante("TAM", None, 2),
+            variante("TAM LINHAS AÉREAS", Some("02012862000160"), 1),
+            variante("POSTO IPIRANGA", None, 5),
+            variante("POSTO SHELL", None, 5),
+            variante("POSTO", None, 1),
+        ]);
+
+        assert_eq!(grupos.len(), 4);
+
+        let tam = grupos.iter().find(|g| g.nome == "TAM LINHAS AEREAS S/A").unwrap();
+        assert_eq!(nomes(tam), vec!["TAM", "TAM LINHAS AEREAS S/A", "TAM LINHAS AÉREAS", "Tam Linhas Aéreas"]);
+        assert_eq!(tam.documento.as_ref().map(Cnpj::as_str), Some("02012862000160"));
+        assert_eq!(tam.confianca(), PREFIX);
+
+        // "POSTO" é o início de dois fornecedores distintos, e por isso não é unido a nenhum deles.
+        assert!(grupos.iter().any(|g| nomes(g) == vec!["POSTO"]));
+        assert!(grupos.iter().any(|g| nomes(g) == vec!["POSTO IPIRANGA"]));
+    }
+
+    #[test]
+    fn agrupa_nomes_com_erros_de_digitacao() {
+        let grupos = cluster(&[
+            variante("COMPANHIA BRASILEIRA DE DISTRIBUICAO", None, 4),
+            variante("COMPANHIA BRASILEIRA DE DISTRIBUIÇÃO", None, 1),
+            variante("COMPANHIA BRASILERA DE DISTRIBUICAO", None, 1),
+        ]);
+
+        assert_eq!(grupos.len(), 1);
+        assert_eq!(grupos[0].nome, "COMPANHIA BRASILEIRA DE DISTRIBUICAO");
+        assert!(grupos[0].confianca() >= SIMILARITY_THRESHOLD);
+    }
+
+    #[test]
+    fn ignora_documentos_invalidos() {
+        let grupos = cluster(&[
+            variante("HOTEL A", Some("00000000000000"), 1),
+            variante("RESTAURANTE B", Some("00000000000000"), 1),
+        ]);
+
+        assert_eq!(grupos.len(), 2);
+    }
+}
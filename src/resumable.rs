@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Error};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Metadados de um upload retomável, gravados ao lado do arquivo parcial.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UploadInfo {
+    pub length: u64,
+    pub file_name: Option<String>,
+    pub file_type: Option<String>,
+}
+
+// Armazena em disco os uploads retomáveis em andamento. Cada upload é composto por um arquivo
+// `{id}.part`, ao qual os blocos recebidos são acrescentados, e um arquivo `{id}.json` com seus
+// metadados; o offset atual de um upload é o tamanho do seu arquivo parcial. Os métodos fazem E/S
+// bloqueante, e devem ser chamados fora do executor assíncrono (web::block).
+pub struct UploadStore {
+    dir: PathBuf,
+    locked: Mutex<HashSet<String>>,
+}
+
+// Garante acesso exclusivo a um upload enquanto um bloco é gravado; o upload é liberado quando a
+// guarda é descartada. A guarda não depende da requisição, e pode ser movida para web::block.
+pub struct UploadGuard {
+    store: Arc<UploadStore>,
+    id: String,
+}
+
+impl Drop for UploadGuard {
+    fn drop(&mut self) {
+        self.store.locked.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl UploadStore {
+    pub fn new(dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create upload directory {}", dir.display()))?;
+
+        Ok(Self { dir, locked: Mutex::new(HashSet::new()) })
+    }
+
+    // Cria um upload vazio e retorna seu identificador.
+    pub fn create(&self, info: &UploadInfo) -> Result<String, Error> {
+        let id = Uuid::new_v4().simple().to_string();
+
+        File::create(self.data_path(&id)).with_context(|| "failed to create upload file")?;
+        fs::write(self.info_path(&id), serde_json::to_vec(info)?)
+            .with_context(|| "failed to write upload metadata")?;
+
+        Ok(id)
+    }
+
+    // Retorna os metadados e o offset atual de um upload, ou None caso ele não exista.
+    pub fn get(&self, id: &str) -> Result<Option<(UploadInfo, u64)>, Error> {
+        if !is_valid_id(id) {
+            return Ok(None);
+        }
+
+        let info = match fs::read(self.info_path(id)) {
+            Ok(info) => serde_json::from_slice(&info).with_context(|| "corrupted upload metadata")?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| "failed to read upload metadata"),
+        };
+        let offset = fs::metadata(self.data_path(id))
+            .with_context(|| "failed to read upload file")?
+            .len();
+
+        Ok(Some((info, offset)))
+    }
+
+    // Reserva um upload para escrita, retornando None caso outra requisição já o esteja usando.
+    pub fn lock(self: &Arc<Self>, id: &str) -> Option<UploadGuard> {
+        if self.locked.lock().unwrap().insert(id.to_string()) {
+            Some(UploadGuard { store: self.clone(), id: id.to_string() })
+        } else {
+            None
+        }
+    }
+
+    // Acrescenta um bloco ao final de um upload.
+    pub fn append(&self, guard: &UploadGuard, chunk: &[u8]) -> Result<(), Error> {
+        OpenOptions::new()
+            .append(true)
+            .open(self.data_path(&guard.id))
+            .and_then(|mut file| file.write_all(chunk))
+            .with_context(|| "failed to write upload chunk")
+    }
+
+    // Descarta os dados de um upload a partir de `offset`, como os de um bloco rejeitado.
+    pub fn truncate(&self, guard: &UploadGuard, offset: u64) -> Result<(), Error> {
+        OpenOptions::new()
+            .write(true)
+            .open(self.data_path(&guard.id))
+            .and_then(|file| file.set_len(offset))
+            .with_context(|| "failed to truncate upload file")
+    }
+
+    // Lê o conteúdo de um upload concluído.
+    pub fn read(&self, id: &str) -> Result<Vec<u8>, Error> {
+        fs::read(self.data_path(id)).with_context(|| "failed to read upload file")
+    }
+
+    // Remove um upload e seus metadados.
+    pub fn remove(&self, id: &str) -> Result<(), Error> {
+        fs::remove_file(self.data_path(id)).with_context(|| "failed to remove upload file")?;
+        fs::remove_file(self.info_path(id)).with_context(|| "failed to remove upload metadata")
+    }
+
+    // Remove os uploads abandonados, que não recebem blocos há mais de `ttl`, retornando quantos
+    // foram removidos. Os uploads sendo escritos são preservados.
+    pub fn expire(self: &Arc<Self>, ttl: Duration) -> Result<usize, Error> {
+        let mut removed = 0;
+
+        for entry in fs::read_dir(&self.dir).with_context(|| "failed to read upload directory")? {
+            let path = entry.with_context(|| "failed to read upload directory")?.path();
+            let id = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(id) if is_valid_id(id) && path.extension().is_some_and(|ext| ext == "json") => id,
+                _ => continue,
+            };
+            let Some(_guard) = self.lock(id) else { continue };
+
+            let modified = fs::metadata(self.data_path(id))
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            if modified.elapsed().unwrap_or_default() >= ttl {
+                let _ = fs::remove_file(self.data_path(id));
+                fs::remove_file(self.info_path(id)).with_context(|| "failed to remove upload metadata")?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    fn data_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.part"))
+    }
+
+    fn info_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+}
+
+// Os identificadores são UUIDs em hexadecimal; qualquer outra coisa é rejeitada para que o
+// identificador recebido na URL não possa apontar para fora do diretório de uploads.
+fn is_valid_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> Arc<UploadStore> {
+        let dir = std::env::temp_dir().join(format!("knex-uploads-test-{}", Uuid::new_v4().simple()));
+        Arc::new(UploadStore::new(dir).unwrap())
+    }
+
+    fn info() -> UploadInfo {
+        UploadInfo { length: 6, file_name: Some("despesas.csv".to_string()), file_type: None }
+    }
+
+    #[test]
+    fn acrescenta_blocos_e_atualiza_offset() {
+        let store = store();
+        let id = store.create(&info()).unwrap();
+
+        assert_eq!(store.get(&id).unwrap(), Some((info(), 0)));
+
+        let guard = store.lock(&id).unwrap();
+        store.append(&guard, b"abc").unwrap();
+        store.append(&guard, b"def").unwrap();
+
+        assert_eq!(store.get(&id).unwrap(), Some((info(), 6)));
+        assert_eq!(store.read(&id).unwrap(), b"abcdef");
+
+        store.truncate(&guard, 4).unwrap();
+        assert_eq!(store.read(&id).unwrap(), b"abcd");
+
+        store.remove(&id).unwrap();
+        assert_eq!(store.get(&id).unwrap(), None);
+    }
+
+    #[test]
+    fn impede_escritas_simultaneas() {
+        let store = store();
+        let id = store.create(&info()).unwrap();
+
+        let guard = store.lock(&id).unwrap();
+        assert!(store.lock(&id).is_none());
+
+        drop(guard);
+        assert!(store.lock(&id).is_some());
+    }
+
+    #[test]
+    fn remove_uploads_abandonados() {
+        let store = store();
+        let abandonado = store.create(&info()).unwrap();
+        let em_andamento = store.create(&info()).unwrap();
+
+        assert_eq!(store.expire(Duration::from_secs(3600)).unwrap(), 0);
+
+        let _guard = store.lock(&em_andamento).unwrap();
+        assert_eq!(store.expire(Duration::ZERO).unwrap(), 1);
+        assert_eq!(store.get(&abandonado).unwrap(), None);
+        assert!(store.get(&em_andamento).unwrap().is_some());
+    }
+
+    #[test]
+    fn rejeita_identificador_invalido() {
+        let store = store();
+
+        assert_eq!(store.get("../../etc/passwd").unwrap(), None);
+        assert_eq!(store.get("").unwrap(), None);
+    }
+}
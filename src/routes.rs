@@ -1,214 +1,1169 @@
 use actix_multipart::{Field, Multipart};
-use actix_web::{error::{ErrorBadRequest, ErrorInternalServerError}, get, http::header::ContentType, post, web, HttpResponse};
+use actix_web::{dev::HttpServiceFactory, get, http::header::{self, ContentType}, mime, post, web, HttpRequest, HttpResponse};
 use anyhow::Context;
-use diesel::{r2d2::ConnectionManager, Connection, PgConnection};
-use futures_util::StreamExt;
+use diesel::{r2d2::{ConnectionManager, PooledConnection}, Connection, PgConnection};
+use futures_util::{Stream, StreamExt};
 use r2d2::Pool;
 use serde::{Serialize, Deserialize};
+use utoipa::{IntoParams, ToSchema};
 
-use crate::{import::process_csv, models::{Deputado, Expense}};
+use crate::{config::UploadConfig, error::{ApiError, ErrorBody}, export::{self, Celula, Exportavel, Formato, NDJSON_MIME, XLSX_MIME}, import::{extract_zip, process_csv, ImportSummary}, models::{Agregacao, ClienteFornecedor, FornecedorRanking, DeputadoComTotal, ResultadoBusca, Tabela, FornecedorComTotal, PerfilFornecedor, pagination::{Cursor, Pagina, PaginaNumerada, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE}, search, DateFilter, Deputado, DeputyFilter, DespesaComDeputado, DespesaExportada, DespesaSemDeputado, Direction, Expense, ExpenseFilter, ExpenseOrder, LinhaDespesa, Perfil, Ranking, RankingFilter, Soma}, privacy::{Privacidade, Protegido}, types::{Cpf, DocumentoFornecedor, Uf}, versioning::Versao};
 
-// Representa um argumento contendo uma Unidade Federativa, usado na consulta a deputados por UF.
-#[derive(Deserialize)]
-struct QueryArgs {
-    uf: String,
-}
+mod admin;
+mod graphql;
+mod upload;
+
+pub use admin::*;
+pub use graphql::*;
+pub use upload::*;
 
 // Representa um argumento opcional contendo a página; utilizado nos endpoints que retornam um
 // grande volume de dados e usam paginação para reduzir o consumo de memória e de largura de banda.
-#[derive(Deserialize)]
+// O tamanho da página é aceito apenas pelas listagens que o permitem escolher.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct PageArgs {
     page: Option<u32>,
+    page_size: Option<i64>,
+}
+
+// Configuração dos parâmetros de caminho: valores inválidos, como um CPF ou uma UF inválidos, são
+// rejeitados com 400, em vez do 404 padrão.
+pub fn path_config() -> web::PathConfig {
+    web::PathConfig::default().error_handler(|err, _| ApiError::BadRequest(err.to_string()).into())
+}
+
+// Configurações dos parâmetros de URL e dos corpos JSON, cujos erros seguem o formato de ApiError.
+pub fn query_config() -> web::QueryConfig {
+    web::QueryConfig::default().error_handler(|err, _| ApiError::BadRequest(err.to_string()).into())
+}
+
+pub fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default().error_handler(|err, _| ApiError::BadRequest(err.to_string()).into())
+}
+
+// Destino do registro dos endpoints: a configuração do actix ou, no teste da especificação
+// OpenAPI, a lista das rotas registradas. Todo endpoint deve ter a anotação #[utoipa::path], que o
+// descreve na especificação.
+pub trait Registro {
+    fn endpoint<S: HttpServiceFactory + utoipa::Path + 'static>(&mut self, endpoint: S) -> &mut Self;
+}
+
+impl Registro for web::ServiceConfig {
+    fn endpoint<S: HttpServiceFactory + utoipa::Path + 'static>(&mut self, endpoint: S) -> &mut Self {
+        self.service(endpoint)
+    }
+}
+
+// Endpoints da API REST.
+pub(crate) fn endpoints<R: Registro>(registro: &mut R) {
+    registro
+        .endpoint(import_csv)
+        .endpoint(busca)
+        .endpoint(resolve_fornecedores)
+        .endpoint(lista_grupos_fornecedores)
+        .endpoint(une_grupos_fornecedores)
+        .endpoint(separa_grupo_fornecedores)
+        .endpoint(remove_despesas_do_ano)
+        .endpoint(opcoes_upload)
+        .endpoint(cria_upload)
+        .endpoint(consulta_upload)
+        .endpoint(envia_bloco)
+        .endpoint(remove_upload)
+        .endpoint(lista_deputados)
+        .endpoint(perfil_deputado)
+        .endpoint(lista_fornecedores)
+        .endpoint(perfil_fornecedor)
+        .endpoint(clientes_fornecedor)
+        .endpoint(agrega_despesas)
+        .endpoint(ranking_deputados)
+        .endpoint(ranking_fornecedores)
+        .endpoint(busca_despesas)
+        .endpoint(lista_despesas_por_cpf)
+        .endpoint(lista_despesas_por_uf)
+        .endpoint(soma_despesas)
+        .endpoint(soma_todas_as_despesas);
+}
+
+// Endpoints da API GraphQL, que não é versionada.
+pub(crate) fn endpoints_graphql<R: Registro>(registro: &mut R) {
+    registro
+        .endpoint(consulta_graphql)
+        .endpoint(graphiql);
+}
+
+// Registra os endpoints da API REST; veja versioning, que os monta sob o prefixo de cada versão.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    endpoints(cfg);
+}
+
+pub fn configure_graphql(cfg: &mut web::ServiceConfig) {
+    endpoints_graphql(cfg);
+}
+
+// Converte o resultado de uma operação, tratando a ausência do recurso como 404 com a mensagem
+// informada.
+fn resultado<T>(result: anyhow::Result<T>, nao_encontrado: &'static str) -> Result<T, ApiError> {
+    match result {
+        Ok(value) => Ok(value),
+        Err(e) => match ApiError::from(e) {
+            ApiError::NotFound(_) => Err(ApiError::NotFound(nao_encontrado.to_string())),
+            e => Err(e),
+        },
+    }
+}
+
+// Argumentos das listagens de despesas, paginadas por cursor: o cursor retornado na página
+// anterior, em next ou prev, e o número de despesas por página, limitado a MAX_PAGE_SIZE.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct CursorArgs {
+    cursor: Option<String>,
+    page_size: Option<i64>,
+}
+
+impl CursorArgs {
+    // Decodifica o cursor, que deve ter sido gerado por uma listagem com a mesma ordenação.
+    fn parse(&self, ordem: ExpenseOrder, direcao: Direction) -> Result<(Option<Cursor>, i64), ApiError> {
+        let cursor = match &self.cursor {
+            Some(token) => Some(
+                Cursor::decode(token)
+                    .filter(|cursor| cursor.matches(ordem, direcao))
+                    .ok_or_else(|| ApiError::BadRequest("Cursor inválido.".to_string()))?
+            ),
+            None => None,
+        };
+
+        Ok((cursor, self.page_size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)))
+    }
+}
+
+// Preenche os links para as páginas vizinhas, repetindo os parâmetros da requisição com o cursor
+// correspondente.
+fn fill_links<T>(req: &HttpRequest, pagina: &mut Pagina<T>) {
+    let params: Vec<&str> = req.query_string()
+        .split('&')
+        .filter(|param| !param.is_empty() && !param.starts_with("cursor="))
+        .collect();
+    let link = |cursor: &String| {
+        let mut params = params.clone();
+        let cursor = format!("cursor={cursor}");
+        params.push(&cursor);
+        format!("{}?{}", req.path(), params.join("&"))
+    };
+
+    pagina.links.next = pagina.next.as_ref().map(link);
+    pagina.links.prev = pagina.prev.as_ref().map(link);
+}
+
+// Formato das listagens e agregações: o parâmetro format ou, na sua ausência, o cabeçalho Accept.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct FormatArgs {
+    #[param(inline)]
+    format: Option<Formato>,
+}
+
+fn formato(req: &HttpRequest, format: Option<Formato>) -> Formato {
+    format.unwrap_or_else(|| {
+        req.headers().get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .map(Formato::from_accept)
+            .unwrap_or_default()
+    })
+}
+
+// Resposta com um arquivo exportado, em CSV ou XLSX, com as linhas já montadas.
+fn arquivo<S: AsRef<str>>(formato: Formato, nome: &str, colunas: &[S], linhas: Vec<Vec<Celula>>) -> Result<HttpResponse, ApiError> {
+    Ok(match formato {
+        Formato::Xlsx => HttpResponse::Ok()
+            .content_type(XLSX_MIME)
+            .insert_header(header::ContentDisposition::attachment(format!("{nome}.xlsx")))
+            .body(export::xlsx(colunas, linhas)?),
+        _ => HttpResponse::Ok()
+            .content_type(ContentType(mime::TEXT_CSV_UTF_8))
+            .insert_header(header::ContentDisposition::attachment(format!("{nome}.csv")))
+            .body(export::csv(Some(colunas), &linhas)?),
+    })
+}
+
+// Resposta em NDJSON, um item por linha.
+fn ndjson<T: Serialize>(itens: &[T]) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().content_type(NDJSON_MIME).body(export::ndjson(itens)?))
+}
+
+fn exporta<T: Exportavel + Serialize>(formato: Formato, nome: &str, itens: &[T]) -> Result<HttpResponse, ApiError> {
+    if formato == Formato::Ndjson {
+        return ndjson(itens);
+    }
+
+    arquivo(formato, nome, &T::colunas(), itens.iter().map(Exportavel::celulas).collect())
+}
+
+// Transmite as despesas que satisfazem os filtros, lidas em lotes numa tarefa bloqueante; cada lote é
+// convertido por `serializa`, com a privacidade informada, e enviado assim que lido. O canal
+// limita os lotes em espera, de modo que a leitura acompanha o ritmo do cliente e é interrompida
+// caso ele desista da resposta.
+fn transmite<T: LinhaDespesa + Protegido + 'static>(
+    mut connection: PooledConnection<ConnectionManager<PgConnection>>,
+    filtro: ExpenseFilter,
+    periodo: DateFilter,
+    privacidade: Privacidade,
+    inicio: Option<Vec<u8>>,
+    serializa: impl Fn(&[T]) -> Result<Vec<u8>, ApiError> + Send + 'static) -> impl Stream<Item = Result<web::Bytes, ApiError>> {
+        let (tx, rx) = tokio::sync::mpsc::channel::<Result<web::Bytes, ApiError>>(4);
+
+        actix_web::rt::task::spawn_blocking(move || {
+            if let Some(inicio) = inicio {
+                if tx.blocking_send(Ok(inicio.into())).is_err() {
+                    return;
+                }
+            }
+
+            let result = Expense::export(&mut connection, &filtro, &periodo, |lote: Vec<T>| {
+                let bytes = serializa(&lote.protegido(privacidade)).map(Into::into);
+                let ok = bytes.is_ok();
+
+                tx.blocking_send(bytes).is_ok() && ok
+            });
+            if let Err(e) = result {
+                log::error!("export failed: {e}");
+                let _ = tx.blocking_send(Err(e.into()));
+            }
+        });
+
+        futures_util::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|lote| (lote, rx))
+        })
 }
 
-// Representa a soma das despesas; existe apenas para ser serializado em JSON nos corpos das
-// respostas.
-#[derive(Serialize)]
-struct SumResult {
-    soma: f32,
+// Linhas da planilha das despesas que satisfazem os filtros. A leitura é interrompida, e a
+// exportação rejeitada, assim que as despesas excedem o limite de linhas, antes que a planilha
+// inteira seja montada em memória.
+fn linhas_xlsx(
+    connection: &mut PgConnection,
+    filtro: &ExpenseFilter,
+    periodo: &DateFilter,
+    privacidade: Privacidade,
+    limite: usize) -> Result<Vec<Vec<Celula>>, ApiError> {
+        let mut linhas = Vec::new();
+        let mut excedeu = false;
+
+        Expense::export(connection, filtro, periodo, |lote: Vec<DespesaExportada>| {
+            if linhas.len() + lote.len() > limite {
+                excedeu = true;
+                return false;
+            }
+
+            linhas.extend(lote.protegido(privacidade).iter().map(Exportavel::celulas));
+            true
+        })?;
+
+        if excedeu {
+            return Err(ApiError::BadRequest(format!("the export exceeds the {limite} rows of an XLSX sheet; use format=csv or narrow the filters")));
+        }
+
+        Ok(linhas)
+}
+
+// Exporta todas as despesas que satisfazem os filtros, sem paginação. Em CSV e em NDJSON, as
+// despesas são transmitidas aos poucos, as de NDJSON no mesmo formato `T` da listagem em JSON; em
+// XLSX, a planilha é montada em memória e enviada ao final.
+async fn exporta_despesas<T: LinhaDespesa + Protegido + Serialize + 'static>(
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>,
+    formato: Formato,
+    nome: &str,
+    filtro: ExpenseFilter,
+    periodo: DateFilter,
+    privacidade: Privacidade) -> Result<HttpResponse, ApiError> {
+        // A conexão é obtida antes de iniciar a resposta, para que a sua falta resulte em 503.
+        let mut connection = web::block(move || pool.get()).await??;
+
+        Ok(match formato {
+            Formato::Xlsx => {
+                let planilha = web::block(move || {
+                    let linhas = linhas_xlsx(&mut connection, &filtro, &periodo, privacidade, export::XLSX_MAX_LINHAS)?;
+
+                    Ok::<_, ApiError>(export::xlsx(&DespesaExportada::colunas(), linhas)?)
+                })
+                .await??;
+
+                HttpResponse::Ok()
+                    .content_type(XLSX_MIME)
+                    .insert_header(header::ContentDisposition::attachment(format!("{nome}.xlsx")))
+                    .body(planilha)
+            },
+            Formato::Ndjson => HttpResponse::Ok()
+                .content_type(NDJSON_MIME)
+                .streaming(transmite(connection, filtro, periodo, privacidade, None, |lote: &[T]| Ok(export::ndjson(lote)?))),
+            _ => {
+                let cabecalho = export::csv(Some(&DespesaExportada::colunas()), &[])?;
+                let serializa = |lote: &[DespesaExportada]| {
+                    let linhas: Vec<Vec<Celula>> = lote.iter().map(Exportavel::celulas).collect();
+                    Ok(export::csv::<&str>(None, &linhas)?)
+                };
+
+                HttpResponse::Ok()
+                    .content_type(ContentType(mime::TEXT_CSV_UTF_8))
+                    .insert_header(header::ContentDisposition::attachment(format!("{nome}.csv")))
+                    .streaming(transmite(connection, filtro, periodo, privacidade, Some(cabecalho), serializa))
+            },
+        })
 }
 
-// Retorna a soma de todas as despesas.
+// Retorna a soma de todas as despesas, obtida dos totais pré-calculados, e o momento em que eles
+// foram atualizados.
+#[utoipa::path(
+    get, path = "/despesas/soma", tag = "despesas",
+    summary = "Soma de todas as despesas",
+    responses((status = 200, description = "Soma das despesas e momento da atualização dos totais.", body = Soma)),
+)]
 #[get("/despesas/soma")]
 pub async fn soma_todas_as_despesas(
-    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, actix_web::Error> {
+    versao: Versao,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
         let result = web::block(move || {
             let connection = &mut pool.get().with_context(|| "database error")?;
 
-            Ok(
-                Expense::sum_all(connection)?
-            )
+            Soma::sum_all(connection).with_context(|| "database error")
         })
-        .await?
-        .map_err(ErrorInternalServerError::<anyhow::Error>)?;
+        .await??;
 
-        Ok(
-            HttpResponse::Ok()
-            .content_type(ContentType::json())
-            .body(serde_json::to_string(
-                &SumResult { soma: result }
-            )?)
-        )
+        versao.json(&result)
 }
 
-// Retorna a soma das despesas do deputado com um dado CPF. 
+// Retorna a soma das despesas do deputado com um dado CPF, também obtida dos totais pré-calculados.
+#[utoipa::path(
+    get, path = "/despesas/cpf/{cpf}/soma", tag = "despesas",
+    summary = "Soma das despesas de um deputado",
+    params(("cpf" = Cpf, Path, description = "CPF do deputado, com ou sem formatação.")),
+    responses(
+        (status = 200, description = "Soma das despesas e momento da atualização dos totais.", body = Soma),
+        (status = 400, description = "Parâmetros inválidos.", body = ErrorBody),
+        (status = 401, description = "Consulta por CPF sem autenticação, com a restrição habilitada.", body = ErrorBody),
+        (status = 404, description = "Deputado não encontrado.", body = ErrorBody),
+    ),
+)]
 #[get("/despesas/cpf/{cpf}/soma")]
 pub async fn soma_despesas(
-    cpf: web::Path<String>,
-    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, actix_web::Error> {
+    cpf: web::Path<Cpf>,
+    versao: Versao,
+    privacidade: Privacidade,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        privacidade.consulta_por_cpf()?;
         let cpf = cpf.into_inner();
 
         let result = web::block(move || {
             let connection = &mut pool.get().with_context(|| "database error")?;
 
             Ok(
-                Expense::sum_all_by_cpf(connection, &cpf)?
+                Soma::sum_by_cpf(connection, &cpf)?
             )
         })
-        .await?
-        .map_err(ErrorInternalServerError::<anyhow::Error>)?;
+        .await?;
+        let result = resultado(result, "Deputado não encontrado.")?;
+
+        versao.json(&result)
+}
+
+// Busca as despesas que satisfazem qualquer combinação dos filtros de ExpenseFilter, informados
+// como parâmetros de URL, na ordem escolhida e paginadas por cursor como as demais listagens. Em
+// CSV ou XLSX, retorna todas as despesas, sem paginação.
+#[utoipa::path(
+    get, path = "/despesas", tag = "despesas",
+    summary = "Busca de despesas",
+    params(ExpenseFilter, CursorArgs, FormatArgs),
+    responses(
+        (status = 200, description = "Página de despesas ou, em CSV, XLSX e NDJSON, todas as despesas.", content(
+            (Pagina<DespesaComDeputado> = "application/json"),
+            (String = "text/csv"),
+            (String = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+            (String = "application/x-ndjson"),
+        )),
+        (status = 400, description = "Parâmetros inválidos.", body = ErrorBody),
+        (status = 401, description = "Consulta por CPF sem autenticação, com a restrição habilitada.", body = ErrorBody),
+    ),
+)]
+#[get("/despesas")]
+pub async fn busca_despesas(
+    req: HttpRequest,
+    filtro: web::Query<ExpenseFilter>,
+    pagina: web::Query<CursorArgs>,
+    args: web::Query<FormatArgs>,
+    versao: Versao,
+    privacidade: Privacidade,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        let filtro = filtro.into_inner();
+        if filtro.cpf.is_some() {
+            privacidade.consulta_por_cpf()?;
+        }
+        let formato = formato(&req, args.format);
+        if formato != Formato::Json {
+            return exporta_despesas::<DespesaComDeputado>(pool, formato, "despesas", filtro, DateFilter::default(), privacidade).await;
+        }
+        let (cursor, page_size) = pagina.parse(filtro.ordem, filtro.direcao)?;
+
+        let mut result = web::block(move || {
+            let connection = &mut pool.get().with_context(|| "database error")?;
+
+            Expense::search::<DespesaComDeputado>(connection, &filtro, cursor.as_ref(), page_size).with_context(|| "database error")
+        })
+        .await??;
+        fill_links(&req, &mut result);
+
+        versao.pagina(&result.protegido(privacidade))
+}
+
+// Argumentos da agregação: as dimensões pelas quais as despesas são agrupadas e as métricas
+// calculadas, ambas separadas por vírgulas, e o formato da resposta.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AggregateArgs {
+    group_by: Option<String>,
+    metric: Option<String>,
+    #[param(inline)]
+    format: Option<Formato>,
+}
+
+// Agrupa as despesas por qualquer combinação das dimensões uf, deputado, partido, fornecedor,
+// categoria, ano e mes, calculando as métricas sum, count, avg, min e max de cada grupo. Aceita os
+// mesmos filtros da busca de despesas, e retorna uma tabela em JSON, CSV ou XLSX.
+#[utoipa::path(
+    get, path = "/despesas/agregado", tag = "despesas",
+    summary = "Agregação das despesas",
+    params(ExpenseFilter, AggregateArgs),
+    responses(
+        (status = 200, description = "Uma linha por grupo, com as dimensões e as métricas pedidas.", content(
+            (Tabela = "application/json"),
+            (String = "text/csv"),
+            (String = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+            (String = "application/x-ndjson"),
+        )),
+        (status = 400, description = "Parâmetros inválidos.", body = ErrorBody),
+        (status = 401, description = "Consulta por CPF sem autenticação, com a restrição habilitada.", body = ErrorBody),
+    ),
+)]
+#[get("/despesas/agregado")]
+pub async fn agrega_despesas(
+    req: HttpRequest,
+    filtro: web::Query<ExpenseFilter>,
+    args: web::Query<AggregateArgs>,
+    versao: Versao,
+    privacidade: Privacidade,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        let filtro = filtro.into_inner();
+        if filtro.cpf.is_some() {
+            privacidade.consulta_por_cpf()?;
+        }
+        let agregacao = Agregacao::parse(args.group_by.as_deref(), args.metric.as_deref())
+            .map_err(ApiError::BadRequest)?;
+
+        let result = web::block(move || {
+            let connection = &mut pool.get().with_context(|| "database error")?;
+
+            agregacao.run(connection, &filtro).with_context(|| "database error")
+        })
+        .await??
+        .protegido(privacidade);
+
+        Ok(match formato(&req, args.format) {
+            Formato::Json => versao.json(&result)?,
+            Formato::Csv => HttpResponse::Ok()
+                .content_type(ContentType(mime::TEXT_CSV_UTF_8))
+                .insert_header(header::ContentDisposition::attachment("agregado.csv"))
+                .body(result.to_csv()?),
+            Formato::Xlsx => arquivo(Formato::Xlsx, "agregado", &result.colunas, export::linhas_tabela(&result))?,
+            Formato::Ndjson => ndjson(&result.itens())?,
+        })
+}
+
+// Ranking dos deputados pelo total gasto, com a participação de cada um no total e a variação em
+// relação ao período anterior. Aceita o período (ano, ou de e ate), a UF, a categoria das despesas e
+// o número de posições (limite).
+#[utoipa::path(
+    get, path = "/ranking/deputados", tag = "rankings",
+    summary = "Ranking dos deputados",
+    params(RankingFilter, FormatArgs),
+    responses(
+        (status = 200, description = "Deputados pelo total gasto no período.", content(
+            (Ranking<Deputado> = "application/json"),
+            (String = "text/csv"),
+            (String = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+            (String = "application/x-ndjson"),
+        )),
+        (status = 400, description = "Parâmetros inválidos.", body = ErrorBody),
+    ),
+)]
+#[get("/ranking/deputados")]
+pub async fn ranking_deputados(
+    req: HttpRequest,
+    filtro: web::Query<RankingFilter>,
+    args: web::Query<FormatArgs>,
+    versao: Versao,
+    privacidade: Privacidade,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        let filtro = filtro.into_inner();
+        let periodo = filtro.periodo().map_err(ApiError::BadRequest)?;
+
+        let result = web::block(move || {
+            let connection = &mut pool.get().with_context(|| "database error")?;
+
+            Ranking::deputies(connection, &filtro, periodo).with_context(|| "database error")
+        })
+        .await??
+        .protegido(privacidade);
+
+        let formato = formato(&req, args.format);
+        if formato != Formato::Json {
+            return exporta(formato, "ranking-deputados", &result.itens);
+        }
+
+        versao.json(&result)
+}
+
+// Ranking dos fornecedores pelo total recebido, com os mesmos parâmetros do ranking dos deputados.
+#[utoipa::path(
+    get, path = "/ranking/fornecedores", tag = "rankings",
+    summary = "Ranking dos fornecedores",
+    params(RankingFilter, FormatArgs),
+    responses(
+        (status = 200, description = "Fornecedores pelo total recebido no período.", content(
+            (Ranking<FornecedorRanking> = "application/json"),
+            (String = "text/csv"),
+            (String = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+            (String = "application/x-ndjson"),
+        )),
+        (status = 400, description = "Parâmetros inválidos.", body = ErrorBody),
+    ),
+)]
+#[get("/ranking/fornecedores")]
+pub async fn ranking_fornecedores(
+    req: HttpRequest,
+    filtro: web::Query<RankingFilter>,
+    args: web::Query<FormatArgs>,
+    versao: Versao,
+    privacidade: Privacidade,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        let filtro = filtro.into_inner();
+        let periodo = filtro.periodo().map_err(ApiError::BadRequest)?;
+
+        let result = web::block(move || {
+            let connection = &mut pool.get().with_context(|| "database error")?;
+
+            Ranking::suppliers(connection, &filtro, periodo).with_context(|| "database error")
+        })
+        .await??
+        .protegido(privacidade);
+
+        let formato = formato(&req, args.format);
+        if formato != Formato::Json {
+            return exporta(formato, "ranking-fornecedores", &result.itens);
+        }
 
-        Ok(
-            HttpResponse::Ok()
-            .content_type(ContentType::json())
-            .body(serde_json::to_string(
-                &SumResult { soma: result }
-            )?)
-        )
+        versao.json(&result)
 }
 
-// Retorna a lista das despesas de uma determinada unidade federativa.
+// Retorna a lista das despesas de uma determinada unidade federativa. Os parâmetros opcionais de,
+// ate e data filtram as despesas por período, pela data de emissão ou pela competência.
+#[utoipa::path(
+    get, path = "/despesas/uf/{uf}", tag = "despesas",
+    summary = "Despesas de uma UF",
+    params(("uf" = Uf, Path, description = "Sigla da unidade federativa."), DateFilter, CursorArgs, FormatArgs),
+    responses(
+        (status = 200, description = "Página de despesas ou, em CSV, XLSX e NDJSON, todas as despesas.", content(
+            (Pagina<DespesaComDeputado> = "application/json"),
+            (String = "text/csv"),
+            (String = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+            (String = "application/x-ndjson"),
+        )),
+        (status = 400, description = "Parâmetros inválidos.", body = ErrorBody),
+    ),
+)]
 #[get("/despesas/uf/{uf}")]
+#[allow(clippy::too_many_arguments)]
 pub async fn lista_despesas_por_uf(
-    uf: web::Path<String>,
+    req: HttpRequest,
+    uf: web::Path<Uf>,
+    pagina: web::Query<CursorArgs>,
+    periodo: web::Query<DateFilter>,
+    args: web::Query<FormatArgs>,
+    versao: Versao,
+    privacidade: Privacidade,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        let uf = uf.into_inner();
+        let formato = formato(&req, args.format);
+        if formato != Formato::Json {
+            let filtro = ExpenseFilter { uf: Some(uf.clone()), ..Default::default() };
+            return exporta_despesas::<DespesaComDeputado>(pool, formato, &format!("despesas-{uf}"), filtro, periodo.into_inner(), privacidade).await;
+        }
+        let (cursor, page_size) = pagina.parse(ExpenseOrder::Data, Direction::Asc)?;
+
+        let mut result = web::block(move || {
+            let connection = &mut pool.get().with_context(|| "database error")?;
+
+            Expense::get_expenses_by_uf(connection, &uf, &periodo, cursor.as_ref(), page_size).with_context(|| "database error")
+        })
+        .await??;
+        fill_links(&req, &mut result);
+
+        versao.pagina(&result.protegido(privacidade))
+}
+
+// Retorna a lista das despesas do deputado com um dado CPF, com os mesmos filtros por período da
+// listagem por UF.
+#[utoipa::path(
+    get, path = "/despesas/cpf/{cpf}", tag = "despesas",
+    summary = "Despesas de um deputado",
+    params(("cpf" = Cpf, Path, description = "CPF do deputado, com ou sem formatação."), DateFilter, CursorArgs, FormatArgs),
+    responses(
+        (status = 200, description = "Página de despesas ou, em CSV, XLSX e NDJSON, todas as despesas.", content(
+            (Pagina<DespesaSemDeputado> = "application/json"),
+            (String = "text/csv"),
+            (String = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+            (String = "application/x-ndjson"),
+        )),
+        (status = 400, description = "Parâmetros inválidos.", body = ErrorBody),
+        (status = 401, description = "Consulta por CPF sem autenticação, com a restrição habilitada.", body = ErrorBody),
+    ),
+)]
+#[get("/despesas/cpf/{cpf}")]
+#[allow(clippy::too_many_arguments)]
+pub async fn lista_despesas_por_cpf(
+    req: HttpRequest,
+    cpf: web::Path<Cpf>,
+    pagina: web::Query<CursorArgs>,
+    periodo: web::Query<DateFilter>,
+    args: web::Query<FormatArgs>,
+    versao: Versao,
+    privacidade: Privacidade,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        privacidade.consulta_por_cpf()?;
+        let cpf = cpf.into_inner();
+        let formato = formato(&req, args.format);
+        if formato != Formato::Json {
+            let filtro = ExpenseFilter { cpf: Some(cpf.clone()), ..Default::default() };
+            return exporta_despesas::<DespesaSemDeputado>(pool, formato, &format!("despesas-{cpf}"), filtro, periodo.into_inner(), privacidade).await;
+        }
+        let (cursor, page_size) = pagina.parse(ExpenseOrder::Data, Direction::Asc)?;
+
+        let mut result = web::block(move || {
+            let connection = &mut pool.get().with_context(|| "database error")?;
+
+            Expense::get_expenses_by_cpf(connection, &cpf, &periodo, cursor.as_ref(), page_size).with_context(|| "database error")
+        })
+        .await??;
+        fill_links(&req, &mut result);
+
+        versao.pagina(&result.protegido(privacidade))
+}
+
+// Lista os deputados, opcionalmente filtrados pela UF, pelo partido e pelo início do nome, com o
+// total das despesas de cada um; a listagem é ordenada pelo nome, pela UF, pelo partido ou pelo
+// total, e paginada por número.
+#[utoipa::path(
+    get, path = "/deputados", tag = "deputados",
+    summary = "Listagem dos deputados",
+    params(DeputyFilter, PageArgs, FormatArgs),
+    responses(
+        (status = 200, description = "Página de deputados ou, em CSV, XLSX e NDJSON, todos os deputados.", content(
+            (PaginaNumerada<DeputadoComTotal> = "application/json"),
+            (String = "text/csv"),
+            (String = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+            (String = "application/x-ndjson"),
+        )),
+        (status = 400, description = "Parâmetros inválidos.", body = ErrorBody),
+    ),
+)]
+#[get("/deputados")]
+pub async fn lista_deputados(
+    req: HttpRequest,
+    filtro: web::Query<DeputyFilter>,
     page: web::Query<PageArgs>,
-    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, actix_web::Error> {
-        let uf = uf.into_inner().trim().to_uppercase();
-        let page = if let Some(page) = page.page {
-            page
-        } else {
-            1
-        };
+    args: web::Query<FormatArgs>,
+    versao: Versao,
+    privacidade: Privacidade,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        let filtro = filtro.into_inner();
+        let formato = formato(&req, args.format);
+        if formato != Formato::Json {
+            let result = web::block(move || {
+                let connection = &mut pool.get().with_context(|| "database error")?;
+
+                Deputado::all(connection, &filtro).with_context(|| "database error")
+            })
+            .await??
+            .protegido(privacidade);
+
+            return exporta(formato, "deputados", &result);
+        }
+        let (page, page_size) = (page.page.unwrap_or(1), page.page_size.unwrap_or(DEFAULT_PAGE_SIZE));
+
+        let result = web::block(move || {
+            let connection = &mut pool.get().with_context(|| "database error")?;
+
+            Deputado::list(connection, &filtro, page, page_size).with_context(|| "database error")
+        })
+        .await??;
+
+        versao.pagina_numerada(&result.protegido(privacidade))
+}
+
+// Retorna o perfil do deputado, identificado pelo seu identificador ou pelo CPF: os seus dados, os
+// totais das suas despesas por ano e por categoria, os principais fornecedores, e as datas da
+// primeira e da última despesa.
+#[utoipa::path(
+    get, path = "/deputados/{deputado}", tag = "deputados",
+    summary = "Perfil de um deputado",
+    params(("deputado" = String, Path, description = "Identificador ou CPF do deputado.")),
+    responses(
+        (status = 200, description = "Perfil do deputado.", body = Perfil),
+        (status = 401, description = "Consulta por CPF sem autenticação, com a restrição habilitada.", body = ErrorBody),
+        (status = 404, description = "Deputado não encontrado.", body = ErrorBody),
+    ),
+)]
+#[get("/deputados/{deputado}")]
+pub async fn perfil_deputado(
+    deputado: web::Path<String>,
+    versao: Versao,
+    privacidade: Privacidade,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        let deputado = deputado.into_inner();
+        if Deputado::chave_e_cpf(&deputado) {
+            privacidade.consulta_por_cpf()?;
+        }
 
         let result = web::block(move || {
             let connection = &mut pool.get().with_context(|| "database error")?;
 
             Ok(
-                Expense::get_expenses_by_uf(connection, &uf, page).with_context(|| "database error")?
+                Perfil::get(connection, &deputado)?
             )
         })
-        .await?
-        .map_err(ErrorInternalServerError::<anyhow::Error>)?;
+        .await?;
+        let result = resultado(result, "Deputado não encontrado.")?;
 
-        Ok(
-            HttpResponse::Ok()
-            .content_type(ContentType::json())
-            .body(serde_json::to_string(&result)?)
-        )
+        versao.json(&result.protegido(privacidade))
 }
 
-// Retorna a lista das despesas do deputado com um dado CPF.
-#[get("/despesas/cpf/{cpf}")]
-pub async fn lista_despesas_por_cpf(
-    cpf: web::Path<String>,
+// Argumento opcional da listagem de fornecedores: o texto buscado no nome ou no documento.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SupplierSearchArgs {
+    q: Option<String>,
+}
+
+// Lista os fornecedores, identificados pelo documento, com o total recebido, o número de despesas
+// e o número de deputados que lhes pagaram, do que mais recebeu ao que menos recebeu. Aceita uma
+// busca pelo nome ou pelo documento, e é paginada por número.
+#[utoipa::path(
+    get, path = "/fornecedores", tag = "fornecedores",
+    summary = "Listagem dos fornecedores",
+    params(SupplierSearchArgs, PageArgs, FormatArgs),
+    responses(
+        (status = 200, description = "Página de fornecedores ou, em CSV, XLSX e NDJSON, todos os fornecedores.", content(
+            (PaginaNumerada<FornecedorComTotal> = "application/json"),
+            (String = "text/csv"),
+            (String = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+            (String = "application/x-ndjson"),
+        )),
+        (status = 400, description = "Parâmetros inválidos.", body = ErrorBody),
+    ),
+)]
+#[get("/fornecedores")]
+pub async fn lista_fornecedores(
+    req: HttpRequest,
+    args: web::Query<SupplierSearchArgs>,
     page: web::Query<PageArgs>,
-    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, actix_web::Error> {
-        let cpf = cpf.into_inner();
-        let page = if let Some(page) = page.page {
-            page
-        } else {
-            1
-        };
+    formato_args: web::Query<FormatArgs>,
+    versao: Versao,
+    privacidade: Privacidade,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        let q = args.into_inner().q;
+        let formato = formato(&req, formato_args.format);
+        if formato != Formato::Json {
+            let result = web::block(move || {
+                let connection = &mut pool.get().with_context(|| "database error")?;
+
+                FornecedorComTotal::all(connection, q.as_deref(), privacidade).with_context(|| "database error")
+            })
+            .await??
+            .protegido(privacidade);
+
+            return exporta(formato, "fornecedores", &result);
+        }
+        let (page, page_size) = (page.page.unwrap_or(1), page.page_size.unwrap_or(DEFAULT_PAGE_SIZE));
+
+        let result = web::block(move || {
+            let connection = &mut pool.get().with_context(|| "database error")?;
+
+            FornecedorComTotal::list(connection, q.as_deref(), privacidade, page, page_size).with_context(|| "database error")
+        })
+        .await??;
+
+        versao.pagina_numerada(&result.protegido(privacidade))
+}
+
+// Retorna o perfil do fornecedor com o documento informado: os seus totais, os nomes com que
+// aparece nas despesas, e os totais por ano e por categoria.
+#[utoipa::path(
+    get, path = "/fornecedores/{documento}", tag = "fornecedores",
+    summary = "Perfil de um fornecedor",
+    params(("documento" = DocumentoFornecedor, Path, description = "CNPJ ou CPF do fornecedor, com ou sem formatação.")),
+    responses(
+        (status = 200, description = "Perfil do fornecedor.", body = PerfilFornecedor),
+        (status = 400, description = "Parâmetros inválidos.", body = ErrorBody),
+        (status = 401, description = "Consulta por CPF sem autenticação, com a restrição habilitada.", body = ErrorBody),
+        (status = 404, description = "Fornecedor não encontrado.", body = ErrorBody),
+    ),
+)]
+#[get("/fornecedores/{documento}")]
+pub async fn perfil_fornecedor(
+    documento: web::Path<DocumentoFornecedor>,
+    versao: Versao,
+    privacidade: Privacidade,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        let documento = documento.into_inner();
+        if documento.as_str().len() == 11 {
+            privacidade.consulta_por_cpf()?;
+        }
 
         let result = web::block(move || {
             let connection = &mut pool.get().with_context(|| "database error")?;
 
             Ok(
-                Expense::get_expenses_by_cpf(connection, &cpf, page).with_context(|| "database error")?
+                PerfilFornecedor::get(connection, &documento)?
             )
         })
-        .await?
-        .map_err(ErrorInternalServerError::<anyhow::Error>)?;
+        .await?;
+        let result = resultado(result, "Fornecedor não encontrado.")?;
 
-        Ok(
-            HttpResponse::Ok()
-            .content_type(ContentType::json())
-            .body(serde_json::to_string(&result)?)
-        )
+        versao.json(&result.protegido(privacidade))
 }
 
-// Lista os deputados de uma determinada unidade federativa; esta é informada através de um
-// parâmetro de URL.
-#[get("/deputados")]
-pub async fn lista_deputados_por_uf(
-    args: web::Query<QueryArgs>,
-    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, actix_web::Error> {
-        let uf = args.uf.trim().to_uppercase();
-        if args.uf.len() != 2 {
-            return Err(ErrorBadRequest("UF inválida."));
+// Lista os deputados que pagaram o fornecedor com o documento informado, com o total pago e o
+// número de despesas de cada um, do que mais pagou ao que menos pagou.
+#[utoipa::path(
+    get, path = "/fornecedores/{documento}/deputados", tag = "fornecedores",
+    summary = "Deputados que pagaram um fornecedor",
+    params(("documento" = DocumentoFornecedor, Path, description = "CNPJ ou CPF do fornecedor, com ou sem formatação."), FormatArgs),
+    responses(
+        (status = 200, description = "Deputados, do que mais pagou ao que menos pagou.", content(
+            (Vec<ClienteFornecedor> = "application/json"),
+            (String = "text/csv"),
+            (String = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+            (String = "application/x-ndjson"),
+        )),
+        (status = 400, description = "Parâmetros inválidos.", body = ErrorBody),
+        (status = 401, description = "Consulta por CPF sem autenticação, com a restrição habilitada.", body = ErrorBody),
+        (status = 404, description = "Fornecedor não encontrado.", body = ErrorBody),
+    ),
+)]
+#[get("/fornecedores/{documento}/deputados")]
+pub async fn clientes_fornecedor(
+    req: HttpRequest,
+    documento: web::Path<DocumentoFornecedor>,
+    args: web::Query<FormatArgs>,
+    versao: Versao,
+    privacidade: Privacidade,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        let documento = documento.into_inner();
+        if documento.as_str().len() == 11 {
+            privacidade.consulta_por_cpf()?;
+        }
+        let nome = format!("fornecedor-{documento}-deputados");
+
+        let result = web::block(move || {
+            let connection = &mut pool.get().with_context(|| "database error")?;
+
+            Ok(
+                ClienteFornecedor::list(connection, &documento)?
+            )
+        })
+        .await?;
+        let result = resultado(result, "Fornecedor não encontrado.")?.protegido(privacidade);
+
+        let formato = formato(&req, args.format);
+        if formato != Formato::Json {
+            return exporta(formato, &nome, &result);
+        }
+
+        versao.json(&result)
+}
+
+// Argumentos da busca textual: o texto buscado e, opcionalmente, o número máximo de resultados de
+// cada tipo.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SearchArgs {
+    q: String,
+    limite: Option<i64>,
+}
+
+// Número máximo de resultados de cada tipo retornados pela busca.
+const MAX_SEARCH_RESULTS: i64 = 50;
+
+// Busca fornecedores e deputados pelo nome, tolerando diferenças de acentuação e erros de
+// digitação; os resultados são ordenados pela relevância, e as palavras correspondentes à busca
+// vêm destacadas.
+#[utoipa::path(
+    get, path = "/busca", tag = "busca",
+    summary = "Busca de fornecedores e deputados pelo nome",
+    params(SearchArgs),
+    responses(
+        (status = 200, description = "Fornecedores e deputados, pela relevância.", body = ResultadoBusca),
+        (status = 400, description = "Parâmetros inválidos.", body = ErrorBody),
+    ),
+)]
+#[get("/busca")]
+pub async fn busca(
+    args: web::Query<SearchArgs>,
+    versao: Versao,
+    privacidade: Privacidade,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        let SearchArgs { q, limite } = args.into_inner();
+        let q = q.trim().to_string();
+        if q.chars().count() < 2 {
+            return Err(ApiError::BadRequest("a busca deve ter ao menos 2 caracteres.".to_string()));
         }
+        let limite = limite.unwrap_or(10).clamp(1, MAX_SEARCH_RESULTS);
+
         let result = web::block(move || {
-            let mut connection = pool
-                .get()
-                .with_context(|| "database error")?;
+            let connection = &mut pool.get().with_context(|| "database error")?;
 
-                Deputado::get_all_by_uf(&mut connection, &uf).with_context(|| "database error")
+            search(connection, &q, limite).with_context(|| "database error")
         })
-        .await?
-        .map_err(ErrorInternalServerError)?;
+        .await??;
+
+        versao.json(&result.protegido(privacidade))
+}
+
+// Formato de um arquivo enviado para importação, deduzido do seu tipo de conteúdo.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum UploadKind {
+    Csv,
+    Zip,
+}
+
+impl UploadKind {
+    // Identifica o formato de um campo do multipart. Campos sem tipo de conteúdo são tratados como
+    // texto, e portanto como CSV; campos do tipo application/octet-stream são identificados pela
+    // extensão do nome do arquivo.
+    fn detect(content_type: Option<&mime::Mime>, file_name: Option<&str>) -> Option<Self> {
+        let content_type = match content_type {
+            Some(content_type) => content_type,
+            None => return Some(Self::Csv),
+        };
+
+        match (content_type.type_().as_str(), content_type.subtype().as_str()) {
+            ("text", "csv") | ("text", "plain") | ("application", "csv")
+                | ("application", "vnd.ms-excel") => Some(Self::Csv),
+            ("application", "zip") | ("application", "x-zip-compressed") => Some(Self::Zip),
+            ("application", "octet-stream") => {
+                let file_name = file_name?.to_lowercase();
+                if file_name.ends_with(".csv") {
+                    Some(Self::Csv)
+                } else if file_name.ends_with(".zip") {
+                    Some(Self::Zip)
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        }
+    }
+}
 
+// Representa um arquivo recebido no corpo de uma requisição de importação.
+struct Upload {
+    name: String,
+    kind: UploadKind,
+    bytes: Vec<u8>,
+}
 
-        Ok(
-            HttpResponse::Ok()
-            .content_type(ContentType::json())
-            .body(serde_json::to_string(&result)?)
-        )
+// Resultado da importação de um arquivo; cada CSV, inclusive os contidos em um ZIP, é importado
+// em sua própria transação e tem seu próprio resultado.
+#[derive(Serialize, ToSchema)]
+struct ImportResult {
+    arquivo: String,
+    #[serde(flatten)]
+    resumo: Option<ImportSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    erro: Option<String>,
 }
 
-// Processa um CSV contendo as despesas dos deputados; aceita o arquivo como um Multipart, e, caso
-// haja múltiplos arquivos, considera apenas o primeiro.
+// Processa CSVs contendo as despesas dos deputados. Os arquivos são lidos dos campos do multipart
+// cujo nome é o configurado em UploadConfig; os demais campos são ignorados. Cada arquivo pode ser
+// um CSV ou um ZIP contendo CSVs, e é importado separadamente dos demais.
+#[utoipa::path(
+    post, path = "/processar-ceap", tag = "importação",
+    summary = "Importação de arquivos da CEAP",
+    request_body(content_type = "multipart/form-data", description = "Um ou mais CSVs, ou ZIPs contendo CSVs, no campo configurado (padrão: file)."),
+    responses(
+        (status = 200, description = "Resultado da importação de cada arquivo.", body = Vec<ImportResult>),
+        (status = 413, description = "Arquivo maior que o tamanho máximo.", body = ErrorBody),
+        (status = 415, description = "Tipo de arquivo não suportado.", body = ErrorBody),
+        (status = 422, description = "Algum arquivo foi rejeitado; o resultado de cada um está em details.", body = ErrorBody),
+    ),
+)]
 #[post("/processar-ceap")]
 pub async fn import_csv(
+    req: HttpRequest,
     mut payload: Multipart,
-    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, actix_web::Error> {
-    
-    let field = if let Some(field) = payload.next().await {
-        field
+    config: web::Data<UploadConfig>,
+    versao: Versao,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+
+    let content_length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<usize>().ok());
+
+    if content_length.is_some_and(|length| length > config.max_size) {
+        return Err(ApiError::PayloadTooLarge("upload exceeds the maximum size".to_string()));
+    }
+
+    let mut uploads = Vec::new();
+    let mut total_size = 0;
+
+    while let Some(field) = payload.next().await {
+        let field = match field {
+            Ok(field) => field,
+            Err(e) => return Err(ApiError::BadRequest(e.to_string())),
+        };
+
+        // Os demais campos são descartados, mas contam para o tamanho máximo da requisição, que
+        // pode não informar o Content-Length.
+        if field.name() != Some(config.field_name.as_str()) {
+            total_size += descarta_campo(field, config.max_size - total_size).await?;
+            continue;
+        }
+
+        let file_name = field
+            .content_disposition()
+            .and_then(|disposition| disposition.get_filename())
+            .map(str::to_string);
+
+        let kind = match UploadKind::detect(field.content_type(), file_name.as_deref()) {
+            Some(kind) => kind,
+            None => return Err(ApiError::UnsupportedMediaType("only CSV and ZIP files are accepted".to_string())),
+        };
+
+        let bytes = process_multipart(field, config.max_size - total_size).await?;
+        total_size += bytes.len();
+
+        uploads.push(Upload {
+            name: file_name.unwrap_or_else(|| format!("{}[{}]", config.field_name, uploads.len())),
+            kind,
+            bytes,
+        });
+    }
+
+    if uploads.is_empty() {
+        return Err(ApiError::BadRequest("multipart file not found".to_string()));
+    }
+
+    let max_size = config.max_size;
+    let results = web::block(move || run_imports(&pool, uploads, max_size))
+        .await??;
+
+    Ok(import_response(versao, results))
+}
+
+// Importa os arquivos recebidos, cada CSV em sua própria transação. Os CSVs contidos em arquivos
+// ZIP são importados individualmente.
+fn run_imports(
+    pool: &Pool<ConnectionManager<PgConnection>>,
+    uploads: Vec<Upload>,
+    max_size: usize) -> Result<Vec<ImportResult>, anyhow::Error> {
+    let mut connection = pool.get().with_context(|| "database error")?;
+    let mut results = Vec::new();
+
+    for upload in uploads {
+        let units = match upload.kind {
+            UploadKind::Csv => vec![(upload.name, upload.bytes)],
+            UploadKind::Zip => match extract_zip(&upload.bytes, max_size) {
+                Ok(files) => files
+                    .into_iter()
+                    .map(|(name, bytes)| (format!("{}/{}", upload.name, name), bytes))
+                    .collect(),
+                Err(e) => {
+                    results.push(ImportResult { arquivo: upload.name, resumo: None, erro: Some(format!("{e:#}")) });
+                    continue;
+                },
+            },
+        };
+
+        for (name, bytes) in units {
+            let result = connection.transaction(
+                |connection| process_csv(connection, bytes.as_slice())
+            );
+
+            results.push(match result {
+                Ok(summary) => ImportResult { arquivo: name, resumo: Some(summary), erro: None },
+                Err(e) => {
+                    log::error!("failed to import {name}: {e:#}");
+                    ImportResult { arquivo: name, resumo: None, erro: Some(format!("{e:#}")) }
+                },
+            });
+        }
+    }
+
+    Ok(results)
+}
+
+// Monta a resposta de uma importação: 200, com o resultado de cada arquivo, caso todos tenham sido
+// importados, ou um ApiError::Import, com os resultados em `details`, caso algum tenha falhado.
+fn import_response(versao: Versao, results: Vec<ImportResult>) -> HttpResponse {
+    let falhas = results.iter().filter(|result| result.erro.is_some()).count();
+    let erro = if falhas == 0 {
+        match versao.json(&results) {
+            Ok(response) => return response,
+            Err(e) => e,
+        }
     } else {
-        return Err(ErrorBadRequest("multipart file not found"))
+        match serde_json::to_value(&results) {
+            Ok(details) => ApiError::Import {
+                message: format!("{falhas} de {} arquivos não puderam ser importados.", results.len()),
+                details,
+            },
+            Err(e) => ApiError::from(e),
+        }
     };
 
-    let field = match field {
-        Ok(field) => field,
-        Err(e) => return Err(actix_web::error::ErrorBadRequest(e.to_string())),
-    };
+    // O erro acompanha a resposta, para que o middleware da v2 o envolva como os demais.
+    HttpResponse::from_error(erro)
+}
 
-    let bytes = process_multipart(field).await?;
+// Lê e descarta um campo do multipart, retornando o seu tamanho, que não pode exceder `limit`.
+async fn descarta_campo(mut field: Field, limit: usize) -> Result<usize, ApiError> {
+    let mut size = 0;
 
-    web::block(move || -> Result<(), anyhow::Error>{
-        let mut connection = pool.get().expect("");
-        
-        connection.transaction(
-            |connection| process_csv(connection, bytes.as_slice())
-        )
-    })
-    .await?
-    .map_err(ErrorInternalServerError)?;
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk.map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        size += chunk.len();
+        if size > limit {
+            return Err(ApiError::PayloadTooLarge("upload exceeds the maximum size".to_string()));
+        }
+    }
 
-    Ok(HttpResponse::Ok().body("File saved successfully"))
+    Ok(size)
 }
 
-// Processa o Multipart contido no corpo de uma requisição
-async fn process_multipart(mut field: Field) -> Result<Vec<u8>, actix_web::Error> {
+// Lê o conteúdo de um campo do Multipart contido no corpo de uma requisição, falhando caso ele
+// exceda `limit` bytes.
+async fn process_multipart(mut field: Field, limit: usize) -> Result<Vec<u8>, ApiError> {
     let mut bytes: Vec<u8> = Vec::new();
 
-        // Write the file content to the file
     while let Some(chunk) = field.next().await {
         match chunk {
             Ok(chunk) => {
-                bytes.append(&mut chunk.to_vec());
+                if bytes.len() + chunk.len() > limit {
+                    return Err(ApiError::PayloadTooLarge("upload exceeds the maximum size".to_string()));
+                }
+                bytes.extend_from_slice(&chunk);
             },
-            Err(e) => return Err(actix_web::error::ErrorBadRequest(e.to_string())),
+            Err(e) => return Err(ApiError::BadRequest(e.to_string())),
         };
     }
 
@@ -216,14 +1171,14 @@ async fn process_multipart(mut field: Field) -> Result<Vec<u8>, actix_web::Error
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use r2d2::CustomizeConnection;
     use actix_multipart_test::MultiPartFormDataBuilder;
-    use actix_web::{test, App};
+    use actix_web::{http::StatusCode, test, App};
     use anyhow::Context;
     use diesel::{r2d2::ConnectionManager, Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper};
-    use crate::{get_url_from_env, models::Deputado, routes::import_csv, schema};
+    use crate::{config::{PrivacyConfig, UploadConfig}, get_url_from_env, models::Deputado, routes::import_csv, schema};
 
     #[derive(Debug)]
     pub struct TransactionCustomizer;
@@ -240,13 +1195,19 @@ mod tests {
 
         let manager = ConnectionManager::<PgConnection>::new(url);
         
-        Ok(
-            Pool::builder()
-                .test_on_check_out(true)
-                .connection_customizer(Box::new(TransactionCustomizer))
-                .build(manager)
-                .with_context(|| "failed to build test connection pool")?
-        )
+        // Uma única conexão, para que todas as requisições de um teste vejam a mesma transação,
+        // inclusive as que chegam antes que uma exportação transmitida devolva a sua conexão.
+        Pool::builder()
+            .max_size(1)
+            .test_on_check_out(true)
+            .connection_customizer(Box::new(TransactionCustomizer))
+            .build(manager)
+            .with_context(|| "failed to build test connection pool")
+    }
+
+    // Configuração de privacidade dos testes, com o token "leitor".
+    pub fn privacy_config() -> web::Data<PrivacyConfig> {
+        web::Data::new(PrivacyConfig { tokens: vec!["leitor".to_string()], ..PrivacyConfig::default() })
     }
 
     fn get_csv() -> &'static str {
@@ -265,6 +1226,7 @@ Jorge;22488012033;;;2023;PB;;57;1;Descrição;0;;Fornecedor;CNPJ-fornecedor;1984
             App::new()
                 .service(import_csv)
                 .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(UploadConfig::default()))
         ).await;
 
         let req =
@@ -286,6 +1248,7 @@ Jorge;22488012033;;;2023;PB;;57;1;Descrição;0;;Fornecedor;CNPJ-fornecedor;1984
             App::new()
                 .service(import_csv)
                 .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(UploadConfig::default()))
         ).await;
 
         let (header, payload): ((String, String), Vec<u8>) = MultiPartFormDataBuilder::new().with_text("file", get_csv()).build();
@@ -315,8 +1278,8 @@ Jorge;22488012033;;;2023;PB;;57;1;Descrição;0;;Fornecedor;CNPJ-fornecedor;1984
             .first(&mut connection)
             .unwrap();
 
-        assert_eq!(dep1.cpf, "22488012033");
-        assert_eq!(dep2.cpf, "71838787089");
+        assert_eq!(dep1.cpf.as_str(), "22488012033");
+        assert_eq!(dep2.cpf.as_str(), "71838787089");
         assert_eq!(dep1.nome, "Jorge");
         assert_eq!(dep2.nome, "Zé");
     }
@@ -328,6 +1291,7 @@ Jorge;22488012033;;;2023;PB;;57;1;Descrição;0;;Fornecedor;CNPJ-fornecedor;1984
             App::new()
                 .service(import_csv)
                 .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(UploadConfig::default()))
         ).await;
 
         let req =
@@ -340,4 +1304,815 @@ Jorge;22488012033;;;2023;PB;;57;1;Descrição;0;;Fornecedor;CNPJ-fornecedor;1984
         assert!(response.status().is_client_error());
     }
 
+    // Monta um corpo multipart com os campos dados, na forma (nome, tipo de conteúdo, nome do
+    // arquivo, conteúdo).
+    fn build_multipart(fields: &[(&str, &str, &str, &[u8])]) -> ((String, String), Vec<u8>) {
+        let boundary = "knex-test-boundary";
+        let mut body = Vec::new();
+
+        for (name, content_type, file_name, content) in fields {
+            body.extend(format!("--{boundary}\r\n").as_bytes());
+            body.extend(format!("Content-Disposition: form-data; name=\"{name}\"; filename=\"{file_name}\"\r\n").as_bytes());
+            body.extend(format!("Content-Type: {content_type}\r\n\r\n").as_bytes());
+            body.extend(*content);
+            body.extend(b"\r\n");
+        }
+        body.extend(format!("--{boundary}--\r\n").as_bytes());
+
+        (("Content-Type".to_string(), format!("multipart/form-data; boundary={boundary}")), body)
+    }
+
+    fn build_zip(files: &[(&str, &str)]) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[actix_web::test]
+    async fn process_request_with_unknown_field_name() {
+        let pool = build_test_connection_pool().unwrap();
+        let app = test::init_service(
+            App::new()
+                .service(import_csv)
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(UploadConfig::default()))
+        ).await;
+
+        let (header, payload) = MultiPartFormDataBuilder::new().with_text("outro", get_csv()).build();
+
+        let req =
+            test::TestRequest::post()
+            .uri("/processar-ceap")
+            .insert_header(header)
+            .set_payload(payload)
+            .to_request();
+
+        let response = test::call_service(&app, req).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn process_request_with_configured_field_name() {
+        let pool = build_test_connection_pool().unwrap();
+        let config = UploadConfig { field_name: "ceap".to_string(), ..Default::default() };
+        let app = test::init_service(
+            App::new()
+                .service(import_csv)
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(config))
+        ).await;
+
+        let (header, payload) = MultiPartFormDataBuilder::new()
+            .with_text("file", "conteúdo ignorado")
+            .with_text("ceap", get_csv())
+            .build();
+
+        let req =
+            test::TestRequest::post()
+            .uri("/processar-ceap")
+            .insert_header(header)
+            .set_payload(payload)
+            .to_request();
+
+        let response = test::call_service(&app, req).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn process_request_with_unsupported_content_type() {
+        let pool = build_test_connection_pool().unwrap();
+        let app = test::init_service(
+            App::new()
+                .service(import_csv)
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(UploadConfig::default()))
+        ).await;
+
+        let (header, payload) = build_multipart(&[("file", "image/png", "despesas.png", get_csv().as_bytes())]);
+
+        let req =
+            test::TestRequest::post()
+            .uri("/processar-ceap")
+            .insert_header(header)
+            .set_payload(payload)
+            .to_request();
+
+        let response = test::call_service(&app, req).await;
+
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[actix_web::test]
+    async fn process_request_exceeding_max_size() {
+        let pool = build_test_connection_pool().unwrap();
+        let config = UploadConfig { max_size: 64, ..Default::default() };
+        let app = test::init_service(
+            App::new()
+                .service(import_csv)
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(config))
+        ).await;
+
+        let (header, payload) = MultiPartFormDataBuilder::new().with_text("file", get_csv()).build();
+
+        let req =
+            test::TestRequest::post()
+            .uri("/processar-ceap")
+            .insert_header(header)
+            .set_payload(payload)
+            .to_request();
+
+        let response = test::call_service(&app, req).await;
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[actix_web::test]
+    async fn limit_other_fields_in_requests_without_content_length() {
+        let pool = build_test_connection_pool().unwrap();
+        let config = UploadConfig { max_size: 256, ..Default::default() };
+        let app = test::init_service(
+            App::new()
+                .service(import_csv)
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(config))
+        ).await;
+
+        let comentario = "x".repeat(1024);
+        let (content_type, payload) = build_multipart(&[
+            ("comentario", "text/plain", "comentario.txt", comentario.as_bytes()),
+            ("file", "text/csv", "vazio.csv", b""),
+        ]);
+
+        let mut req =
+            test::TestRequest::post()
+            .uri("/processar-ceap")
+            .insert_header(content_type)
+            .set_payload(payload)
+            .to_request();
+        req.headers_mut().remove(header::CONTENT_LENGTH);
+
+        let response = test::call_service(&app, req).await;
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[actix_web::test]
+    async fn process_request_with_multiple_files() {
+        use crate::schema::expenses::dsl::expenses;
+
+        let pool = build_test_connection_pool().unwrap();
+        let app = test::init_service(
+            App::new()
+                .service(import_csv)
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(UploadConfig::default()))
+        ).await;
+
+        let zip = build_zip(&[("2024.csv", get_csv()), ("leiame.txt", "ignorado")]);
+        let (header, payload) = build_multipart(&[
+            ("file", "text/csv", "2025.csv", get_csv().as_bytes()),
+            ("file", "text/csv", "invalido.csv", b"sem;cabecalho\nvalido;aqui"),
+            ("file", "application/zip", "anos.zip", &zip),
+        ]);
+
+        let req =
+            test::TestRequest::post()
+            .uri("/processar-ceap")
+            .insert_header(header)
+            .set_payload(payload)
+            .to_request();
+
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["code"], "import_failed");
+        assert_eq!(body["message"], "1 de 3 arquivos não puderam ser importados.");
+        let results = body["details"].as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["arquivo"], "2025.csv");
+        assert_eq!(results[0]["despesas"], 4);
+        assert_eq!(results[1]["arquivo"], "invalido.csv");
+        assert!(results[1]["erro"].is_string());
+        assert_eq!(results[2]["arquivo"], "anos.zip/2024.csv");
+        assert_eq!(results[2]["despesas"], 4);
+
+        let mut connection = pool.get().unwrap();
+        assert_eq!(expenses.count().get_result(&mut connection), Ok(8));
+    }
+
+    #[actix_web::test]
+    async fn list_expenses_filtered_by_date() {
+        let pool = build_test_connection_pool().unwrap();
+        crate::import::process_csv(&mut pool.get().unwrap(), get_csv().as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(lista_despesas_por_uf)
+                .service(lista_despesas_por_cpf)
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(path_config())
+        ).await;
+
+        let cases = [
+            ("/despesas/uf/PB", 3),
+            ("/despesas/uf/PB?de=2025-02-10&ate=2025-02-28", 3),
+            ("/despesas/uf/PB?data=emissao&de=2025-02-01&ate=2025-02-28", 2),
+            ("/despesas/uf/PB?data=emissao&de=2025-02-08", 0),
+            ("/despesas/uf/PB?data=competencia&ate=2025-01-31", 0),
+            ("/despesas/cpf/22488012033?data=emissao&ate=2025-02-07", 2),
+        ];
+
+        for (uri, expected) in cases {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(body["dados"].as_array().unwrap().len(), expected, "{uri}");
+            assert_eq!(body["total"], expected, "{uri}");
+        }
+
+        for uri in ["/despesas/uf/PB?data=pagamento", "/despesas/uf/XX", "/despesas/cpf/12345678900"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn search_expenses_with_combined_filters() {
+        let pool = build_test_connection_pool().unwrap();
+        let header = get_csv().lines().next().unwrap();
+        let csv = format!("{header}
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;2025-01-10T00:00:00;1467;0;120.50;1;2025;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;PSB;57;1;PASSAGEM AÉREA;0;;TAM LINHAS AEREAS;;1984;0;2025-02-07T00:00:00;1467;0;900;2;2025;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO SHELL;;1984;0;;1467;0;80;3;2025;0;;;0;;;;0;0;
+Zé;71838787089;;;2023;RJ;PT;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;2025-02-20T00:00:00;1467;0;200;2;2025;0;;;0;;;;0;0;");
+        crate::import::process_csv(&mut pool.get().unwrap(), csv.as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(busca_despesas)
+                .app_data(web::Data::new(pool.clone()))
+        ).await;
+
+        let cases: [(&str, &[&str]); 7] = [
+            ("/despesas?categoria=COMBUST%C3%8DVEIS&ordem=valor", &["80.00", "120.50", "200.00"]),
+            ("/despesas?uf=pb&ordem=valor&direcao=desc", &["900.00", "120.50", "80.00"]),
+            ("/despesas?partido=pt", &["200.00"]),
+            ("/despesas?fornecedor=ipiranga&cpf=22488012033", &["120.50"]),
+            ("/despesas?valor_min=100&valor_max=500&ordem=fornecedor", &["120.50", "200.00"]),
+            ("/despesas?emissao_de=2025-02-01&emissao_ate=2025-02-28&ordem=data", &["900.00", "200.00"]),
+            ("/despesas?competencia_de=2025-02-15&uf=PB", &["900.00", "80.00"]),
+        ];
+
+        for (uri, expected) in cases {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+            let valores: Vec<&str> = body["dados"].as_array().unwrap().iter().map(|d| d["valor_liquido"].as_str().unwrap()).collect();
+            assert_eq!(valores, expected, "{uri}");
+        }
+
+        let req = test::TestRequest::get().uri("/despesas?valor_min=muito").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn paginate_expenses_by_cursor() {
+        let pool = build_test_connection_pool().unwrap();
+        let header = get_csv().lines().next().unwrap();
+        let linhas: Vec<String> = (1..=5)
+            .map(|i| format!("Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO {i};;1984;0;2025-02-0{i}T00:00:00;1467;0;{i}0;2;2025;0;;;0;;;;0;0;"))
+            .collect();
+        let csv = format!("{header}\n{}", linhas.join("\n"));
+        crate::import::process_csv(&mut pool.get().unwrap(), csv.as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(busca_despesas)
+                .service(lista_despesas_por_uf)
+                .app_data(web::Data::new(pool.clone()))
+        ).await;
+
+        let valores = |body: &serde_json::Value| -> Vec<String> {
+            body["dados"].as_array().unwrap().iter().map(|d| d["valor_liquido"].as_str().unwrap().to_string()).collect()
+        };
+
+        let req = test::TestRequest::get().uri("/despesas?uf=PB&ordem=valor&page_size=2").to_request();
+        let primeira: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(valores(&primeira), ["10.00", "20.00"]);
+        assert_eq!(primeira["total"], 5);
+        assert_eq!(primeira["page_size"], 2);
+        assert!(primeira["prev"].is_null());
+
+        let req = test::TestRequest::get().uri(primeira["links"]["next"].as_str().unwrap()).to_request();
+        let segunda: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(valores(&segunda), ["30.00", "40.00"]);
+
+        let req = test::TestRequest::get().uri(segunda["links"]["next"].as_str().unwrap()).to_request();
+        let terceira: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(valores(&terceira), ["50.00"]);
+        assert!(terceira["next"].is_null());
+
+        let req = test::TestRequest::get().uri(terceira["links"]["prev"].as_str().unwrap()).to_request();
+        let volta: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(valores(&volta), ["30.00", "40.00"]);
+
+        let req = test::TestRequest::get().uri(volta["links"]["prev"].as_str().unwrap()).to_request();
+        let inicio: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(valores(&inicio), ["10.00", "20.00"]);
+        assert!(inicio["prev"].is_null());
+
+        let req = test::TestRequest::get().uri("/despesas/uf/PB?page_size=1000").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["page_size"], MAX_PAGE_SIZE);
+        assert_eq!(valores(&body), ["10.00", "20.00", "30.00", "40.00", "50.00"]);
+
+        // Cursores inválidos, ou gerados por uma listagem com outra ordenação, são rejeitados.
+        let cursor = primeira["next"].as_str().unwrap();
+        for uri in ["/despesas?cursor=invalido".to_string(), format!("/despesas?ordem=data&cursor={cursor}")] {
+            let req = test::TestRequest::get().uri(&uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn aggregate_expenses_by_dimensions() {
+        let pool = build_test_connection_pool().unwrap();
+        let header = get_csv().lines().next().unwrap();
+        let csv = format!("{header}
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;2025-01-10T00:00:00;1467;0;120.50;1;2025;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;PSB;57;1;PASSAGEM AÉREA;0;;TAM LINHAS AEREAS;;1984;0;2025-02-07T00:00:00;1467;0;900;2;2025;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO SHELL;;1984;0;;1467;0;80;2;2025;0;;;0;;;;0;0;
+Zé;71838787089;;;2023;RJ;PT;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;2025-02-20T00:00:00;1467;0;200;2;2025;0;;;0;;;;0;0;");
+        crate::import::process_csv(&mut pool.get().unwrap(), csv.as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(agrega_despesas)
+                .app_data(web::Data::new(pool.clone()))
+        ).await;
+
+        let req = test::TestRequest::get().uri("/despesas/agregado?group_by=partido,mes&metric=sum,count,avg").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body, serde_json::json!([
+            {"partido": "PSB", "mes": "2025-01", "soma": "120.50", "quantidade": 1, "media": "120.50"},
+            {"partido": "PSB", "mes": "2025-02", "soma": "980.00", "quantidade": 2, "media": "490.00"},
+            {"partido": "PT", "mes": "2025-02", "soma": "200.00", "quantidade": 1, "media": "200.00"},
+        ]));
+
+        let req = test::TestRequest::get().uri("/despesas/agregado?group_by=uf&categoria=COMBUST%C3%8DVEIS&metric=max").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body, serde_json::json!([{"uf": "PB", "maximo": "120.50"}, {"uf": "RJ", "maximo": "200.00"}]));
+
+        let req = test::TestRequest::get().uri("/despesas/agregado?cpf=22488012033").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body, serde_json::json!([{"soma": "1100.50", "quantidade": 3}]));
+
+        let req = test::TestRequest::get().uri("/despesas/agregado?group_by=deputado,ano&uf=RJ&format=csv").to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.headers().get(header::CONTENT_TYPE).unwrap(), "text/csv; charset=utf-8");
+        let body = test::read_body(response).await;
+        assert_eq!(body, "cpf;deputado;ano;soma;quantidade\n***.387.870-**;Zé;2025;200.00;1\n");
+
+        for uri in ["/despesas/agregado?group_by=semana", "/despesas/agregado?metric=median", "/despesas/agregado?format=pdf"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn export_listings_as_csv_and_xlsx() {
+        let pool = build_test_connection_pool().unwrap();
+        let header = get_csv().lines().next().unwrap();
+        let csv = format!("{header}
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;07.575.651/0001-59;1984;0;2025-01-10T00:00:00;1467;0;120.50;1;2025;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;PSB;57;1;PASSAGEM AÉREA;0;;TAM LINHAS AEREAS;;1984;0;2025-02-07T00:00:00;1467;0;900;2;2025;0;;;0;;;;0;0;
+Zé;71838787089;;;2023;RJ;PT;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;07.575.651/0001-59;1984;0;2025-02-20T00:00:00;1467;0;200;2;2025;0;;;0;;;;0;0;");
+        crate::import::process_csv(&mut pool.get().unwrap(), csv.as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(busca_despesas)
+                .service(lista_despesas_por_uf)
+                .service(agrega_despesas)
+                .service(lista_deputados)
+                .service(lista_fornecedores)
+                .app_data(web::Data::new(pool.clone()))
+        ).await;
+
+        let req = test::TestRequest::get().uri("/despesas?uf=PB&ordem=valor&direcao=desc&format=csv").to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(header::CONTENT_TYPE).unwrap(), "text/csv; charset=utf-8");
+        assert_eq!(response.headers().get(header::CONTENT_DISPOSITION).unwrap(), "attachment; filename=\"despesas.csv\"");
+        let body = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+        let linhas: Vec<&str> = body.lines().collect();
+        assert_eq!(linhas[0], "txNomeParlamentar;cpf;sgUF;sgPartido;txtDescricao;txtFornecedor;txtCNPJCPF;txtFornecedorCanonico;datEmissao;vlrLiquido;numMes;numAno;urlDocumento");
+        assert_eq!(linhas.len(), 3);
+        assert!(linhas[1].starts_with("Jorge;***.880.120-**;PB;PSB;PASSAGEM AÉREA;"), "{}", linhas[1]);
+        assert!(linhas[1].contains(";900.00;2;2025;"), "{}", linhas[1]);
+        assert!(linhas[2].contains(";07575651000159;"), "{}", linhas[2]);
+
+        let req = test::TestRequest::get().uri("/despesas/uf/RJ").insert_header((header::ACCEPT, "text/csv")).to_request();
+        let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+        assert_eq!(body.lines().count(), 2);
+        assert!(body.lines().nth(1).unwrap().starts_with("Zé;***.387.870-**;RJ;PT;"));
+
+        for uri in ["/despesas?format=xlsx", "/despesas/agregado?group_by=uf&format=xlsx", "/deputados?format=xlsx", "/fornecedores?format=xlsx"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let response = test::call_service(&app, req).await;
+            assert_eq!(response.status(), StatusCode::OK, "{uri}");
+            assert_eq!(response.headers().get(header::CONTENT_TYPE).unwrap(), XLSX_MIME, "{uri}");
+            assert!(test::read_body(response).await.starts_with(b"PK"), "{uri}");
+        }
+
+        let req = test::TestRequest::get().uri("/deputados?uf=RJ&format=csv").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert_eq!(body, "id;txNomeParlamentar;cpf;sgUF;sgPartido;vlrTotal\n".to_string()
+            + &format!("{};Zé;***.387.870-**;RJ;PT;200.00\n", Deputado::find(&mut pool.get().unwrap(), "71838787089").unwrap().id));
+
+        let req = test::TestRequest::get().uri("/fornecedores").insert_header((header::ACCEPT, "text/csv")).to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert_eq!(body, "txtCNPJCPF;txtFornecedor;vlrTotal;qtdDespesas;qtdDeputados\n07575651000159;POSTO IPIRANGA;320.50;2;2\n");
+
+        let req = test::TestRequest::get().uri("/despesas?format=pdf").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn reject_xlsx_exports_over_the_row_limit() {
+        let pool = build_test_connection_pool().unwrap();
+        let connection = &mut pool.get().unwrap();
+        crate::import::process_csv(connection, get_csv().as_bytes()).unwrap();
+
+        let (filtro, periodo) = (ExpenseFilter::default(), DateFilter::default());
+        let total = linhas_xlsx(connection, &filtro, &periodo, Privacidade::Mascarada, usize::MAX).unwrap().len();
+        assert!(total > 1);
+        assert_eq!(linhas_xlsx(connection, &filtro, &periodo, Privacidade::Mascarada, total).unwrap().len(), total);
+
+        let erro = linhas_xlsx(connection, &filtro, &periodo, Privacidade::Mascarada, total - 1).unwrap_err();
+        assert!(matches!(erro, ApiError::BadRequest(_)), "{erro:?}");
+    }
+
+    #[actix_web::test]
+    async fn stream_expenses_as_ndjson() {
+        let pool = build_test_connection_pool().unwrap();
+        let header = get_csv().lines().next().unwrap();
+        let csv = format!("{header}
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;2025-01-10T00:00:00;1467;0;120.50;1;2025;0;;;0;;;;0;0;
+Zé;71838787089;;;2023;RJ;PT;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;2025-02-20T00:00:00;1467;0;200;2;2025;0;;;0;;;;0;0;");
+        let mut connection = pool.get().unwrap();
+        crate::import::process_csv(&mut connection, csv.as_bytes()).unwrap();
+        // Despesas suficientes para que a listagem seja lida em vários lotes.
+        diesel::sql_query("INSERT INTO expenses (fornecedor, valor_liquido, deputado_id, competencia, categoria)
+            SELECT 'POSTO ' || n, n, deputados.id, '2025-03-01', 'COMBUSTÍVEIS'
+            FROM generate_series(1, 2500) n, deputados WHERE deputados.cpf = '22488012033'")
+            .execute(&mut connection)
+            .unwrap();
+        drop(connection);
+
+        let app = test::init_service(
+            App::new()
+                .service(busca_despesas)
+                .service(lista_despesas_por_uf)
+                .service(lista_despesas_por_cpf)
+                .service(agrega_despesas)
+                .app_data(web::Data::new(pool.clone()))
+        ).await;
+
+        let req = test::TestRequest::get().uri("/despesas/uf/PB?format=ndjson").to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(header::CONTENT_TYPE).unwrap(), "application/x-ndjson");
+        let body = test::read_body(response).await;
+        let despesas: Vec<serde_json::Value> = body.split(|byte| *byte == b'\n')
+            .filter(|linha| !linha.is_empty())
+            .map(|linha| serde_json::from_slice(linha).unwrap())
+            .collect();
+        assert_eq!(despesas.len(), 2501);
+        assert_eq!(despesas[0]["fornecedor"], "POSTO IPIRANGA");
+        assert_eq!(despesas[0]["cpf"], "***.880.120-**");
+        let ids: Vec<i64> = despesas[1..].iter().map(|despesa| despesa["expense_id"].as_i64().unwrap()).collect();
+        assert!(ids.windows(2).all(|par| par[0] < par[1]));
+
+        let req = test::TestRequest::get().uri("/despesas?uf=RJ").insert_header((header::ACCEPT, "application/x-ndjson")).to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert_eq!(body.iter().filter(|byte| **byte == b'\n').count(), 1);
+        let despesa: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(despesa["valor_liquido"], "200.00");
+
+        let req = test::TestRequest::get().uri("/despesas/cpf/71838787089?format=ndjson").to_request();
+        let despesa: serde_json::Value = serde_json::from_slice(&test::call_and_read_body(&app, req).await).unwrap();
+        assert_eq!(despesa["fornecedor"], "POSTO IPIRANGA");
+        assert!(despesa.get("cpf").is_none());
+
+        let req = test::TestRequest::get().uri("/despesas/agregado?group_by=uf&format=ndjson").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert_eq!(body, "{\"uf\":\"PB\",\"soma\":\"3126370.50\",\"quantidade\":2501}\n{\"uf\":\"RJ\",\"soma\":\"200.00\",\"quantidade\":1}\n");
+    }
+
+    #[actix_web::test]
+    async fn rank_deputies_and_suppliers() {
+        let pool = build_test_connection_pool().unwrap();
+        let header = get_csv().lines().next().unwrap();
+        let csv = format!("{header}
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;;1467;0;100;6;2031;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;PSB;57;1;PASSAGEM AÉREA;0;;TAM;;1984;0;;1467;0;300;7;2032;0;;;0;;;;0;0;
+Zé;71838787089;;;2023;RJ;PT;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;;1467;0;200;3;2032;0;;;0;;;;0;0;
+Zé;71838787089;;;2023;RJ;PT;57;1;COMBUSTÍVEIS;0;;POSTO SHELL;;1984;0;;1467;0;500;4;2032;0;;;0;;;;0;0;");
+        crate::import::process_csv(&mut pool.get().unwrap(), csv.as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(ranking_deputados)
+                .service(ranking_fornecedores)
+                .app_data(web::Data::new(pool.clone()))
+        ).await;
+
+        let req = test::TestRequest::get().uri("/ranking/deputados?ano=2032").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["periodo"], serde_json::json!({"de": "2032-01", "ate": "2032-12"}));
+        assert_eq!(body["periodo_anterior"], serde_json::json!({"de": "2031-01", "ate": "2031-12"}));
+        assert_eq!(body["total"], "1000.00");
+        let itens = body["itens"].as_array().unwrap();
+        assert_eq!(itens.len(), 2);
+        assert_eq!(itens[0]["posicao"], 1);
+        assert_eq!(itens[0]["cpf"], "***.387.870-**");
+        assert_eq!(itens[0]["valor"], "700.00");
+        assert_eq!(itens[0]["participacao"], 70.0);
+        assert_eq!(itens[0]["anterior"], serde_json::json!({"valor": "0.00", "variacao": "700.00", "percentual": null}));
+        assert_eq!(itens[1]["posicao"], 2);
+        assert_eq!(itens[1]["nome"], "Jorge");
+        assert_eq!(itens[1]["anterior"], serde_json::json!({"valor": "100.00", "variacao": "200.00", "percentual": 200.0}));
+
+        let req = test::TestRequest::get().uri("/ranking/deputados?ano=2032&categoria=COMBUST%C3%8DVEIS&uf=pb").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["itens"].as_array().unwrap().len(), 0);
+
+        let req = test::TestRequest::get().uri("/ranking/fornecedores?de=2032-01-01&ate=2032-06-30&limite=1").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["total"], "700.00");
+        assert_eq!(body["periodo_anterior"], serde_json::json!({"de": "2031-07", "ate": "2031-12"}));
+        let itens = body["itens"].as_array().unwrap();
+        assert_eq!(itens.len(), 1);
+        assert_eq!(itens[0]["fornecedor"], "POSTO SHELL");
+        assert_eq!(itens[0]["participacao"], 71.43);
+
+        let req = test::TestRequest::get().uri("/ranking/fornecedores").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert!(body["periodo"].is_null());
+        assert!(body["itens"][0]["anterior"].is_null());
+
+        let req = test::TestRequest::get().uri("/ranking/deputados?ano=2032&de=2032-01-01").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn follow_expenses_from_the_supplier_side() {
+        let pool = build_test_connection_pool().unwrap();
+        let header = get_csv().lines().next().unwrap();
+        let csv = format!("{header}
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO XYZQ;07.575.651/0001-59;1984;0;2025-01-10T00:00:00;1467;0;120.50;1;2025;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;PSB;57;1;MANUTENÇÃO;0;;POSTO XYZQ LTDA;07575651000159;1984;0;2024-03-07T00:00:00;1467;0;30;3;2024;0;;;0;;;;0;0;
+Zé;71838787089;;;2023;RJ;PT;57;1;COMBUSTÍVEIS;0;;POSTO XYZQ;07.575.651/0001-59;1984;0;2025-02-20T00:00:00;1467;0;200;2;2025;0;;;0;;;;0;0;
+Zé;71838787089;;;2023;RJ;PT;57;1;COMBUSTÍVEIS;0;;OUTRO POSTO;;1984;0;2025-02-20T00:00:00;1467;0;10;2;2025;0;;;0;;;;0;0;");
+        crate::import::process_csv(&mut pool.get().unwrap(), csv.as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(lista_fornecedores)
+                .service(perfil_fornecedor)
+                .service(clientes_fornecedor)
+                .app_data(path_config())
+                .app_data(web::Data::new(pool.clone()))
+        ).await;
+
+        for uri in ["/fornecedores?q=xyzq", "/fornecedores?q=0757565"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(body["total"], 1, "{uri}");
+            assert_eq!(body["dados"][0]["documento"], "07575651000159");
+            assert_eq!(body["dados"][0]["nome"], "POSTO XYZQ");
+            assert_eq!(body["dados"][0]["total"], "350.50");
+            assert_eq!(body["dados"][0]["despesas"], 3);
+            assert_eq!(body["dados"][0]["deputados"], 2);
+        }
+
+        let req = test::TestRequest::get().uri("/fornecedores/07.575.651-0001-59").to_request();
+        let perfil: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(perfil["nomes"], serde_json::json!(["POSTO XYZQ", "POSTO XYZQ LTDA"]));
+        assert_eq!(perfil["por_ano"], serde_json::json!([
+            {"ano": 2024, "total": "30.00", "despesas": 1},
+            {"ano": 2025, "total": "320.50", "despesas": 2},
+        ]));
+        assert_eq!(perfil["por_categoria"][0], serde_json::json!({"categoria": "COMBUSTÍVEIS", "total": "320.50", "despesas": 2}));
+
+        let req = test::TestRequest::get().uri("/fornecedores/07575651000159/deputados").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body[0]["nome"], "Zé");
+        assert_eq!(body[0]["total"], "200.00");
+        assert_eq!(body[1]["cpf"], "***.880.120-**");
+        assert_eq!(body[1]["despesas"], 2);
+
+        let req = test::TestRequest::get().uri("/fornecedores/11222333000181/deputados").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+        let req = test::TestRequest::get().uri("/fornecedores/11222333000181").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+        let req = test::TestRequest::get().uri("/fornecedores/123").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+        // O nome do fornecedor é o nome canônico do grupo ao qual o documento pertence.
+        {
+            use diesel::RunQueryDsl;
+            use crate::schema::grupos_fornecedores;
+
+            diesel::insert_into(grupos_fornecedores::table)
+                .values((
+                    grupos_fornecedores::nome.eq("XYZQ COMÉRCIO DE COMBUSTÍVEIS"),
+                    grupos_fornecedores::documento.eq("07575651000159"),
+                    grupos_fornecedores::confianca.eq(1.0),
+                ))
+                .execute(&mut pool.get().unwrap())
+                .unwrap();
+        }
+
+        let req = test::TestRequest::get().uri("/fornecedores/07575651000159").to_request();
+        let perfil: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(perfil["nome"], "XYZQ COMÉRCIO DE COMBUSTÍVEIS");
+    }
+
+    #[actix_web::test]
+    async fn get_deputy_profile_by_id_or_cpf() {
+        let pool = build_test_connection_pool().unwrap();
+        let header = get_csv().lines().next().unwrap();
+        let csv = format!("{header}
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;2025-01-10T00:00:00;1467;0;120.50;1;2025;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;PSB;57;1;PASSAGEM AÉREA;0;;TAM;;1984;0;2024-03-07T00:00:00;1467;0;900;3;2024;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;;1467;0;80;2;2025;0;;;0;;;;0;0;
+Zé;71838787089;;;2023;RJ;PT;57;1;COMBUSTÍVEIS;0;;POSTO SHELL;;1984;0;2025-02-20T00:00:00;1467;0;200;2;2025;0;;;0;;;;0;0;");
+        crate::import::process_csv(&mut pool.get().unwrap(), csv.as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(perfil_deputado)
+                .app_data(web::Data::new(pool.clone()))
+        ).await;
+
+        let req = test::TestRequest::get().uri("/deputados/22488012033").to_request();
+        let perfil: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(perfil["nome"], "Jorge");
+        assert_eq!(perfil["partido"], "PSB");
+        assert_eq!(perfil["total"], "1100.50");
+        assert_eq!(perfil["despesas"], 3);
+        assert_eq!(perfil["primeira_despesa"], "2024-03-07T00:00:00");
+        assert_eq!(perfil["ultima_despesa"], "2025-02-01T00:00:00");
+        assert_eq!(perfil["por_ano"], serde_json::json!([
+            {"ano": 2024, "total": "900.00", "despesas": 1},
+            {"ano": 2025, "total": "200.50", "despesas": 2},
+        ]));
+        assert_eq!(perfil["por_categoria"][0]["categoria"], "PASSAGEM AÉREA");
+        assert_eq!(perfil["principais_fornecedores"], serde_json::json!([
+            {"fornecedor": "TAM", "total": "900.00", "despesas": 1},
+            {"fornecedor": "POSTO IPIRANGA", "total": "200.50", "despesas": 2},
+        ]));
+
+        let req = test::TestRequest::get().uri(&format!("/deputados/{}", perfil["id"])).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["cpf"], "***.880.120-**");
+
+        let req = test::TestRequest::get().uri("/deputados/52998224725").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn list_deputies_with_filters_and_totals() {
+        let pool = build_test_connection_pool().unwrap();
+        let header = get_csv().lines().next().unwrap();
+        let csv = format!("{header}
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;;1467;0;120.50;1;2025;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;;1467;0;80;2;2025;0;;;0;;;;0;0;
+Zé;71838787089;;;2023;RJ;PT;57;1;COMBUSTÍVEIS;0;;POSTO SHELL;;1984;0;;1467;0;500;2;2025;0;;;0;;;;0;0;
+Ângela;52998224725;;;2023;PB;PT;57;1;COMBUSTÍVEIS;0;;POSTO SHELL;;1984;0;;1467;0;10;2;2025;0;;;0;;;;0;0;");
+        crate::import::process_csv(&mut pool.get().unwrap(), csv.as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(lista_deputados)
+                .app_data(web::Data::new(pool.clone()))
+        ).await;
+
+        let nomes = |body: &serde_json::Value| -> Vec<String> {
+            body["dados"].as_array().unwrap().iter().map(|d| d["nome"].as_str().unwrap().to_string()).collect()
+        };
+
+        let req = test::TestRequest::get().uri("/deputados?uf=pb&ordem=total&direcao=desc").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(nomes(&body), ["Jorge", "Ângela"]);
+        assert_eq!(body["total"], 2);
+        assert_eq!(body["dados"][0]["total"], "200.50");
+
+        let req = test::TestRequest::get().uri("/deputados?nome=ang").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(nomes(&body), ["Ângela"]);
+
+        let req = test::TestRequest::get().uri("/deputados?partido=pt&ordem=total&direcao=desc").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(nomes(&body), ["Zé", "Ângela"]);
+
+        let req = test::TestRequest::get().uri("/deputados?ordem=total&direcao=desc&page=2&page_size=1").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(nomes(&body), ["Jorge"]);
+        assert_eq!(body["total"], 3);
+        assert_eq!(body["page"], 2);
+
+        let req = test::TestRequest::get().uri("/deputados?uf=PBA").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn sum_expenses_from_rollups() {
+        let pool = build_test_connection_pool().unwrap();
+        crate::import::process_csv(&mut pool.get().unwrap(), get_csv().as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(soma_despesas)
+                .app_data(web::Data::new(pool.clone()))
+        ).await;
+
+        let req = test::TestRequest::get().uri("/despesas/cpf/22488012033/soma").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["soma"], "4401.00");
+        assert!(body["atualizado_em"].is_string());
+
+        let req = test::TestRequest::get().uri("/despesas/cpf/52998224725/soma").to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body, serde_json::json!({"code": "not_found", "message": "Deputado não encontrado."}));
+    }
+
+    #[actix_web::test]
+    async fn sum_of_no_expenses_is_zero() {
+        use diesel::RunQueryDsl;
+
+        let pool = build_test_connection_pool().unwrap();
+        diesel::delete(crate::schema::resumo_despesas::table).execute(&mut pool.get().unwrap()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(soma_todas_as_despesas)
+                .app_data(web::Data::new(pool.clone()))
+        ).await;
+
+        let req = test::TestRequest::get().uri("/despesas/soma").to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["soma"], "0.00");
+    }
+
+    #[actix_web::test]
+    async fn search_tolerates_accents_and_typos() {
+        let pool = build_test_connection_pool().unwrap();
+        let header = get_csv().lines().next().unwrap();
+        let csv = format!("{header}
+João Conceição;22488012033;;;2023;PB;;57;1;Descrição;0;;TAM LINHAS AÉREAS S/A;;1984;0;2025-02-07T00:00:00;1467;0;100;2;2025;0;;;0;;;;0;0;
+João Conceição;22488012033;;;2023;PB;;57;1;Descrição;0;;TAM LINHAS AÉREAS S/A;;1984;0;2025-02-07T00:00:00;1467;0;50.5;2;2025;0;;;0;;;;0;0;
+Zé;71838787089;;;2023;RJ;;57;1;Descrição;0;;POSTO IPIRANGA;;1984;0;2025-02-07T00:00:00;1467;0;10;3;2025;0;;;0;;;;0;0;");
+        crate::import::process_csv(&mut pool.get().unwrap(), csv.as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(busca)
+                .app_data(web::Data::new(pool.clone()))
+        ).await;
+
+        let req = test::TestRequest::get().uri("/busca?q=linhas%20aereas").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let fornecedores = body["fornecedores"].as_array().unwrap();
+        assert_eq!(fornecedores.len(), 1);
+        assert_eq!(fornecedores[0]["nome"], "TAM LINHAS AÉREAS S/A");
+        assert_eq!(fornecedores[0]["destaque"], "TAM <mark>LINHAS</mark> <mark>AÉREAS</mark> S/A");
+        assert_eq!(fornecedores[0]["despesas"], 2);
+        assert_eq!(fornecedores[0]["total"], "150.50");
+
+        let req = test::TestRequest::get().uri("/busca?q=conceicao").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["deputados"][0]["nome"], "João Conceição");
+        assert_eq!(body["deputados"][0]["destaque"], "João <mark>Conceição</mark>");
+
+        let req = test::TestRequest::get().uri("/busca?q=ipiranaga").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["fornecedores"][0]["nome"], "POSTO IPIRANGA");
+
+        let req = test::TestRequest::get().uri("/busca?q=a").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
 }
@@ -0,0 +1,379 @@
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use diesel::{r2d2::ConnectionManager, PgConnection};
+use r2d2::Pool;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{auth::{bearer_token, token_valido}, config::AdminConfig, error::{ApiError, ErrorBody}, models::{Expense, GrupoComVariantes, GrupoFornecedor}, resolution::{resolve_suppliers, ResolutionSummary}, versioning::Versao};
+
+use super::{resultado, PageArgs};
+
+// Verifica se a requisição traz o token administrativo configurado.
+fn autoriza(req: &HttpRequest, config: &AdminConfig) -> Result<(), ApiError> {
+    let token = match &config.token {
+        Some(token) => token,
+        None => return Err(ApiError::Forbidden("admin endpoints are disabled".to_string())),
+    };
+
+    if bearer_token(req.headers()).is_some_and(|informado| token_valido(informado, token)) {
+        Ok(())
+    } else {
+        Err(ApiError::Unauthorized("invalid admin token".to_string()))
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GroupSearchArgs {
+    q: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct MergeArgs {
+    grupos: Vec<i32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct SplitArgs {
+    fornecedores: Vec<String>,
+    nome: Option<String>,
+}
+
+// Executa a resolução de fornecedores, reagrupando as variantes dos nomes dos fornecedores.
+#[utoipa::path(
+    post, path = "/admin/fornecedores/resolver", tag = "admin",
+    summary = "Resolução dos fornecedores",
+    security(("admin" = [])),
+    responses(
+        (status = 200, description = "Número de grupos e de variantes resultantes.", body = ResolutionSummary),
+        (status = 401, description = "Token administrativo inválido.", body = ErrorBody),
+        (status = 403, description = "Endpoints administrativos desabilitados.", body = ErrorBody),
+    ),
+)]
+#[post("/admin/fornecedores/resolver")]
+pub async fn resolve_fornecedores(
+    req: HttpRequest,
+    config: web::Data<AdminConfig>,
+    versao: Versao,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        autoriza(&req, &config)?;
+
+        let result = web::block(move || {
+            let connection = &mut pool.get().with_context(|| "database error")?;
+
+            resolve_suppliers(connection)
+        })
+        .await??;
+
+        versao.json(&result)
+}
+
+// Lista os grupos de fornecedores e suas variantes, opcionalmente filtrando-os pelo nome.
+#[utoipa::path(
+    get, path = "/admin/fornecedores/grupos", tag = "admin",
+    summary = "Listagem dos grupos de fornecedores",
+    security(("admin" = [])),
+    params(GroupSearchArgs, PageArgs),
+    responses(
+        (status = 200, description = "Grupos e suas variantes, vinte por página.", body = Vec<GrupoComVariantes>),
+        (status = 401, description = "Token administrativo inválido.", body = ErrorBody),
+        (status = 403, description = "Endpoints administrativos desabilitados.", body = ErrorBody),
+    ),
+)]
+#[get("/admin/fornecedores/grupos")]
+pub async fn lista_grupos_fornecedores(
+    req: HttpRequest,
+    args: web::Query<GroupSearchArgs>,
+    page: web::Query<PageArgs>,
+    config: web::Data<AdminConfig>,
+    versao: Versao,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        autoriza(&req, &config)?;
+        let page = page.page.unwrap_or(1);
+
+        let result = web::block(move || {
+            let connection = &mut pool.get().with_context(|| "database error")?;
+
+            GrupoFornecedor::list(connection, args.q.as_deref(), page).with_context(|| "database error")
+        })
+        .await??;
+
+        versao.json(&result)
+}
+
+// Une os grupos informados no corpo da requisição ao grupo da URL.
+#[utoipa::path(
+    post, path = "/admin/fornecedores/grupos/{id}/unir", tag = "admin",
+    summary = "União de grupos de fornecedores",
+    security(("admin" = [])),
+    params(("id" = i32, Path, description = "Grupo ao qual os demais são unidos.")),
+    request_body = MergeArgs,
+    responses(
+        (status = 200, description = "Grupo resultante.", body = GrupoComVariantes),
+        (status = 401, description = "Token administrativo inválido.", body = ErrorBody),
+        (status = 403, description = "Endpoints administrativos desabilitados.", body = ErrorBody),
+        (status = 404, description = "Grupo não encontrado.", body = ErrorBody),
+    ),
+)]
+#[post("/admin/fornecedores/grupos/{id}/unir")]
+pub async fn une_grupos_fornecedores(
+    req: HttpRequest,
+    id: web::Path<i32>,
+    args: web::Json<MergeArgs>,
+    config: web::Data<AdminConfig>,
+    versao: Versao,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        autoriza(&req, &config)?;
+        let id = id.into_inner();
+
+        let result = web::block(move || {
+            let connection = &mut pool.get().with_context(|| "database error")?;
+
+            Ok(GrupoFornecedor::merge(connection, id, &args.grupos)?)
+        })
+        .await?;
+
+        versao.json(&resultado(result, "supplier group not found")?)
+}
+
+// Separa as variantes informadas no corpo da requisição do grupo da URL, criando um novo grupo.
+#[utoipa::path(
+    post, path = "/admin/fornecedores/grupos/{id}/separar", tag = "admin",
+    summary = "Separação de variantes de um grupo de fornecedores",
+    security(("admin" = [])),
+    params(("id" = i32, Path, description = "Grupo do qual as variantes são separadas.")),
+    request_body = SplitArgs,
+    responses(
+        (status = 201, description = "Novo grupo, com as variantes separadas.", body = GrupoComVariantes),
+        (status = 401, description = "Token administrativo inválido.", body = ErrorBody),
+        (status = 403, description = "Endpoints administrativos desabilitados.", body = ErrorBody),
+        (status = 404, description = "Grupo não encontrado.", body = ErrorBody),
+    ),
+)]
+#[post("/admin/fornecedores/grupos/{id}/separar")]
+pub async fn separa_grupo_fornecedores(
+    req: HttpRequest,
+    id: web::Path<i32>,
+    args: web::Json<SplitArgs>,
+    config: web::Data<AdminConfig>,
+    versao: Versao,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        autoriza(&req, &config)?;
+        let id = id.into_inner();
+        let SplitArgs { fornecedores, nome } = args.into_inner();
+
+        let result = web::block(move || {
+            let connection = &mut pool.get().with_context(|| "database error")?;
+
+            Ok(GrupoFornecedor::split(connection, id, &fornecedores, nome)?)
+        })
+        .await?;
+
+        versao.responde(HttpResponse::Created(), &resultado(result, "supplier group not found")?)
+}
+
+// Resultado da remoção das despesas de um ano.
+#[derive(Serialize, ToSchema)]
+struct PurgeResult {
+    ano: i32,
+    despesas: i64,
+}
+
+// Remove todas as despesas com competência no ano da URL, descartando a partição do ano.
+#[utoipa::path(
+    delete, path = "/admin/despesas/{ano}", tag = "admin",
+    summary = "Remoção das despesas de um ano",
+    security(("admin" = [])),
+    params(("ano" = i32, Path, description = "Ano da competência das despesas.")),
+    responses(
+        (status = 200, description = "Número de despesas removidas.", body = PurgeResult),
+        (status = 401, description = "Token administrativo inválido.", body = ErrorBody),
+        (status = 403, description = "Endpoints administrativos desabilitados.", body = ErrorBody),
+        (status = 404, description = "Não há despesas no ano.", body = ErrorBody),
+    ),
+)]
+#[delete("/admin/despesas/{ano}")]
+pub async fn remove_despesas_do_ano(
+    req: HttpRequest,
+    ano: web::Path<i32>,
+    config: web::Data<AdminConfig>,
+    versao: Versao,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+        autoriza(&req, &config)?;
+        let ano = ano.into_inner();
+
+        let result = web::block(move || {
+            let connection = &mut pool.get().with_context(|| "database error")?;
+
+            Ok(Expense::purge_year(connection, ano)?)
+        })
+        .await?;
+
+        let despesas = resultado(result, "no expenses for this year")?;
+        versao.json(&PurgeResult { ano, despesas })
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App};
+
+    use super::*;
+    use crate::routes::tests::build_test_connection_pool;
+
+    fn config() -> AdminConfig {
+        AdminConfig { token: Some("segredo".to_string()) }
+    }
+
+    fn get_csv() -> &'static str {
+        "txNomeParlamentar;cpf;ideCadastro;nuCarteiraParlamentar;nuLegislatura;sgUF;sgPartido;codLegislatura;numSubCota;txtDescricao;numEspecificacaoSubCota;txtDescricaoEspecificacao;txtFornecedor;txtCNPJCPF;txtNumero;indTipoDocumento;datEmissao;vlrDocumento;vlrGlosa;vlrLiquido;numMes;numAno;numParcela;txtPassageiro;txtTrecho;numLote;numRessarcimento;datPagamentoRestituicao;vlrRestituicao;nuDeputadoId;ideDocumento;urlDocumento
+Jorge;22488012033;;;2023;PB;;57;1;Descrição;0;;TAM LINHAS AEREAS S/A;02.012.862/0001-60;1984;0;2025-02-07T00:00:00;1467;0;100;2;2025;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;;57;1;Descrição;0;;TAM LINHAS AEREAS S/A;02.012.862/0001-60;1984;0;2025-02-07T00:00:00;1467;0;100;2;2025;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;;57;1;Descrição;0;;Tam Linhas Aéreas;02.012.862/0001-60;1984;0;2025-02-07T00:00:00;1467;0;50;2;2025;0;;;0;;;;0;0;
+Zé;71838787089;;;2023;RJ;;57;1;Descrição;0;;LATAM AIRLINES;;1984;0;2025-02-07T00:00:00;1467;0;25;3;2025;0;;;0;;;;0;0;"
+    }
+
+    #[actix_web::test]
+    async fn exige_token_administrativo() {
+        let app = test::init_service(
+            App::new()
+                .service(resolve_fornecedores)
+                .app_data(web::Data::new(build_test_connection_pool().unwrap()))
+                .app_data(web::Data::new(config()))
+        ).await;
+
+        let req = test::TestRequest::post().uri("/admin/fornecedores/resolver").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::post()
+            .uri("/admin/fornecedores/resolver")
+            .insert_header(("Authorization", "Bearer errado"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+        let app = test::init_service(
+            App::new()
+                .service(resolve_fornecedores)
+                .app_data(web::Data::new(build_test_connection_pool().unwrap()))
+                .app_data(web::Data::new(AdminConfig::default()))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/fornecedores/resolver")
+            .insert_header(("Authorization", "Bearer segredo"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn resolve_une_e_separa_fornecedores() {
+        let pool = build_test_connection_pool().unwrap();
+        crate::import::process_csv(&mut pool.get().unwrap(), get_csv().as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(resolve_fornecedores)
+                .service(lista_grupos_fornecedores)
+                .service(une_grupos_fornecedores)
+                .service(separa_grupo_fornecedores)
+                .service(crate::routes::busca)
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(config()))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/fornecedores/resolver")
+            .insert_header(("Authorization", "Bearer segredo"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["variantes"], 3);
+        assert_eq!(body["grupos"], 2);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/fornecedores/grupos?q=tam")
+            .insert_header(("Authorization", "Bearer segredo"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let grupos = body.as_array().unwrap();
+        assert_eq!(grupos.len(), 2);
+        let tam = grupos.iter().find(|g| g["nome"] == "TAM LINHAS AEREAS S/A").unwrap();
+        let latam = grupos.iter().find(|g| g["nome"] == "LATAM AIRLINES").unwrap();
+        assert_eq!(tam["documento"], "02012862000160");
+        assert_eq!(tam["variantes"].as_array().unwrap().len(), 2);
+
+        // As agregações por fornecedor usam o nome canônico do grupo.
+        let req = test::TestRequest::get().uri("/busca?q=tam%20linhas").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["fornecedores"].as_array().unwrap().len(), 1);
+        assert_eq!(body["fornecedores"][0]["total"], "250.00");
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/admin/fornecedores/grupos/{}/unir", tam["id"]))
+            .insert_header(("Authorization", "Bearer segredo"))
+            .set_json(serde_json::json!({ "grupos": [latam["id"]] }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["variantes"].as_array().unwrap().len(), 3);
+        assert_eq!(body["manual"], true);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/admin/fornecedores/grupos/{}/separar", tam["id"]))
+            .insert_header(("Authorization", "Bearer segredo"))
+            .set_json(serde_json::json!({ "fornecedores": ["LATAM AIRLINES"] }))
+            .to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["nome"], "LATAM AIRLINES");
+
+        // Os grupos manuais são preservados por uma nova resolução.
+        let req = test::TestRequest::post()
+            .uri("/admin/fornecedores/resolver")
+            .insert_header(("Authorization", "Bearer segredo"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["variantes"], 0);
+
+        let req = test::TestRequest::post()
+            .uri("/admin/fornecedores/grupos/0/unir")
+            .insert_header(("Authorization", "Bearer segredo"))
+            .set_json(serde_json::json!({ "grupos": [] }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn remove_despesas_do_ano_descartando_a_particao() {
+        let pool = build_test_connection_pool().unwrap();
+        let header = get_csv().lines().next().unwrap();
+        let csv = format!("{header}
+Ana;11144477735;;;2010;SP;;57;1;Descrição;0;;Fornecedor;;1984;0;;10;0;10;3;2010;0;;;0;;;;0;0;
+Ana;11144477735;;;2010;SP;;57;1;Descrição;0;;Fornecedor;;1984;0;;10;0;15;4;2010;0;;;0;;;;0;0;");
+        crate::import::process_csv(&mut pool.get().unwrap(), csv.as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(remove_despesas_do_ano)
+                .service(crate::routes::soma_despesas)
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(config()))
+        ).await;
+
+        let req = test::TestRequest::delete()
+            .uri("/admin/despesas/2010")
+            .insert_header(("Authorization", "Bearer segredo"))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["ano"], 2010);
+        assert_eq!(body["despesas"], 2);
+
+        let req = test::TestRequest::get().uri("/despesas/cpf/11144477735/soma").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::delete()
+            .uri("/admin/despesas/2010")
+            .insert_header(("Authorization", "Bearer segredo"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+}
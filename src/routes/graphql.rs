@@ -0,0 +1,215 @@
+use actix_web::{get, post, web, HttpResponse};
+use async_graphql::http::GraphiQLSource;
+use diesel::{r2d2::ConnectionManager, PgConnection};
+use r2d2::Pool;
+
+use crate::{graphql::{self, CeapSchema}, privacy::Privacidade};
+
+// Executa uma consulta GraphQL, recebida em JSON no formato usual: {"query": "...",
+// "variables": {...}}. Como em qualquer servidor GraphQL, os erros da consulta são retornados em
+// `errors`, com status 200; cada erro traz em extensions.code o mesmo código das respostas de erro
+// dos demais endpoints.
+#[utoipa::path(
+    post, path = "/graphql", tag = "graphql",
+    summary = "Consulta GraphQL",
+    request_body(content = Object, description = "Consulta, em query, e as suas variáveis, em variables."),
+    responses(
+        (status = 200, description = "Resultado da consulta, em data, e os erros, em errors.", body = Object),
+        (status = 400, description = "Corpo da requisição inválido.", body = crate::error::ErrorBody),
+    ),
+)]
+#[post("/graphql")]
+pub async fn consulta_graphql(
+    requisicao: web::Json<async_graphql::Request>,
+    schema: web::Data<CeapSchema>,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>,
+    privacidade: Privacidade) -> HttpResponse {
+        let resposta = graphql::execute(&schema, pool.get_ref().clone(), privacidade, requisicao.into_inner()).await;
+
+        HttpResponse::Ok().json(resposta)
+}
+
+// Interface interativa (GraphiQL) para explorar o esquema e executar consultas.
+#[utoipa::path(
+    get, path = "/graphql", tag = "graphql",
+    summary = "GraphiQL",
+    responses((status = 200, description = "Página do GraphiQL.", content_type = "text/html")),
+)]
+#[get("/graphql")]
+pub async fn graphiql() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, App};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{get_url_from_env, routes::tests::{privacy_config, TransactionCustomizer}};
+
+    // Os carregadores consultam o banco de dados em paralelo; com uma única conexão, todas as
+    // consultas enxergam os dados inseridos na transação de teste.
+    fn build_test_connection_pool() -> Pool<ConnectionManager<PgConnection>> {
+        Pool::builder()
+            .max_size(1)
+            .connection_customizer(Box::new(TransactionCustomizer))
+            .build(ConnectionManager::new(get_url_from_env().unwrap()))
+            .unwrap()
+    }
+
+    fn get_csv() -> &'static str {
+        "txNomeParlamentar;cpf;ideCadastro;nuCarteiraParlamentar;nuLegislatura;sgUF;sgPartido;codLegislatura;numSubCota;txtDescricao;numEspecificacaoSubCota;txtDescricaoEspecificacao;txtFornecedor;txtCNPJCPF;txtNumero;indTipoDocumento;datEmissao;vlrDocumento;vlrGlosa;vlrLiquido;numMes;numAno;numParcela;txtPassageiro;txtTrecho;numLote;numRessarcimento;datPagamentoRestituicao;vlrRestituicao;nuDeputadoId;ideDocumento;urlDocumento
+Jorge;22488012033;;;2023;PB;PSB;57;1;PASSAGEM AÉREA;0;;TAM LINHAS AEREAS S/A;02.012.862/0001-60;1984;0;2025-03-07T00:00:00;100;0;100;3;2025;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;PSB;57;1;PASSAGEM AÉREA;0;;Tam Linhas Aéreas;02.012.862/0001-60;1984;0;2025-02-07T00:00:00;50;0;50;2;2025;0;;;0;;;;0;0;
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;2025-01-07T00:00:00;100;0;100;1;2025;0;;;0;;;;0;0;
+Zé;71838787089;;;2023;RJ;PT;57;1;PASSAGEM AÉREA;0;;LATAM AIRLINES;;1984;0;2025-03-07T00:00:00;25;0;25;3;2025;0;;;0;;;;0;0;"
+    }
+
+    async fn executa(pool: Pool<ConnectionManager<PgConnection>>, query: &str) -> Value {
+        executa_como(pool, None, query).await
+    }
+
+    // Executa a consulta com o token informado, como as requisições autenticadas.
+    async fn executa_como(pool: Pool<ConnectionManager<PgConnection>>, token: Option<&str>, query: &str) -> Value {
+        let app = test::init_service(
+            App::new()
+                .service(consulta_graphql)
+                .app_data(web::Data::new(graphql::schema()))
+                .app_data(web::Data::new(pool))
+                .app_data(privacy_config())
+        ).await;
+
+        let mut req = test::TestRequest::post().uri("/graphql").set_json(json!({ "query": query }));
+        if let Some(token) = token {
+            req = req.insert_header(("Authorization", format!("Bearer {token}")));
+        }
+        test::call_and_read_body_json(&app, req.to_request()).await
+    }
+
+    #[actix_web::test]
+    async fn resolve_nested_objects() {
+        let pool = build_test_connection_pool();
+        let mut connection = pool.get().unwrap();
+        crate::import::process_csv(&mut connection, get_csv().as_bytes()).unwrap();
+        drop(connection);
+
+        let resposta = executa(pool.clone(), r#"{
+            deputados(filtro: { ordem: TOTAL, direcao: DESC }, pageSize: 2) {
+                total
+                dados {
+                    nome
+                    partido
+                    total
+                    quantidadeDespesas
+                    despesas(ultimas: 2) {
+                        valorLiquido
+                        competencia
+                        nomeFornecedor
+                        deputado { cpf }
+                        fornecedor {
+                            documento
+                            total
+                            clientes(limite: 5) { deputado { nome } total quantidadeDespesas }
+                        }
+                    }
+                }
+            }
+            fornecedor(documento: "02.012.862/0001-60") { nome quantidadeDeputados }
+            soma
+        }"#).await;
+        assert!(resposta.get("errors").is_none(), "{resposta}");
+
+        let deputados = &resposta["data"]["deputados"];
+        assert_eq!(deputados["total"], 2);
+        let jorge = &deputados["dados"][0];
+        assert_eq!(jorge["nome"], "Jorge");
+        assert_eq!(jorge["partido"], "PSB");
+        assert_eq!(jorge["total"], "250.00");
+        assert_eq!(jorge["quantidadeDespesas"], 3);
+        assert_eq!(jorge["despesas"].as_array().unwrap().len(), 2);
+        let despesa = &jorge["despesas"][0];
+        assert_eq!(despesa["competencia"], "2025-03");
+        assert_eq!(despesa["valorLiquido"], "100.00");
+        assert_eq!(despesa["deputado"]["cpf"], "***.880.120-**");
+        assert_eq!(despesa["fornecedor"]["documento"], "02012862000160");
+        assert_eq!(despesa["fornecedor"]["total"], "150.00");
+        assert_eq!(despesa["fornecedor"]["clientes"], json!([{ "deputado": { "nome": "Jorge" }, "total": "150.00", "quantidadeDespesas": 2 }]));
+        assert_eq!(jorge["despesas"][1]["nomeFornecedor"], "Tam Linhas Aéreas");
+
+        let ze = &deputados["dados"][1];
+        assert_eq!(ze["total"], "25.00");
+        assert_eq!(ze["despesas"][0]["nomeFornecedor"], "LATAM AIRLINES");
+        assert_eq!(ze["despesas"][0]["fornecedor"], Value::Null);
+
+        assert_eq!(resposta["data"]["fornecedor"]["quantidadeDeputados"], 1);
+        assert_eq!(resposta["data"]["soma"], "275.00");
+    }
+
+    #[actix_web::test]
+    async fn paginate_and_filter_expenses() {
+        let pool = build_test_connection_pool();
+        let mut connection = pool.get().unwrap();
+        crate::import::process_csv(&mut connection, get_csv().as_bytes()).unwrap();
+        drop(connection);
+
+        let resposta = executa(pool.clone(), r#"{
+            despesas(filtro: { uf: "PB", ordem: VALOR, direcao: DESC }, pageSize: 2) {
+                total
+                next
+                dados { valorLiquido deputado { nome } }
+            }
+        }"#).await;
+        let pagina = &resposta["data"]["despesas"];
+        assert_eq!(pagina["total"], 3);
+        assert_eq!(pagina["dados"][0]["valorLiquido"], "100.00");
+        assert_eq!(pagina["dados"][1]["valorLiquido"], "100.00");
+        assert_eq!(pagina["dados"][1]["deputado"]["nome"], "Jorge");
+
+        let query = format!(
+            r#"{{ despesas(filtro: {{ uf: "PB", ordem: VALOR, direcao: DESC }}, pageSize: 2, cursor: "{}") {{ dados {{ valorLiquido }} next }} }}"#,
+            pagina["next"].as_str().unwrap(),
+        );
+        let resposta = executa(pool.clone(), &query).await;
+        assert_eq!(resposta["data"]["despesas"]["dados"], json!([{ "valorLiquido": "50.00" }]));
+        assert_eq!(resposta["data"]["despesas"]["next"], Value::Null);
+
+        let resposta = executa(pool.clone(), r#"{ despesas(filtro: { uf: "XX" }) { total } }"#).await;
+        assert_eq!(resposta["errors"][0]["extensions"]["code"], "bad_request");
+        assert_eq!(resposta["errors"][0]["message"], "UF inválido: \"XX\".");
+
+        let consulta = r#"{ deputado(chave: "71838787089") { nome uf cpf } nenhum: deputado(chave: "999") { nome } }"#;
+        let resposta = executa(pool.clone(), consulta).await;
+        assert_eq!(resposta["data"], json!({ "deputado": { "nome": "Zé", "uf": "RJ", "cpf": "***.387.870-**" }, "nenhum": null }));
+
+        let resposta = executa_como(pool.clone(), Some("leitor"), consulta).await;
+        assert_eq!(resposta["data"]["deputado"]["cpf"], "71838787089");
+
+        let resposta = executa(pool, r#"{ despesas(filtro: { cpf: "71838787089" }) { total } }"#).await;
+        assert_eq!(resposta["data"]["despesas"]["total"], 1);
+    }
+
+    #[actix_web::test]
+    async fn reject_queries_whose_lists_are_too_large() {
+        let pool = build_test_connection_pool();
+
+        let resposta = executa(pool.clone(), r#"{
+            deputados(pageSize: 100) {
+                dados { despesas(ultimas: 100) { valorLiquido competencia } }
+            }
+        }"#).await;
+        assert_eq!(resposta["errors"][0]["message"], "Query is too complex.");
+
+        let resposta = executa(pool.clone(), r#"{
+            fornecedores(pageSize: 100) { dados { clientes(limite: 100) { total } } }
+        }"#).await;
+        assert_eq!(resposta["errors"][0]["message"], "Query is too complex.");
+
+        let resposta = executa(pool, r#"{
+            deputados(pageSize: 10) { dados { despesas(ultimas: 10) { valorLiquido competencia } } }
+        }"#).await;
+        assert!(resposta.get("errors").is_none(), "{resposta}");
+    }
+}
@@ -0,0 +1,520 @@
+use std::sync::Arc;
+
+use actix_web::{delete, head, http::header::{self, HeaderMap}, mime, patch, post, route, web, HttpRequest, HttpResponse};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use diesel::{r2d2::ConnectionManager, PgConnection};
+use futures_util::StreamExt;
+use r2d2::Pool;
+
+use crate::{config::UploadConfig, error::{ApiError, ErrorBody}, resumable::{UploadGuard, UploadInfo, UploadStore}, versioning::Versao};
+
+use super::{import_response, run_imports, ImportResult, Upload, UploadKind};
+
+// Endpoints de upload retomável, seguindo o protocolo tus (https://tus.io/protocols/resumable-upload):
+// o cliente cria um upload informando seu tamanho total, envia o conteúdo em blocos com PATCH,
+// cada um a partir do offset atual, e pode consultar esse offset com HEAD para retomar um envio
+// interrompido. Quando o último bloco é recebido, o arquivo é importado como em /processar-ceap.
+
+const TUS_VERSION: &str = "1.0.0";
+
+// Quantidade de bytes de um bloco acumulada antes de cada escrita em disco.
+const WRITE_BATCH_SIZE: usize = 1024 * 1024;
+
+// Lê um cabeçalho numérico da requisição.
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+// Acrescenta dados a um upload fora do executor, devolvendo a guarda para as próximas escritas.
+async fn write(store: &Arc<UploadStore>, guard: UploadGuard, data: Vec<u8>) -> Result<UploadGuard, ApiError> {
+    let store = store.clone();
+
+    Ok(web::block(move || store.append(&guard, &data).map(|_| guard)).await??)
+}
+
+// Interpreta o cabeçalho Upload-Metadata, composto por pares "chave valor" separados por vírgulas,
+// onde o valor é codificado em base64, e extrai o nome e o tipo do arquivo.
+fn parse_metadata(headers: &HeaderMap) -> Result<(Option<String>, Option<String>), ApiError> {
+    let mut file_name = None;
+    let mut file_type = None;
+
+    let metadata = match headers.get("Upload-Metadata") {
+        Some(metadata) => metadata.to_str().map_err(|_| ApiError::BadRequest("invalid Upload-Metadata".to_string()))?,
+        None => return Ok((None, None)),
+    };
+
+    for pair in metadata.split(',') {
+        let mut parts = pair.trim().splitn(2, ' ');
+        let key = parts.next().unwrap_or_default();
+        let value = match parts.next() {
+            Some(value) => STANDARD
+                .decode(value.trim())
+                .ok()
+                .and_then(|value| String::from_utf8(value).ok())
+                .ok_or_else(|| ApiError::BadRequest("invalid Upload-Metadata".to_string()))?,
+            None => continue,
+        };
+
+        match key {
+            "filename" => file_name = Some(value),
+            "filetype" => file_type = Some(value),
+            _ => {},
+        }
+    }
+
+    Ok((file_name, file_type))
+}
+
+// Identifica o formato de um upload a partir dos metadados informados na sua criação.
+fn upload_kind(info: &UploadInfo) -> Option<UploadKind> {
+    let file_type = match &info.file_type {
+        Some(file_type) => Some(file_type.parse::<mime::Mime>().ok()?),
+        None => None,
+    };
+
+    UploadKind::detect(file_type.as_ref(), info.file_name.as_deref())
+}
+
+// Informa as capacidades do servidor de upload.
+#[utoipa::path(
+    options, path = "/uploads", tag = "uploads",
+    summary = "Capacidades do servidor de upload",
+    responses((status = 204, description = "Versão, extensões e tamanho máximo, nos cabeçalhos Tus-*.")),
+)]
+#[route("/uploads", method = "OPTIONS")]
+pub async fn opcoes_upload(config: web::Data<UploadConfig>) -> HttpResponse {
+    HttpResponse::NoContent()
+        .insert_header(("Tus-Resumable", TUS_VERSION))
+        .insert_header(("Tus-Version", TUS_VERSION))
+        .insert_header(("Tus-Max-Size", config.max_size.to_string()))
+        .insert_header(("Tus-Extension", "creation,termination"))
+        .finish()
+}
+
+// Cria um upload retomável; o tamanho total do arquivo deve ser informado no cabeçalho
+// Upload-Length, e seu nome e tipo podem ser informados no cabeçalho Upload-Metadata.
+#[utoipa::path(
+    post, path = "/uploads", tag = "uploads",
+    summary = "Criação de um upload retomável",
+    params(
+        ("Upload-Length" = u64, Header, description = "Tamanho total do arquivo."),
+        ("Upload-Metadata" = Option<String>, Header, description = "Pares filename e filetype, com os valores em base64."),
+    ),
+    responses(
+        (status = 201, description = "Upload criado; o endereço está no cabeçalho Location."),
+        (status = 400, description = "Cabeçalhos inválidos.", body = ErrorBody),
+        (status = 413, description = "Arquivo maior que o tamanho máximo.", body = ErrorBody),
+        (status = 415, description = "Tipo de arquivo não suportado.", body = ErrorBody),
+    ),
+)]
+#[post("/uploads")]
+pub async fn cria_upload(
+    req: HttpRequest,
+    config: web::Data<UploadConfig>,
+    store: web::Data<UploadStore>) -> Result<HttpResponse, ApiError> {
+    let length = header_u64(req.headers(), "Upload-Length")
+        .ok_or_else(|| ApiError::BadRequest("missing or invalid Upload-Length".to_string()))?;
+
+    if length > config.max_size as u64 {
+        return Err(ApiError::PayloadTooLarge("upload exceeds the maximum size".to_string()));
+    }
+
+    let (file_name, file_type) = parse_metadata(req.headers())?;
+    let info = UploadInfo { length, file_name, file_type };
+
+    if upload_kind(&info).is_none() {
+        return Err(ApiError::UnsupportedMediaType("only CSV and ZIP files are accepted".to_string()));
+    }
+
+    let id = web::block(move || store.create(&info))
+        .await??;
+
+    // O endereço é relativo ao da requisição, que pode estar sob o prefixo de uma versão da API.
+    Ok(
+        HttpResponse::Created()
+        .insert_header((header::LOCATION, format!("{}/{id}", req.path().trim_end_matches('/'))))
+        .insert_header(("Tus-Resumable", TUS_VERSION))
+        .finish()
+    )
+}
+
+// Informa quantos bytes de um upload já foram recebidos.
+#[utoipa::path(
+    head, path = "/uploads/{id}", tag = "uploads",
+    summary = "Offset de um upload",
+    params(("id" = String, Path, description = "Identificador do upload.")),
+    responses(
+        (status = 200, description = "Bytes já recebidos, no cabeçalho Upload-Offset."),
+        (status = 404, description = "Upload não encontrado."),
+    ),
+)]
+#[head("/uploads/{id}")]
+pub async fn consulta_upload(
+    id: web::Path<String>,
+    store: web::Data<UploadStore>) -> Result<HttpResponse, ApiError> {
+    let (info, offset) = web::block(move || store.get(&id))
+        .await??
+        .ok_or_else(|| ApiError::NotFound("upload not found".to_string()))?;
+
+    Ok(
+        HttpResponse::Ok()
+        .insert_header(("Upload-Offset", offset.to_string()))
+        .insert_header(("Upload-Length", info.length.to_string()))
+        .insert_header(("Tus-Resumable", TUS_VERSION))
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .finish()
+    )
+}
+
+// Recebe um bloco de um upload, a partir do offset informado no cabeçalho Upload-Offset, que deve
+// ser igual à quantidade de bytes já recebida. Caso o bloco conclua o upload, o arquivo é importado
+// e a resposta traz o resultado da importação; caso contrário, a resposta é vazia.
+#[utoipa::path(
+    patch, path = "/uploads/{id}", tag = "uploads",
+    summary = "Envio de um bloco de um upload",
+    params(
+        ("id" = String, Path, description = "Identificador do upload."),
+        ("Upload-Offset" = u64, Header, description = "Bytes já recebidos, a partir dos quais o bloco é escrito."),
+    ),
+    request_body(content_type = "application/offset+octet-stream", description = "Conteúdo do bloco."),
+    responses(
+        (status = 200, description = "O bloco concluiu o upload, e o arquivo foi importado.", body = Vec<ImportResult>),
+        (status = 204, description = "Bloco recebido; o novo offset está no cabeçalho Upload-Offset."),
+        (status = 404, description = "Upload não encontrado.", body = ErrorBody),
+        (status = 409, description = "Upload-Offset diferente do offset atual.", body = ErrorBody),
+        (status = 423, description = "Upload sendo escrito por outra requisição.", body = ErrorBody),
+        (status = 422, description = "O arquivo foi rejeitado na importação.", body = ErrorBody),
+    ),
+)]
+#[patch("/uploads/{id}")]
+pub async fn envia_bloco(
+    req: HttpRequest,
+    id: web::Path<String>,
+    mut payload: web::Payload,
+    config: web::Data<UploadConfig>,
+    store: web::Data<UploadStore>,
+    versao: Versao,
+    pool: web::Data<Pool<ConnectionManager<PgConnection>>>) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+
+    let is_offset_stream = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type == "application/offset+octet-stream");
+    if !is_offset_stream {
+        return Err(ApiError::UnsupportedMediaType("chunks must be sent as application/offset+octet-stream".to_string()));
+    }
+
+    let requested_offset = header_u64(req.headers(), "Upload-Offset")
+        .ok_or_else(|| ApiError::BadRequest("missing or invalid Upload-Offset".to_string()))?;
+
+    let store = store.into_inner();
+    let guard = store
+        .lock(&id)
+        .ok_or_else(|| ApiError::Locked("upload is being written by another request".to_string()))?;
+
+    let upload_store = store.clone();
+    let upload_id = id.clone();
+    let (info, start) = web::block(move || upload_store.get(&upload_id))
+        .await??
+        .ok_or_else(|| ApiError::NotFound("upload not found".to_string()))?;
+
+    if requested_offset != start {
+        return Err(ApiError::Conflict("Upload-Offset does not match the current offset".to_string()));
+    }
+
+    // Um bloco maior que o restante do upload é rejeitado antes de qualquer escrita.
+    let content_length = header_u64(req.headers(), header::CONTENT_LENGTH.as_str());
+    if content_length.is_some_and(|length| start + length > info.length) {
+        return Err(ApiError::BadRequest("chunk exceeds the upload length".to_string()));
+    }
+
+    // O conteúdo é acumulado e gravado em lotes, fora do executor.
+    let mut guard = guard;
+    let mut offset = start;
+    let mut pending = Vec::new();
+    let mut error = None;
+
+    while let Some(chunk) = payload.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                // Os dados já recebidos são mantidos, e o cliente pode retomar o envio a partir deles.
+                error = Some(ApiError::BadRequest(e.to_string()));
+                break;
+            },
+        };
+
+        if offset + (pending.len() + chunk.len()) as u64 > info.length {
+            // Sem o Content-Length, o excesso só é percebido durante o envio; o que já foi gravado
+            // deste bloco é descartado.
+            let upload_store = store.clone();
+            web::block(move || upload_store.truncate(&guard, start)).await??;
+            return Err(ApiError::BadRequest("chunk exceeds the upload length".to_string()));
+        }
+
+        pending.extend_from_slice(&chunk);
+        if pending.len() >= WRITE_BATCH_SIZE {
+            offset += pending.len() as u64;
+            guard = write(&store, guard, std::mem::take(&mut pending)).await?;
+        }
+    }
+
+    if !pending.is_empty() {
+        offset += pending.len() as u64;
+        guard = write(&store, guard, pending).await?;
+    }
+
+    if let Some(error) = error {
+        return Err(error);
+    }
+
+    if offset < info.length {
+        return Ok(
+            HttpResponse::NoContent()
+            .insert_header(("Upload-Offset", offset.to_string()))
+            .insert_header(("Tus-Resumable", TUS_VERSION))
+            .finish()
+        );
+    }
+
+    let kind = upload_kind(&info).ok_or_else(|| ApiError::UnsupportedMediaType("only CSV and ZIP files are accepted".to_string()))?;
+    let name = info.file_name.clone().unwrap_or_else(|| id.clone());
+    let max_size = config.max_size;
+
+    let results = web::block(move || {
+        let bytes = store.read(&id)?;
+        let results = run_imports(&pool, vec![Upload { name, kind, bytes }], max_size)?;
+        store.remove(&id)?;
+        drop(guard);
+
+        Ok::<_, anyhow::Error>(results)
+    })
+    .await??;
+
+    let mut response = import_response(versao, results);
+    let headers = response.headers_mut();
+    headers.insert(
+        header::HeaderName::from_static("upload-offset"),
+        header::HeaderValue::from(offset),
+    );
+    headers.insert(
+        header::HeaderName::from_static("tus-resumable"),
+        header::HeaderValue::from_static(TUS_VERSION),
+    );
+
+    Ok(response)
+}
+
+// Cancela um upload, removendo os dados já recebidos.
+#[utoipa::path(
+    delete, path = "/uploads/{id}", tag = "uploads",
+    summary = "Remoção de um upload",
+    params(("id" = String, Path, description = "Identificador do upload.")),
+    responses(
+        (status = 204, description = "Upload removido."),
+        (status = 404, description = "Upload não encontrado.", body = ErrorBody),
+    ),
+)]
+#[delete("/uploads/{id}")]
+pub async fn remove_upload(
+    id: web::Path<String>,
+    store: web::Data<UploadStore>) -> Result<HttpResponse, ApiError> {
+    let store = store.into_inner();
+    let guard = store
+        .lock(&id)
+        .ok_or_else(|| ApiError::Locked("upload is being written by another request".to_string()))?;
+
+    let found = web::block(move || {
+        let found = store.get(&id)?.is_some();
+        if found {
+            store.remove(&id)?;
+        }
+        drop(guard);
+
+        Ok::<_, anyhow::Error>(found)
+    })
+    .await??;
+
+    if !found {
+        return Err(ApiError::NotFound("upload not found".to_string()));
+    }
+
+    Ok(
+        HttpResponse::NoContent()
+        .insert_header(("Tus-Resumable", TUS_VERSION))
+        .finish()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App};
+    use diesel::{QueryDsl, RunQueryDsl};
+
+    use super::*;
+    use crate::routes::tests::build_test_connection_pool;
+
+    fn get_csv() -> &'static str {
+        "txNomeParlamentar;cpf;ideCadastro;nuCarteiraParlamentar;nuLegislatura;sgUF;sgPartido;codLegislatura;numSubCota;txtDescricao;numEspecificacaoSubCota;txtDescricaoEspecificacao;txtFornecedor;txtCNPJCPF;txtNumero;indTipoDocumento;datEmissao;vlrDocumento;vlrGlosa;vlrLiquido;numMes;numAno;numParcela;txtPassageiro;txtTrecho;numLote;numRessarcimento;datPagamentoRestituicao;vlrRestituicao;nuDeputadoId;ideDocumento;urlDocumento
+Jorge;22488012033;;;2023;PB;;57;1;Descrição;0;;Fornecedor;CNPJ-fornecedor;1984;0;2025-02-07T00:00:00;1467;0;1467;2;2025;0;;;0;;;;0;0;https://test.url/0001.pdf
+Zé;71838787089;;;2023;RJ;;57;1;Descrição;0;;Fornecedor;CNPJ-fornecedor;1984;0;2025-02-07T00:00:00;1467;0;1467;3;2025;0;;;0;;;;0;0;https://test.url/0002.pdf"
+    }
+
+    fn test_store() -> UploadStore {
+        UploadStore::new(std::env::temp_dir().join(format!("knex-uploads-test-{}", uuid::Uuid::new_v4().simple()))).unwrap()
+    }
+
+    fn patch(location: &str, offset: usize, chunk: &[u8]) -> test::TestRequest {
+        test::TestRequest::patch()
+            .uri(location)
+            .insert_header(("Content-Type", "application/offset+octet-stream"))
+            .insert_header(("Upload-Offset", offset.to_string()))
+            .set_payload(chunk.to_vec())
+    }
+
+    #[actix_web::test]
+    async fn envia_upload_em_blocos_e_importa() {
+        use crate::schema::expenses::dsl::expenses;
+
+        let pool = build_test_connection_pool().unwrap();
+        let app = test::init_service(
+            App::new()
+                .service(cria_upload)
+                .service(consulta_upload)
+                .service(envia_bloco)
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(UploadConfig::default()))
+                .app_data(web::Data::new(test_store()))
+        ).await;
+        let csv = get_csv().as_bytes();
+
+        let req = test::TestRequest::post()
+            .uri("/uploads")
+            .insert_header(("Upload-Length", csv.len().to_string()))
+            .insert_header(("Upload-Metadata", format!("filename {}", STANDARD.encode("2025.csv"))))
+            .to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers().get(header::LOCATION).unwrap().to_str().unwrap().to_string();
+
+        let response = test::call_service(&app, patch(&location, 0, &csv[..100]).to_request()).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers().get("Upload-Offset").unwrap(), "100");
+
+        let req = test::TestRequest::default().method(actix_web::http::Method::HEAD).uri(&location).to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("Upload-Offset").unwrap(), "100");
+        assert_eq!(response.headers().get("Upload-Length").unwrap(), &csv.len().to_string());
+
+        let response = test::call_service(&app, patch(&location, 100, &csv[100..]).to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body[0]["arquivo"], "2025.csv");
+        assert_eq!(body[0]["despesas"], 2);
+
+        let mut connection = pool.get().unwrap();
+        assert_eq!(expenses.count().get_result(&mut connection), Ok(2));
+
+        let req = test::TestRequest::default().method(actix_web::http::Method::HEAD).uri(&location).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn rejeita_bloco_com_offset_incorreto() {
+        let app = test::init_service(
+            App::new()
+                .service(cria_upload)
+                .service(consulta_upload)
+                .service(envia_bloco)
+                .service(remove_upload)
+                .app_data(web::Data::new(build_test_connection_pool().unwrap()))
+                .app_data(web::Data::new(UploadConfig::default()))
+                .app_data(web::Data::new(test_store()))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/uploads")
+            .insert_header(("Upload-Length", "10"))
+            .to_request();
+        let response = test::call_service(&app, req).await;
+        let location = response.headers().get(header::LOCATION).unwrap().to_str().unwrap().to_string();
+
+        let response = test::call_service(&app, patch(&location, 5, b"abc").to_request()).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = test::call_service(&app, patch(&location, 0, b"abc").to_request()).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // Um bloco maior que o restante do upload é rejeitado sem que nada seja gravado, com ou sem
+        // o Content-Length.
+        let response = test::call_service(&app, patch(&location, 3, b"defghijklmn").to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let mut req = patch(&location, 3, b"defghijklmn").to_request();
+        req.headers_mut().remove(header::CONTENT_LENGTH);
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::default().method(actix_web::http::Method::HEAD).uri(&location).to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.headers().get("Upload-Offset").unwrap(), "3");
+    }
+
+    #[actix_web::test]
+    async fn rejeita_upload_invalido() {
+        let app = test::init_service(
+            App::new()
+                .service(cria_upload)
+                .service(envia_bloco)
+                .service(remove_upload)
+                .app_data(web::Data::new(build_test_connection_pool().unwrap()))
+                .app_data(web::Data::new(UploadConfig::default()))
+                .app_data(web::Data::new(test_store()))
+        ).await;
+
+        let req = test::TestRequest::post().uri("/uploads").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/uploads")
+            .insert_header(("Upload-Length", (UploadConfig::default().max_size + 1).to_string()))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let req = test::TestRequest::post()
+            .uri("/uploads")
+            .insert_header(("Upload-Length", "10"))
+            .insert_header(("Upload-Metadata", format!("filetype {}", STANDARD.encode("image/png"))))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[actix_web::test]
+    async fn cancela_upload() {
+        let app = test::init_service(
+            App::new()
+                .service(cria_upload)
+                .service(envia_bloco)
+                .service(remove_upload)
+                .app_data(web::Data::new(build_test_connection_pool().unwrap()))
+                .app_data(web::Data::new(UploadConfig::default()))
+                .app_data(web::Data::new(test_store()))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/uploads")
+            .insert_header(("Upload-Length", "10"))
+            .to_request();
+        let response = test::call_service(&app, req).await;
+        let location = response.headers().get(header::LOCATION).unwrap().to_str().unwrap().to_string();
+
+        let req = test::TestRequest::delete().uri(&location).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::delete().uri(&location).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+}
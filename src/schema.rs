@@ -16,15 +16,71 @@ diesel::table! {
         id -> Int4,
         data_emissao -> Nullable<Timestamp>,
         fornecedor -> Varchar,
-        valor_liquido -> Float4,
+        valor_liquido -> Numeric,
         url_documento -> Nullable<Varchar>,
         deputado_id -> Int4,
+        competencia -> Date,
+        fornecedor_documento -> Nullable<Varchar>,
+        categoria -> Varchar,
+    }
+}
+
+diesel::table! {
+    fornecedores_agrupados (fornecedor) {
+        fornecedor -> Varchar,
+        grupo_id -> Int4,
+        confianca -> Float4,
+        manual -> Bool,
+    }
+}
+
+diesel::table! {
+    grupos_fornecedores (id) {
+        id -> Int4,
+        nome -> Varchar,
+        documento -> Nullable<Varchar>,
+        confianca -> Float4,
+        manual -> Bool,
+    }
+}
+
+diesel::table! {
+    resumo_despesas (deputado_id, competencia, categoria) {
+        deputado_id -> Int4,
+        #[max_length = 2]
+        uf -> Bpchar,
+        competencia -> Date,
+        categoria -> Varchar,
+        total -> Numeric,
+        quantidade -> Int8,
+    }
+}
+
+diesel::table! {
+    resumo_despesas_estado (id) {
+        id -> Int4,
+        atualizado_em -> Timestamp,
+    }
+}
+
+diesel::table! {
+    versao_dados (id) {
+        id -> Int4,
+        versao -> Int8,
+        atualizado_em -> Timestamp,
     }
 }
 
 diesel::joinable!(expenses -> deputados (deputado_id));
+diesel::joinable!(fornecedores_agrupados -> grupos_fornecedores (grupo_id));
+diesel::joinable!(resumo_despesas -> deputados (deputado_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     deputados,
     expenses,
+    fornecedores_agrupados,
+    grupos_fornecedores,
+    resumo_despesas,
+    resumo_despesas_estado,
+    versao_dados,
 );
@@ -0,0 +1,153 @@
+use std::collections::HashSet;
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+// Similaridade mínima entre uma palavra do texto e uma palavra da busca para que ela seja destacada.
+const HIGHLIGHT_THRESHOLD: f32 = 0.4;
+
+// Normaliza um texto para comparação, removendo acentos e convertendo-o para minúsculas, como
+// f_unaccent(lower(...)) faz no banco de dados.
+pub fn normalize(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// Divide um texto em palavras, isto é, sequências de caracteres alfanuméricos, retornando a
+// posição de cada uma no texto original.
+pub fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                words.push((s, &text[s..i]));
+                start = None;
+            },
+            _ => {},
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, &text[s..]));
+    }
+
+    words
+}
+
+// Trigramas de um texto, calculados como no pg_trgm: cada palavra, normalizada, recebe dois
+// espaços no início e um no fim antes de ser dividida.
+pub fn trigrams(text: &str) -> HashSet<String> {
+    let mut trigrams = HashSet::new();
+
+    for (_, word) in words(text) {
+        let padded: Vec<char> = format!("  {} ", normalize(word)).chars().collect();
+        for window in padded.windows(3) {
+            trigrams.insert(window.iter().collect());
+        }
+    }
+
+    trigrams
+}
+
+// Similaridade entre dois textos, de 0 a 1, definida como a proporção de trigramas em comum.
+pub fn similarity(a: &str, b: &str) -> f32 {
+    trigram_similarity(&trigrams(a), &trigrams(b))
+}
+
+// Similaridade entre dois conjuntos de trigramas já calculados; útil quando um mesmo texto é
+// comparado com muitos outros.
+pub fn trigram_similarity(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    a.intersection(b).count() as f32 / a.union(b).count() as f32
+}
+
+// Escapa os caracteres especiais do HTML em um texto.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+// Destaca, envolvendo-as com <mark></mark>, as palavras do texto que correspondem a alguma palavra
+// da busca, seja por começarem com ela, seja por serem semelhantes o bastante a ela; a comparação
+// ignora acentos e maiúsculas. O resultado é HTML: o restante do texto é escapado.
+pub fn highlight(text: &str, query: &str) -> String {
+    let query_words: Vec<String> = words(query)
+        .into_iter()
+        .map(|(_, word)| normalize(word))
+        .collect();
+
+    let mut highlighted = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for (start, word) in words(text) {
+        let normalized = normalize(word);
+        let matches = query_words.iter().any(|query_word| {
+            normalized.starts_with(query_word.as_str())
+                || similarity(&normalized, query_word) >= HIGHLIGHT_THRESHOLD
+        });
+
+        if matches {
+            highlighted.push_str(&escape_html(&text[last..start]));
+            highlighted.push_str("<mark>");
+            highlighted.push_str(&escape_html(word));
+            highlighted.push_str("</mark>");
+            last = start + word.len();
+        }
+    }
+    highlighted.push_str(&escape_html(&text[last..]));
+
+    highlighted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normaliza_acentos_e_maiusculas() {
+        assert_eq!(normalize("JOÃO Conceição"), "joao conceicao");
+        assert_eq!(normalize("São Paulo"), "sao paulo");
+    }
+
+    #[test]
+    fn calcula_similaridade_por_trigramas() {
+        assert_eq!(similarity("word", "word"), 1.0);
+        assert_eq!(similarity("", "word"), 0.0);
+        assert!(similarity("latam", "latan") > similarity("latam", "gol"));
+    }
+
+    #[test]
+    fn destaca_palavras_semelhantes() {
+        assert_eq!(highlight("TAM LINHAS AÉREAS S/A", "aereas"), "TAM LINHAS <mark>AÉREAS</mark> S/A");
+        assert_eq!(highlight("Posto Ipiranga", "ipirnga"), "Posto <mark>Ipiranga</mark>");
+        assert_eq!(highlight("Posto Ipiranga", "post"), "<mark>Posto</mark> Ipiranga");
+        assert_eq!(highlight("Posto Ipiranga", "gol"), "Posto Ipiranga");
+    }
+
+    #[test]
+    fn escapa_html_do_texto_destacado() {
+        assert_eq!(
+            highlight("<script>alert('x')</script> Posto & Cia", "posto"),
+            "&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt; <mark>Posto</mark> &amp; Cia",
+        );
+        assert_eq!(highlight("\"Gol\"", "gol"), "&quot;<mark>Gol</mark>&quot;");
+    }
+}
@@ -0,0 +1,261 @@
+use std::{borrow::Cow, fmt, hash::{Hash, Hasher}, io::Write, str::FromStr};
+
+use diesel::{deserialize::{self, FromSql, FromSqlRow}, expression::AsExpression, pg::{Pg, PgValue}, serialize::{self, Output, ToSql}, sql_types::Text};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use utoipa::{openapi::{schema::{ObjectBuilder, Schema, Type}, RefOr}, PartialSchema, ToSchema};
+
+use crate::{privacy::{self, Privacidade}, validate::{normaliza_cpf, normaliza_documento, valida_cnpj}};
+
+// Tipos dos valores de domínio que, até então, circulavam como strings: CPF, CNPJ, UF e o
+// documento dos fornecedores. Só podem
+// ser construídos a partir de valores válidos, de modo que os extratores (web::Path, web::Query) e
+// o importador rejeitam valores inválidos antes de consultar o banco de dados. Os valores lidos do
+// banco de dados já foram validados na inserção, e não são validados novamente.
+
+// Valor rejeitado por um dos tipos de domínio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValorInvalido {
+    pub tipo: &'static str,
+    pub valor: String,
+}
+
+impl fmt::Display for ValorInvalido {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} inválido: {:?}.", self.tipo, self.valor)
+    }
+}
+
+impl std::error::Error for ValorInvalido {}
+
+// Implementa, para um tipo de domínio com um construtor `new` que valida o valor, a conversão
+// de e para strings, a serialização, a leitura e escrita como texto no banco de dados, e o esquema
+// OpenAPI, uma string com o exemplo informado.
+//
+// Os tipos protegidos, cujos valores podem ser CPFs, guardam também a privacidade com que são
+// exibidos, aplicada por `exibe` (veja privacy::Protegido); a serialização aplica ao valor a
+// função informada, como a máscara dos CPFs, com essa privacidade. A privacidade não participa das
+// comparações.
+macro_rules! valor_de_dominio {
+    ($tipo:ident, $exemplo:literal) => {
+        impl $tipo {
+            fn de_texto(valor: String) -> Self {
+                Self(valor)
+            }
+        }
+
+        impl Serialize for $tipo {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.0)
+            }
+        }
+
+        valor_de_dominio!(@comum $tipo, $exemplo);
+    };
+    ($tipo:ident, $exemplo:literal, protegido por $exibe:path) => {
+        impl $tipo {
+            fn de_texto(valor: String) -> Self {
+                Self(valor, Privacidade::Mascarada)
+            }
+
+            pub fn exibe(&mut self, privacidade: Privacidade) {
+                self.1 = privacidade;
+            }
+
+            // Valor como exibido nas respostas e nas exportações.
+            pub fn exibido(&self) -> Cow<'_, str> {
+                $exibe(&self.0, self.1)
+            }
+        }
+
+        impl Serialize for $tipo {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.exibido())
+            }
+        }
+
+        impl PartialEq for $tipo {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0
+            }
+        }
+
+        impl Eq for $tipo {}
+
+        impl Hash for $tipo {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.hash(state);
+            }
+        }
+
+        valor_de_dominio!(@comum $tipo, $exemplo);
+    };
+    (@comum $tipo:ident, $exemplo:literal) => {
+        impl $tipo {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $tipo {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl AsRef<str> for $tipo {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl FromStr for $tipo {
+            type Err = ValorInvalido;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::new(s)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $tipo {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let valor = String::deserialize(deserializer)?;
+
+                Self::new(&valor).map_err(de::Error::custom)
+            }
+        }
+
+        impl ToSql<Text, Pg> for $tipo {
+            fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+                out.write_all(self.0.as_bytes())?;
+                Ok(serialize::IsNull::No)
+            }
+        }
+
+        impl FromSql<Text, Pg> for $tipo {
+            fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+                <String as FromSql<Text, Pg>>::from_sql(bytes).map(Self::de_texto)
+            }
+        }
+
+        impl PartialSchema for $tipo {
+            fn schema() -> RefOr<Schema> {
+                ObjectBuilder::new().schema_type(Type::String).examples([$exemplo]).into()
+            }
+        }
+
+        impl ToSchema for $tipo {}
+    };
+}
+
+// CPF na forma canônica, com 11 dígitos e dígitos verificadores válidos; é construído também a
+// partir do CPF formatado ou sem os zeros à esquerda.
+#[derive(AsExpression, FromSqlRow, Debug, Clone)]
+#[diesel(sql_type = Text)]
+pub struct Cpf(String, Privacidade);
+
+impl Cpf {
+    pub fn new(valor: &str) -> Result<Self, ValorInvalido> {
+        normaliza_cpf(valor)
+            .map(Self::de_texto)
+            .ok_or_else(|| ValorInvalido { tipo: "CPF", valor: valor.trim().to_string() })
+    }
+}
+
+// Nas respostas, o CPF segue a política de privacidade: é mascarado, exceto para as requisições
+// autenticadas; veja privacy.
+valor_de_dominio!(Cpf, "22488012033", protegido por privacy::cpf);
+
+// CNPJ, composto apenas pelos seus 14 dígitos, com dígitos verificadores válidos.
+#[derive(AsExpression, FromSqlRow, Debug, Clone, PartialEq, Eq, Hash)]
+#[diesel(sql_type = Text)]
+pub struct Cnpj(String);
+
+impl Cnpj {
+    pub fn new(valor: &str) -> Result<Self, ValorInvalido> {
+        let valor = valor.trim();
+
+        if valida_cnpj(valor) {
+            Ok(Self(valor.to_string()))
+        } else {
+            Err(ValorInvalido { tipo: "CNPJ", valor: valor.to_string() })
+        }
+    }
+}
+
+valor_de_dominio!(Cnpj, "07575651000159");
+
+// Documento de um fornecedor, um CNPJ ou um CPF, composto apenas pelos seus dígitos, como é
+// armazenado nas despesas; é construído também a partir do documento formatado. Os dígitos
+// verificadores não são conferidos; veja normaliza_documento.
+#[derive(AsExpression, FromSqlRow, Debug, Clone)]
+#[diesel(sql_type = Text)]
+pub struct DocumentoFornecedor(String, Privacidade);
+
+impl DocumentoFornecedor {
+    pub fn new(valor: &str) -> Result<Self, ValorInvalido> {
+        normaliza_documento(valor)
+            .map(Self::de_texto)
+            .ok_or_else(|| ValorInvalido { tipo: "Documento", valor: valor.trim().to_string() })
+    }
+}
+
+// Os documentos que são CPFs seguem a política de privacidade, como os CPFs dos deputados.
+valor_de_dominio!(DocumentoFornecedor, "02012862000160", protegido por privacy::documento);
+
+// Siglas das unidades federativas.
+const UFS: [&str; 27] = [
+    "AC", "AL", "AM", "AP", "BA", "CE", "DF", "ES", "GO", "MA", "MG", "MS", "MT", "PA",
+    "PB", "PE", "PI", "PR", "RJ", "RN", "RO", "RR", "RS", "SC", "SE", "SP", "TO",
+];
+
+// Sigla de uma unidade federativa, em maiúsculas.
+#[derive(AsExpression, FromSqlRow, Debug, Clone, PartialEq, Eq, Hash)]
+#[diesel(sql_type = Text)]
+pub struct Uf(String);
+
+impl Uf {
+    pub fn new(valor: &str) -> Result<Self, ValorInvalido> {
+        let valor = valor.trim().to_uppercase();
+
+        if UFS.contains(&valor.as_str()) {
+            Ok(Self(valor))
+        } else {
+            Err(ValorInvalido { tipo: "UF", valor })
+        }
+    }
+}
+
+valor_de_dominio!(Uf, "PB");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valida_valores_de_dominio() {
+        assert_eq!(Cpf::new(" 52998224725").unwrap().as_str(), "52998224725");
+        assert_eq!(Cpf::new("529.982.247-25").unwrap(), Cpf::new("52998224725").unwrap());
+        assert!(Cpf::new("12345678900").is_err());
+
+        assert_eq!(Cnpj::new("11222333000181").unwrap().to_string(), "11222333000181");
+        assert!(Cnpj::new("00000000000000").is_err());
+
+        assert_eq!(DocumentoFornecedor::new("02.012.862/0001-60").unwrap().as_str(), "02012862000160");
+        assert_eq!(DocumentoFornecedor::new("123"), Err(ValorInvalido { tipo: "Documento", valor: "123".to_string() }));
+
+        assert_eq!(Uf::new("pb").unwrap().as_str(), "PB");
+        assert_eq!(Uf::new("XX"), Err(ValorInvalido { tipo: "UF", valor: "XX".to_string() }));
+    }
+
+    #[test]
+    fn rejeita_valores_invalidos_ao_desserializar() {
+        assert_eq!(serde_json::from_str::<Uf>(r#""sp""#).unwrap(), Uf::new("SP").unwrap());
+        assert!(serde_json::from_str::<Uf>(r#""NA""#).is_err());
+        assert!(serde_json::from_str::<Cpf>(r#""abc""#).is_err());
+        let mut cpf = Cpf::new("52998224725").unwrap();
+        assert_eq!(serde_json::to_string(&cpf).unwrap(), r#""***.982.247-**""#);
+        cpf.exibe(Privacidade::Revelada);
+        assert_eq!(serde_json::to_string(&cpf).unwrap(), r#""52998224725""#);
+        assert_eq!(cpf, Cpf::new("52998224725").unwrap());
+    }
+}
@@ -1,47 +1,80 @@
+// Normaliza um CPF para a sua forma canônica, com 11 dígitos, retornando-o caso seja válido.
+// Aceita o CPF formatado ("123.456.789-09") e o CPF sem os zeros à esquerda, que planilhas
+// costumam descartar ("2673718613" é o mesmo que "02673718613").
+pub fn normaliza_cpf(cpf: &str) -> Option<String> {
+    let cpf = cpf.trim();
+    if !cpf.chars().all(|c| c.is_ascii_digit() || c == '.' || c == '-') { return None; }
+
+    let digitos: String = cpf.chars().filter(char::is_ascii_digit).collect();
+    if !(9..=11).contains(&digitos.len()) { return None; }
+
+    let cpf = format!("{digitos:0>11}");
+    valida_cpf(&cpf).then_some(cpf)
+}
+
+// Verifica se um CPF, composto apenas pelos seus 11 dígitos, é válido. Assim como nos CNPJs, CPFs
+// com todos os dígitos iguais satisfazem os dígitos verificadores, mas não são válidos.
 pub fn valida_cpf(cpf: &str) -> bool {
-    if cpf.chars().filter(|c| c.is_numeric()).count() != cpf.len() { return false; }
-    
-    // Meio porco, mas funciona.
-    let cpf = match cpf.len() {
-        9 => cpf.to_string() + &"00",
-        10 => cpf.to_string() + &"0",
-        11 => cpf.to_string(),
-        _ => return false,
-    };
+    if cpf.len() != 11 || !cpf.bytes().all(|c| c.is_ascii_digit()) { return false; }
 
-    let mut cpf_digits = Vec::with_capacity(11);
+    let digits: Vec<u16> = cpf.bytes().map(|c| (c - b'0') as u16).collect();
+    if digits.iter().all(|d| *d == digits[0]) { return false; }
 
-    cpf.bytes().for_each(|c| cpf_digits.push((c - b'0') as u16));
-    let valida_1o_digito = cpf_digits[..9]
+    let valida_1o_digito = digits[..9]
         .iter()
         .enumerate()
-        .map(
-            |(i, digit)| {
-                // println!("{digit} * {}", 10-i);
-                digit * (10 - i as u16)}
-        ).sum::<u16>();
+        .map(|(i, digit)| digit * (10 - i as u16))
+        .sum::<u16>();
 
-    let valida_2o_digito = cpf_digits[..=9]
+    let valida_2o_digito = digits[..=9]
         .iter()
         .enumerate()
-        .map(
-            |(i, digit)| {
-                // println!("{digit} * {}", 11-i);
-                digit * (11 - i as u16)}
-        ).sum::<u16>();
-    // println!("{}\n{}\n{}\n{}\n{}\n{}",
-    //     valida_1o_digito,
-    //     valida_2o_digito,
-    //     ((valida_1o_digito * 10) % 11) % 10,
-    //     ((valida_2o_digito * 10) % 11) % 10,
-    //     cpf_digits.get(9).unwrap_or(&0),
-    //     cpf_digits.get(10).unwrap_or(&0) 
-    // );
-    ((valida_1o_digito * 10) % 11) % 10 == *cpf_digits.get(9).unwrap_or(&0) 
-        && ((valida_2o_digito * 10) % 11) % 10 == *cpf_digits.get(10).unwrap_or(&0)
+        .map(|(i, digit)| digit * (11 - i as u16))
+        .sum::<u16>();
+
+    ((valida_1o_digito * 10) % 11) % 10 == digits[9]
+        && ((valida_2o_digito * 10) % 11) % 10 == digits[10]
+}
+
+// Normaliza o documento de um fornecedor, um CNPJ ou um CPF, formatado ou não, para apenas os seus
+// dígitos, como é armazenado nas despesas. Os dígitos verificadores não são conferidos, já que os
+// documentos dos fornecedores são armazenados tal como aparecem nos arquivos da CEAP.
+pub fn normaliza_documento(documento: &str) -> Option<String> {
+    let documento = documento.trim();
+    if !documento.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '/')) { return None; }
+
+    let digitos: String = documento.chars().filter(char::is_ascii_digit).collect();
+    matches!(digitos.len(), 11 | 14).then_some(digitos)
+}
+
+// Verifica se um CNPJ, composto apenas pelos seus 14 dígitos, é válido. CNPJs com todos os dígitos
+// iguais satisfazem os dígitos verificadores, mas são usados como marcadores de documento ausente,
+// e por isso são rejeitados.
+pub fn valida_cnpj(cnpj: &str) -> bool {
+    if cnpj.len() != 14 || !cnpj.bytes().all(|c| c.is_ascii_digit()) { return false; }
+
+    let digits: Vec<u32> = cnpj.bytes().map(|c| (c - b'0') as u32).collect();
+    if digits.iter().all(|d| *d == digits[0]) { return false; }
+
+    let digito_verificador = |n: usize| {
+        let soma: u32 = digits[..n]
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, digit)| digit * (2 + (i as u32 % 8)))
+            .sum();
+
+        match soma % 11 {
+            0 | 1 => 0,
+            resto => 11 - resto,
+        }
+    };
+
+    digito_verificador(12) == digits[12] && digito_verificador(13) == digits[13]
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -52,7 +85,7 @@ mod tests {
 
     #[test]
     fn aceita_cpf_valido_com_9_digitos() {
-        assert!(valida_cpf("770338410"));
+        assert_eq!(normaliza_cpf("770338410").as_deref(), Some("00770338410"));
     }
 
     #[test]
@@ -60,10 +93,48 @@ mod tests {
         assert_eq!(valida_cpf(""), false);
         assert_eq!(valida_cpf("12"), false);
         assert_eq!(valida_cpf("7703384"), false);
+        assert_eq!(valida_cpf("770338410"), false);
+        assert_eq!(normaliza_cpf("7703384"), None);
+        assert_eq!(normaliza_cpf("529982247250"), None);
+    }
+
+    #[test]
+    fn normaliza_documento_de_fornecedor() {
+        assert_eq!(normaliza_documento("02.012.862/0001-60").as_deref(), Some("02012862000160"));
+        assert_eq!(normaliza_documento("52998224725").as_deref(), Some("52998224725"));
+        assert_eq!(normaliza_documento("123"), None);
+        assert_eq!(normaliza_documento("CNPJ-fornecedor"), None);
+    }
+
+    #[test]
+    fn normaliza_cpf_formatado_ou_sem_zeros_a_esquerda() {
+        assert_eq!(normaliza_cpf("529.982.247-25").as_deref(), Some("52998224725"));
+        assert_eq!(normaliza_cpf(" 52998224725 ").as_deref(), Some("52998224725"));
+        assert_eq!(normaliza_cpf("2673718613").as_deref(), Some("02673718613"));
+        assert_eq!(normaliza_cpf("026.737.186-13").as_deref(), Some("02673718613"));
+
+        // Os zeros são acrescentados à esquerda, e não à direita.
+        assert_eq!(normaliza_cpf("2673718605"), None);
+        assert_eq!(normaliza_cpf("529.982.247/25"), None);
+        assert_eq!(normaliza_cpf("00000000000"), None);
     }
 
     #[test]
     fn rejeita_cpf_invalido() {
         assert_eq!(valida_cpf("12345678900"), false);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn aceita_cnpj_valido() {
+        assert!(valida_cnpj("11222333000181"));
+        assert!(valida_cnpj("02012862000160"));
+    }
+
+    #[test]
+    fn rejeita_cnpj_invalido() {
+        assert!(!valida_cnpj("11222333000182"));
+        assert!(!valida_cnpj("00000000000000"));
+        assert!(!valida_cnpj("1122233300018"));
+        assert!(!valida_cnpj("11.222.333/0001-81"));
+    }
+}
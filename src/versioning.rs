@@ -0,0 +1,268 @@
+use std::{convert::Infallible, future::{ready, Ready}};
+
+use actix_web::{body::{BoxBody, MessageBody}, dev::{HttpServiceFactory, Payload, ServiceRequest, ServiceResponse}, http::header::{self, ContentType, HeaderName, HeaderValue}, middleware::{from_fn, Next}, web, Error, FromRequest, HttpRequest, HttpResponse, HttpResponseBuilder};
+use serde::Serialize;
+
+use crate::{error::{ApiError, ErrorBody}, models::pagination::{Links, Pagina, PaginaNumerada}, routes};
+
+// A API REST é servida em duas versões, pelos mesmos handlers, de modo que as correções valem para
+// ambas:
+//
+// - v1, sob /v1 e, por compatibilidade, também sem prefixo, mantém os formatos originais das
+//   respostas e dos erros. Está obsoleta: as respostas trazem os cabeçalhos Deprecation (RFC 9745),
+//   com a data em que a v2 foi publicada, e Link, com o endereço correspondente na v2
+//   (rel="successor-version").
+// - v2, sob /v2, envolve as respostas em JSON num envelope: {"data": ...}, acrescido de "meta" (o
+//   total, o tamanho da página e os cursores) e "links" nas listagens paginadas; os erros passam a
+//   {"error": {"code": ..., "message": ..., "details": ...}}, inclusive os das rotas inexistentes.
+//   As respostas em outros formatos (CSV, XLSX, NDJSON) e as sem corpo não mudam.
+//
+// Os handlers recebem a versão (Versao) e montam o corpo das respostas de acordo com ela; os erros
+// são envolvidos pelo middleware da v2, a partir do ApiError retornado pelo handler.
+
+// Versão da API que atende a requisição, definida pelo escopo em que o endpoint está montado; fora
+// dos escopos versionados, como nos testes dos handlers, a v1.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Versao {
+    #[default]
+    V1,
+    V2,
+}
+
+impl FromRequest for Versao {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Infallible>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(req.app_data::<Versao>().copied().unwrap_or_default()))
+    }
+}
+
+// Envelope das respostas da v2.
+#[derive(Serialize)]
+struct Envelope<'a, D, M> {
+    data: &'a D,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meta: Option<M>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    links: Option<&'a Links>,
+}
+
+// Metadados de uma página de uma listagem paginada por cursor.
+#[derive(Serialize)]
+struct MetaPagina<'a> {
+    total: i64,
+    page_size: i64,
+    next: &'a Option<String>,
+    prev: &'a Option<String>,
+}
+
+// Metadados de uma página de uma listagem paginada por número.
+#[derive(Serialize)]
+struct MetaPaginaNumerada {
+    total: i64,
+    page: u32,
+    page_size: i64,
+}
+
+#[derive(Serialize)]
+struct Erro<'a> {
+    error: ErrorBody<'a>,
+}
+
+impl Versao {
+    // Resposta 200 com o valor em JSON.
+    pub fn json<T: Serialize>(self, valor: &T) -> Result<HttpResponse, ApiError> {
+        self.responde(HttpResponse::Ok(), valor)
+    }
+
+    // Resposta com o status de `resposta` e o valor em JSON, envolvido em {"data": ...} na v2.
+    pub fn responde<T: Serialize>(self, resposta: HttpResponseBuilder, valor: &T) -> Result<HttpResponse, ApiError> {
+        match self {
+            Versao::V1 => corpo(resposta, valor),
+            Versao::V2 => corpo(resposta, &Envelope::<_, ()> { data: valor, meta: None, links: None }),
+        }
+    }
+
+    // Resposta 200 com uma página de uma listagem paginada por cursor.
+    pub fn pagina<T: Serialize>(self, pagina: &Pagina<T>) -> Result<HttpResponse, ApiError> {
+        match self {
+            Versao::V1 => corpo(HttpResponse::Ok(), pagina),
+            Versao::V2 => corpo(HttpResponse::Ok(), &Envelope {
+                data: &pagina.dados,
+                meta: Some(MetaPagina { total: pagina.total, page_size: pagina.page_size, next: &pagina.next, prev: &pagina.prev }),
+                links: Some(&pagina.links),
+            }),
+        }
+    }
+
+    // Resposta 200 com uma página de uma listagem paginada por número.
+    pub fn pagina_numerada<T: Serialize>(self, pagina: &PaginaNumerada<T>) -> Result<HttpResponse, ApiError> {
+        match self {
+            Versao::V1 => corpo(HttpResponse::Ok(), pagina),
+            Versao::V2 => corpo(HttpResponse::Ok(), &Envelope {
+                data: &pagina.dados,
+                meta: Some(MetaPaginaNumerada { total: pagina.total, page: pagina.page, page_size: pagina.page_size }),
+                links: None,
+            }),
+        }
+    }
+}
+
+fn corpo<T: Serialize>(mut resposta: HttpResponseBuilder, valor: &T) -> Result<HttpResponse, ApiError> {
+    Ok(resposta.content_type(ContentType::json()).body(serde_json::to_string(valor)?))
+}
+
+pub fn v1(prefixo: &str) -> impl HttpServiceFactory {
+    web::scope(prefixo)
+        .app_data(Versao::V1)
+        .wrap(from_fn(obsoleta))
+        .configure(routes::configure)
+}
+
+pub fn v2() -> impl HttpServiceFactory {
+    web::scope("/v2")
+        .app_data(Versao::V2)
+        .wrap(from_fn(envelope))
+        .configure(routes::configure)
+        .default_service(web::to(rota_inexistente))
+}
+
+async fn rota_inexistente() -> Result<HttpResponse, ApiError> {
+    Err(ApiError::NotFound("Recurso não encontrado.".to_string()))
+}
+
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+
+// Momento em que a v1 se tornou obsoleta, com a publicação da v2 (19/10/2026, 00:00 UTC), no formato
+// do cabeçalho Deprecation: @ seguido do Unix time.
+const OBSOLETA_DESDE: &str = "@1792368000";
+
+async fn obsoleta(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, Error> {
+    let caminho = req.path().strip_prefix("/v1").unwrap_or(req.path());
+    let sucessora = match req.query_string() {
+        "" => format!("</v2{caminho}>; rel=\"successor-version\""),
+        query => format!("</v2{caminho}?{query}>; rel=\"successor-version\""),
+    };
+
+    let mut res = next.call(req).await?;
+    res.headers_mut().insert(DEPRECATION, HeaderValue::from_static(OBSOLETA_DESDE));
+    if let Ok(link) = HeaderValue::from_str(&sucessora) {
+        res.headers_mut().append(header::LINK, link);
+    }
+
+    Ok(res.map_into_boxed_body())
+}
+
+// Envolve em {"error": ...} os erros da v2, retornados pelos handlers, pelos extratores dos
+// parâmetros ou pela rota inexistente; o status e os cabeçalhos da resposta são mantidos.
+async fn envelope(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, Error> {
+    let res = next.call(req).await?;
+
+    let corpo = match res.response().error().and_then(|e| e.as_error::<ApiError>()) {
+        Some(erro) => serde_json::to_vec(&Erro { error: erro.body() })?,
+        None => return Ok(res.map_into_boxed_body()),
+    };
+
+    Ok(res.map_body(|_, _| BoxBody::new(corpo)))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{config::UploadConfig, routes::{json_config, path_config, query_config, tests::build_test_connection_pool}};
+
+    fn get_csv() -> &'static str {
+        "txNomeParlamentar;cpf;ideCadastro;nuCarteiraParlamentar;nuLegislatura;sgUF;sgPartido;codLegislatura;numSubCota;txtDescricao;numEspecificacaoSubCota;txtDescricaoEspecificacao;txtFornecedor;txtCNPJCPF;txtNumero;indTipoDocumento;datEmissao;vlrDocumento;vlrGlosa;vlrLiquido;numMes;numAno;numParcela;txtPassageiro;txtTrecho;numLote;numRessarcimento;datPagamentoRestituicao;vlrRestituicao;nuDeputadoId;ideDocumento;urlDocumento
+Jorge;22488012033;;;2023;PB;PSB;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;2025-01-10T00:00:00;1467;0;120.50;1;2025;0;;;0;;;;0;0;
+Zé;71838787089;;;2023;RJ;PT;57;1;COMBUSTÍVEIS;0;;POSTO IPIRANGA;;1984;0;2025-02-20T00:00:00;1467;0;200;2;2025;0;;;0;;;;0;0;"
+    }
+
+    #[actix_web::test]
+    async fn serve_both_versions_with_the_same_handlers() {
+        let pool = build_test_connection_pool().unwrap();
+        crate::import::process_csv(&mut pool.get().unwrap(), get_csv().as_bytes()).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .service(v1("/v1"))
+                .service(v2())
+                .service(v1(""))
+                .app_data(path_config())
+                .app_data(query_config())
+                .app_data(json_config())
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(UploadConfig::default()))
+        ).await;
+
+        // A v1, com ou sem prefixo, mantém o formato original, mas é marcada como obsoleta.
+        for uri in ["/despesas/soma", "/v1/despesas/soma"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let response = test::call_service(&app, req).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers().get("Deprecation").unwrap(), "@1792368000");
+            assert_eq!(response.headers().get(header::LINK).unwrap(), "</v2/despesas/soma>; rel=\"successor-version\"");
+            let soma: Value = test::read_body_json(response).await;
+            assert_eq!(soma["soma"], "320.50");
+        }
+
+        let req = test::TestRequest::get().uri("/v2/despesas/soma").to_request();
+        let response = test::call_service(&app, req).await;
+        assert!(response.headers().get("Deprecation").is_none());
+        let soma: Value = test::read_body_json(response).await;
+        assert_eq!(soma["data"]["soma"], "320.50");
+
+        let req = test::TestRequest::get().uri("/v1/deputados?ordem=total&page_size=1").to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(
+            response.headers().get(header::LINK).unwrap(),
+            "</v2/deputados?ordem=total&page_size=1>; rel=\"successor-version\"",
+        );
+
+        let req = test::TestRequest::get().uri("/v2/deputados?ordem=total&direcao=desc&page_size=1").to_request();
+        let pagina: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(pagina["data"][0]["nome"], "Zé");
+        assert_eq!(pagina["meta"], json!({ "total": 2, "page": 1, "page_size": 1 }));
+
+        let req = test::TestRequest::get().uri("/v2/despesas?page_size=1").to_request();
+        let pagina: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(pagina["data"][0]["valor_liquido"], "120.50");
+        assert_eq!(pagina["meta"]["total"], 2);
+        let next = pagina["links"]["next"].as_str().unwrap();
+        assert!(next.starts_with("/v2/despesas?page_size=1&cursor="), "{next}");
+
+        // Os erros seguem o formato de cada versão.
+        let req = test::TestRequest::get().uri("/v1/deputados/999").to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let erro: Value = test::read_body_json(response).await;
+        assert_eq!(erro["code"], "not_found");
+
+        let req = test::TestRequest::get().uri("/v2/despesas/uf/XX").to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let erro: Value = test::read_body_json(response).await;
+        assert_eq!(erro["error"]["code"], "bad_request");
+
+        // Inclusive os dos extratores e os das rotas inexistentes.
+        let req = test::TestRequest::get().uri("/v2/deputados?page=x").to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let erro: Value = test::read_body_json(response).await;
+        assert_eq!(erro["error"]["code"], "bad_request");
+
+        let req = test::TestRequest::get().uri("/v2/inexistente").to_request();
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let erro: Value = test::read_body_json(response).await;
+        assert_eq!(erro, json!({ "error": { "code": "not_found", "message": "Recurso não encontrado." } }));
+
+        // As exportações não mudam.
+        let req = test::TestRequest::get().uri("/v2/deputados?format=csv").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert!(body.starts_with(b"id;"), "{}", String::from_utf8_lossy(&body));
+    }
+}